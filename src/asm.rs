@@ -8,10 +8,12 @@ use crate::ch8::{
 use std::{
     cell::Cell,
     fmt::{Display, Write},
+    ops::Range,
     time::Instant,
 };
 
 pub const INSTRUCTION_COLUMNS: usize = 29;
+#[cfg(feature = "debugger")]
 pub const INSTRUCTION_MAX_LENGTH: usize = 13;
 pub const ADDRESS_COMMENT_TOKEN: &'static str = "#";
 
@@ -130,7 +132,15 @@ pub enum TraceError {
 
 impl From<Rom> for Disassembler {
     fn from(rom: Rom) -> Self {
-        let memory = allocate_memory(&rom);
+        let memory_size = rom.config.kind.default_memory_size();
+        Disassembler::with_memory_size(rom, memory_size)
+    }
+}
+
+impl Disassembler {
+    // the disassembly must cover the same address space as the interpreter it annotates
+    pub fn with_memory_size(rom: Rom, memory_size: usize) -> Self {
+        let memory = allocate_memory(&rom, memory_size);
         let mut disassembler = Disassembler {
             instruction_params: Vec::with_capacity(memory.len()),
             instructions: Vec::with_capacity(memory.len()),
@@ -144,9 +154,7 @@ impl From<Rom> for Disassembler {
         disassembler.reset();
         disassembler
     }
-}
 
-impl Disassembler {
     pub fn reset(&mut self) {
         self.instruction_params.clear();
         self.instructions.clear();
@@ -188,61 +196,6 @@ impl Disassembler {
             .max(self.rom.config.kind)
     }
 
-    pub fn rerun(&mut self) {
-        self.tags
-            .iter_mut()
-            .for_each(|tag| *tag = (*tag).min(InstructionTag::Parsable));
-        self.traces.clear();
-        self.run();
-    }
-
-    // it is assumed that bytes > 0 and bytes <= 16
-    pub fn needs_rerun(&mut self, interp: &Interpreter, mut index: u16, mut bytes: u16) -> bool {
-        let memory = &interp.memory;
-        let mut disasm_required = false;
-
-        let mut instruction_bytes = [0; Instruction::MAX_INSTRUCTION_SIZE as usize];
-
-        index = memory.address_sub(index, Instruction::MAX_INSTRUCTION_SIZE - 1);
-        bytes += Instruction::MAX_INSTRUCTION_SIZE - 1;
-
-        for i in 0..bytes {
-            let addr = memory.address_add(index, i);
-            memory.export(addr, &mut instruction_bytes);
-
-            let instruction_param_bits = u32::from_be_bytes(instruction_bytes);
-            if instruction_param_bits == self.instruction_params[addr as usize].bits {
-                continue;
-            }
-
-            let instruction_params = InstructionParameters::new(instruction_param_bits);
-            let instruction = instruction_params.try_decode(interp.rom.config.kind).ok();
-
-            log::debug!(
-                "Updating instruction at {:#05X} ({:04X})",
-                addr,
-                instruction_params.significant_bytes(Instruction::size_or_default(&instruction))
-            );
-
-            let old_tag = self.tags[addr as usize];
-
-            disasm_required = disasm_required
-                || old_tag > InstructionTag::Parsable
-                || old_tag < InstructionTag::Parsable && instruction.is_some();
-
-            self.memory[addr as usize] = instruction_bytes[0];
-            self.instruction_params[addr as usize] = instruction_params;
-            self.instructions[addr as usize] = instruction;
-            self.tags[addr as usize] = if instruction.is_some() {
-                InstructionTag::Parsable
-            } else {
-                InstructionTag::Not
-            };
-        }
-
-        disasm_required
-    }
-
     pub fn run(&mut self) {
         self.run_from(PROGRAM_STARTING_ADDRESS, InstructionTag::Proven, 0);
     }
@@ -476,11 +429,17 @@ impl Disassembler {
         write_byte_str(&mut f.bin, byte, 1).ok();
 
         if let Some(instruction) = instruction.as_ref() {
-            write_inst_dasm(
+            let AddressFormatter { asm, asm_tokens, asm_desc, .. } = &mut f;
+            write_inst_tokens(
                 instruction,
                 self.rom.config,
-                &mut f.asm,
-                &mut f.asm_desc,
+                &mut |token, text| {
+                    let start = asm.len();
+                    asm.push_str(text);
+                    asm_tokens.push((token, start..asm.len()));
+                    Ok(())
+                },
+                asm_desc,
             )?;
         }
 
@@ -586,6 +545,132 @@ impl Disassembler {
     }
 }
 
+#[cfg(feature = "debugger")]
+impl Disassembler {
+    pub fn rerun(&mut self) {
+        self.tags
+            .iter_mut()
+            .for_each(|tag| *tag = (*tag).min(InstructionTag::Parsable));
+        self.traces.clear();
+        self.run();
+    }
+
+    // it is assumed that bytes > 0 and bytes <= 16
+    pub fn needs_rerun(&mut self, interp: &Interpreter, mut index: u16, mut bytes: u16) -> bool {
+        let memory = &interp.memory;
+        let mut disasm_required = false;
+
+        let mut instruction_bytes = [0; Instruction::MAX_INSTRUCTION_SIZE as usize];
+
+        index = memory.address_sub(index, Instruction::MAX_INSTRUCTION_SIZE - 1);
+        bytes += Instruction::MAX_INSTRUCTION_SIZE - 1;
+
+        for i in 0..bytes {
+            let addr = memory.address_add(index, i);
+            memory.export(addr, &mut instruction_bytes);
+
+            let instruction_param_bits = u32::from_be_bytes(instruction_bytes);
+            if instruction_param_bits == self.instruction_params[addr as usize].bits {
+                continue;
+            }
+
+            let instruction_params = InstructionParameters::new(instruction_param_bits);
+            let instruction = instruction_params.try_decode(interp.rom.config.kind).ok();
+
+            log::debug!(
+                "Updating instruction at {:#05X} ({:04X})",
+                addr,
+                instruction_params.significant_bytes(Instruction::size_or_default(&instruction))
+            );
+
+            let old_tag = self.tags[addr as usize];
+
+            disasm_required = disasm_required
+                || old_tag > InstructionTag::Parsable
+                || old_tag < InstructionTag::Parsable && instruction.is_some();
+
+            self.memory[addr as usize] = instruction_bytes[0];
+            self.instruction_params[addr as usize] = instruction_params;
+            self.instructions[addr as usize] = instruction;
+            self.tags[addr as usize] = if instruction.is_some() {
+                InstructionTag::Parsable
+            } else {
+                InstructionTag::Not
+            };
+        }
+
+        disasm_required
+    }
+}
+
+pub struct DecodeCoverage {
+    pub words: usize,
+    pub decodable_words: usize,
+    pub undecodable_ranges: Vec<(u16, u16)>, // end exclusive
+}
+
+// counts the program words of the interpreter's rom that decode without following control flow, which says how
+// much of it could be code at all. runs of words that fail to decode are most likely data. the second word of a
+// long instruction counts as decoded with it rather than on its own
+impl From<&Interpreter> for DecodeCoverage {
+    fn from(interp: &Interpreter) -> Self {
+        let mut coverage = DecodeCoverage {
+            words: 0,
+            decodable_words: 0,
+            undecodable_ranges: Vec::new(),
+        };
+
+        let mut instructions = interp.decoded_instructions();
+        while let Some((addr, instruction)) = instructions.next() {
+            let words = match instruction {
+                Ok(instruction) => {
+                    let words = 1 + instructions.by_ref().take(instruction.size() as usize / 2 - 1).count();
+                    coverage.decodable_words += words;
+                    words
+                }
+                Err(_) => {
+                    match coverage.undecodable_ranges.last_mut() {
+                        Some((_, range_end)) if *range_end == addr => *range_end += 2,
+                        _ => coverage.undecodable_ranges.push((addr, addr + 2)),
+                    }
+                    1
+                }
+            };
+
+            coverage.words += words;
+        }
+
+        coverage
+    }
+}
+
+impl Display for DecodeCoverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = if self.words == 0 {
+            0.0
+        } else {
+            100.0 * self.decodable_words as f64 / self.words as f64
+        };
+
+        writeln!(
+            f,
+            "{} of {} words decode as instructions ({:.1}%)",
+            self.decodable_words, self.words, percent
+        )?;
+
+        if self.undecodable_ranges.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(f, "\nUNDECODABLE (likely data)")?;
+        for &(start, end) in self.undecodable_ranges.iter() {
+            writeln!(f, "  {:#05X}..{:#05X} ({} words)", start, end, (end - start) / 2)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Display for Disassembler {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for (addr, byte, tag) in self.tags[PROGRAM_STARTING_ADDRESS as usize..]
@@ -668,6 +753,7 @@ pub struct AddressFormatter {
     pub tag: String,
     pub bin: String,
     pub asm: String,
+    pub asm_tokens: Vec<(AsmToken, Range<usize>)>, // where each token of asm is so it can be styled
     pub asm_desc: String,
 }
 
@@ -678,6 +764,7 @@ impl AddressFormatter {
         self.tag.clear();
         self.bin.clear();
         self.asm.clear();
+        self.asm_tokens.clear();
         self.asm_desc.clear();
     }
 }
@@ -702,6 +789,190 @@ pub fn write_byte_str(
     Ok(())
 }
 
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum AsmToken {
+    Mnemonic,
+    Register,  // v0 through vf
+    Immediate, // decimal constants
+    Address,   // hex addresses
+    Keyword,   // named operands like i, dt, or k
+    Space,
+}
+
+// assembles a single 2 byte instruction written either as a raw opcode (e.g. 6005) or in the syntax of
+// write_inst_dasm (e.g. ld v0 5), where numbers may be decimal or 0x prefixed hex
+#[cfg(any(feature = "debugger", test))]
+pub fn assemble_inst(text: &str, config: RomConfig) -> Result<(u16, Instruction), String> {
+    let text = text.trim();
+    let hex_digits = text.strip_prefix("0x").unwrap_or(text);
+    if hex_digits.len() == 4 {
+        if let Ok(opcode) = u16::from_str_radix(hex_digits, 16) {
+            return match Instruction::try_from_u32((opcode as u32) << 16, config.kind) {
+                Ok(instruction) if instruction.size() == 2 => Ok((opcode, instruction)),
+                Ok(_) => Err(format!("{:04X} is not a 2 byte instruction", opcode)),
+                Err(e) => Err(e.to_string()),
+            };
+        }
+    }
+
+    let tokens: Vec<String> = text.split_whitespace().map(str::to_ascii_lowercase).collect();
+    let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+
+    // the encoding ignores the rom kind so decoding it is what checks that the kind has the instruction
+    let Some(opcode) = encode_inst_asm(&tokens, config) else {
+        return Err(format!("\"{}\" is not a {} instruction", text, config.kind));
+    };
+    match Instruction::try_from_u32((opcode as u32) << 16, config.kind) {
+        Ok(instruction) if instruction.size() == 2 => Ok((opcode, instruction)),
+        Ok(_) => Err(format!("\"{}\" is not a 2 byte instruction", text)),
+        Err(_) => Err(format!("\"{}\" is not a {} instruction", text, config.kind)),
+    }
+}
+
+// the opcode of lowercase tokens in the syntax of write_inst_dasm, or None if they are not an instruction
+// scu and scd are written without their amount so one given after them is optional and defaults to 0
+#[cfg(any(feature = "debugger", test))]
+fn encode_inst_asm(tokens: &[&str], config: RomConfig) -> Option<u16> {
+    let register = |token: &str| {
+        token
+            .strip_prefix('v')
+            .filter(|x| x.len() == 1)
+            .and_then(|x| u16::from_str_radix(x, 16).ok())
+    };
+    let number = |token: &str, max: u16| {
+        match token.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16).ok(),
+            None => token.parse::<u16>().ok(),
+        }
+        .filter(|&value| value <= max)
+    };
+    let x = |vx: &str| Some(register(vx)? << 8);
+    let xy = |vx: &str, vy: &str| Some(register(vx)? << 8 | register(vy)? << 4);
+    let is_register = |token: &str| register(token).is_some();
+
+    Some(match *tokens {
+        ["cls"] => 0x00E0,
+        ["ret"] => 0x00EE,
+        ["scd"] => 0x00C0,
+        ["scd", n] => 0x00C0 | number(n, 0xF)?,
+        ["scu"] => 0x00D0,
+        ["scu", n] => 0x00D0 | number(n, 0xF)?,
+        ["scr"] => 0x00FB,
+        ["scl"] => 0x00FC,
+        ["exit"] => 0x00FD,
+        ["low"] => 0x00FE,
+        ["high"] => 0x00FF,
+
+        ["jp", addr] => 0x1000 | number(addr, 0xFFF)?,
+        ["jp", vx, addr] => {
+            // the register is the high nibble of the address with the quirk and written as v0 without it
+            let addr = number(addr, 0xFFF)?;
+            let expected_vx = if config.quirks.jump_with_offset_uses_vx { addr >> 8 } else { 0 };
+            if register(vx)? != expected_vx {
+                return None;
+            }
+            0xB000 | addr
+        }
+        ["call", addr] => 0x2000 | number(addr, 0xFFF)?,
+
+        ["se", vx, vy] if is_register(vy) => 0x5000 | xy(vx, vy)?,
+        ["se", vx, nn] => 0x3000 | x(vx)? | number(nn, 0xFF)?,
+        ["sne", vx, vy] if is_register(vy) => 0x9000 | xy(vx, vy)?,
+        ["sne", vx, nn] => 0x4000 | x(vx)? | number(nn, 0xFF)?,
+        ["skp", vx] => 0xE09E | x(vx)?,
+        ["sknp", vx] => 0xE0A1 | x(vx)?,
+
+        ["ld", "a", "i"] => 0xF002,
+        ["ld", "i", vx, vy] => 0x5002 | xy(vx, vy)?,
+        ["ld", vx, vy, "i"] => 0x5003 | xy(vx, vy)?,
+        ["ld", "i", vx] if is_register(vx) => 0xF055 | x(vx)?,
+        ["ld", "i", addr] => 0xA000 | number(addr, 0xFFF)?,
+        ["ld", vx, "i"] => 0xF065 | x(vx)?,
+        ["ld", vx, "k"] => 0xF00A | x(vx)?,
+        ["ld", vx, "dt"] => 0xF007 | x(vx)?,
+        ["ld", vx, "r"] => 0xF085 | x(vx)?,
+        ["ld", "dt", vx] => 0xF015 | x(vx)?,
+        ["ld", "st", vx] => 0xF018 | x(vx)?,
+        ["ld", "f", vx] => 0xF029 | x(vx)?,
+        ["ld", "hf", vx] => 0xF030 | x(vx)?,
+        ["ld", "b", vx] => 0xF033 | x(vx)?,
+        ["ld", "p", vx] => 0xF03A | x(vx)?,
+        ["ld", "r", vx] => 0xF075 | x(vx)?,
+        ["ld", vx, vy] if is_register(vy) => 0x8000 | xy(vx, vy)?,
+        ["ld", vx, nn] => 0x6000 | x(vx)? | number(nn, 0xFF)?,
+        ["lld", "i", addr] => number(addr, 0xFFFF).map(|_| 0xF000)?,
+
+        ["add", "i", n] => 0xF01E | number(n, 0xF)? << 8,
+        ["add", vx, vy] if is_register(vy) => 0x8004 | xy(vx, vy)?,
+        ["add", vx, nn] => 0x7000 | x(vx)? | number(nn, 0xFF)?,
+        ["or", vx, vy] => 0x8001 | xy(vx, vy)?,
+        ["and", vx, vy] => 0x8002 | xy(vx, vy)?,
+        ["xor", vx, vy] => 0x8003 | xy(vx, vy)?,
+        ["sub", vx, vy] => 0x8005 | xy(vx, vy)?,
+        ["shr", vx, vy] => 0x8006 | xy(vx, vy)?,
+        ["subn", vx, vy] => 0x8007 | xy(vx, vy)?,
+        ["shl", vx, vy] => 0x800E | xy(vx, vy)?,
+
+        ["rnd", vx, nn] => 0xC000 | x(vx)? | number(nn, 0xFF)?,
+        ["drw", vx, vy, n] => 0xD000 | xy(vx, vy)? | number(n, 0xF)?,
+        ["pln", n] => 0xF001 | number(n, 0xF)? << 8,
+
+        _ => return None,
+    })
+}
+
+// encodes a sprite drawn as rows of ascii art where # or x is a lit pixel and . is an unlit one (e.g. ..##..)
+// x is accepted since the debugger shell reads a word starting with # as a comment
+// rows up to 8 pixels wide are one byte each and wider rows are two bytes each for the 16x16 sprites of SCHIP
+// and XO-CHIP. pixels are left aligned in their bytes which is how draw reads them
+#[cfg(any(feature = "debugger", test))]
+pub fn assemble_sprite<S: AsRef<str>>(rows: &[S], kind: RomKind) -> Result<Vec<u8>, String> {
+    let Some(first) = rows.first() else {
+        return Err(String::from("Sprite has no rows"))
+    };
+
+    // rows are measured in characters since a pixel is a character however many bytes it takes
+    let width = first.as_ref().chars().count();
+    let max_width = if kind >= RomKind::SCHIP { 16 } else { 8 };
+    if width == 0 || width > max_width {
+        return Err(format!(
+            "Sprite rows must be 1 to {} pixels wide for {} but \"{}\" is {}",
+            max_width,
+            kind,
+            first.as_ref(),
+            width
+        ));
+    }
+
+    let bytes_per_row = if width > 8 { 2 } else { 1 };
+    let mut bytes = Vec::with_capacity(rows.len() * bytes_per_row);
+
+    for row in rows.iter().map(AsRef::as_ref) {
+        let row_width = row.chars().count();
+        if row_width != width {
+            return Err(format!(
+                "Sprite rows must all be {} pixels wide but \"{}\" is {}",
+                width,
+                row,
+                row_width
+            ));
+        }
+
+        let mut bits = 0u16;
+        for (i, c) in row.chars().enumerate() {
+            match c {
+                '#' | 'x' | 'X' => bits |= 0x8000 >> i,
+                '.' => (),
+                _ => return Err(format!("Sprite row \"{}\" has '{}' where #, x, or . was expected", row, c)),
+            }
+        }
+
+        bytes.extend_from_slice(&bits.to_be_bytes()[..bytes_per_row]);
+    }
+
+    Ok(bytes)
+}
+
 // TODO change this to quirks instead of rom kind
 pub fn write_inst_dasm(
     inst: &Instruction,
@@ -709,204 +980,433 @@ pub fn write_inst_dasm(
     f: &mut impl std::fmt::Write,
     c: &mut impl std::fmt::Write,
 ) -> std::fmt::Result {
-    match inst {
+    write_inst_tokens(inst, config, &mut |_, text| f.write_str(text), c)
+}
+
+// an operand as it is written in assembly, in the form assemble_inst reads back
+enum Operand {
+    Register(u8),
+    Immediate(u8),
+    Address(u16),
+    LongAddress(u16),
+    Keyword(&'static str),
+}
+
+// emits the mnemonic and operands as tokens with the operands lined up after the longest mnemonic
+fn write_asm_tokens(
+    sink: &mut impl FnMut(AsmToken, &str) -> std::fmt::Result,
+    mnemonic: &str,
+    operands: &[Operand],
+) -> std::fmt::Result {
+    use Operand::*;
+
+    sink(AsmToken::Mnemonic, mnemonic)?;
+
+    let mut text = String::new();
+    for (i, operand) in operands.iter().enumerate() {
+        let spaces = if i == 0 { 5usize.saturating_sub(mnemonic.len()).max(1) } else { 1 };
+        sink(AsmToken::Space, &"     "[..spaces])?;
+
+        text.clear();
+        let token = match *operand {
+            Register(vx) => {
+                write!(text, "v{:x}", vx)?;
+                AsmToken::Register
+            }
+            Immediate(value) => {
+                write!(text, "{}", value)?;
+                AsmToken::Immediate
+            }
+            Address(addr) => {
+                write!(text, "{:#05X}", addr)?;
+                AsmToken::Address
+            }
+            LongAddress(addr) => {
+                write!(text, "{:#06X}", addr)?;
+                AsmToken::Address
+            }
+            Keyword(keyword) => {
+                text.push_str(keyword);
+                AsmToken::Keyword
+            }
+        };
+        sink(token, &text)?;
+    }
+
+    Ok(())
+}
+
+// the same as write_inst_dasm but the assembly goes to the sink a token at a time so it can be styled by token,
+// spaces are their own tokens so joining the text gives back the plain string
+pub fn write_inst_tokens(
+    inst: &Instruction,
+    config: RomConfig,
+    f: &mut impl FnMut(AsmToken, &str) -> std::fmt::Result,
+    c: &mut impl std::fmt::Write,
+) -> std::fmt::Result {
+    use Operand::*;
+
+    match *inst {
         // side effect of discontinuity instructions having no comments is it highlights a clear break in execution
-        Instruction::Exit => write!(f, "exit"),
-        Instruction::Jump(addr) => write!(f, "jp   {:#05X}", addr),
-        Instruction::JumpWithOffset(addr, x) => write!(
+        Instruction::Exit => write_asm_tokens(f, "exit", &[]),
+        Instruction::Jump(addr) => write_asm_tokens(f, "jp", &[Address(addr)]),
+        Instruction::JumpWithOffset(addr, x) => write_asm_tokens(
             f,
-            "jp   v{:x} {:#05X}",
-            if config.quirks.jump_with_offset_uses_vx { *x } else { 0 },
-            addr
+            "jp",
+            &[
+                Register(if config.quirks.jump_with_offset_uses_vx { x } else { 0 }),
+                Address(addr),
+            ],
         ),
-        Instruction::CallSubroutine(addr) => write!(f, "call {:#05X}", addr),
-        Instruction::SubroutineReturn => write!(f, "ret"),
+        Instruction::CallSubroutine(addr) => write_asm_tokens(f, "call", &[Address(addr)]),
+        Instruction::SubroutineReturn => write_asm_tokens(f, "ret", &[]),
 
         Instruction::SkipIfEqualsConstant(vx, value) => {
-            write!(f, "se   v{:x} {}", vx, value)?;
+            write_asm_tokens(f, "se", &[Register(vx), Immediate(value)])?;
             write!(c, "skip if v{:x} == {}", vx, value)
         }
         Instruction::SkipIfNotEqualsConstant(vx, value) => {
-            write!(f, "sne  v{:x} {}", vx, value)?;
+            write_asm_tokens(f, "sne", &[Register(vx), Immediate(value)])?;
             write!(c, "skip if v{:x} != {}", vx, value)
         }
         Instruction::SkipIfEquals(vx, vy) => {
-            write!(f, "se   v{:x} v{:x}", vx, vy)?;
+            write_asm_tokens(f, "se", &[Register(vx), Register(vy)])?;
             write!(c, "skip if v{:x} == v{:x}", vx, vy)
         }
         Instruction::SkipIfNotEquals(vx, vy) => {
-            write!(f, "sne  v{:x} v{:x}", vx, vy)?;
+            write_asm_tokens(f, "sne", &[Register(vx), Register(vy)])?;
             write!(c, "skip if v{:x} != v{:x}", vx, vy)
         }
         Instruction::SkipIfKeyDown(vx) => {
-            write!(f, "skp  v{:x}", vx)?;
+            write_asm_tokens(f, "skp", &[Register(vx)])?;
             write!(c, "skip if v{:x} keydown", vx)
         }
         Instruction::SkipIfKeyNotDown(vx) => {
-            write!(f, "sknp v{:x}", vx)?;
+            write_asm_tokens(f, "sknp", &[Register(vx)])?;
             write!(c, "skip if v{:x} keyup", vx)
         }
         Instruction::WaitForKey(vx) => {
-            write!(f, "ld   v{:x} k", vx)?;
+            write_asm_tokens(f, "ld", &[Register(vx), Keyword("k")])?;
             write!(c, "v{:x} = next keypress", vx)
         }
         Instruction::SetConstant(vx, value) => {
-            write!(f, "ld   v{:x} {}", vx, value)?;
+            write_asm_tokens(f, "ld", &[Register(vx), Immediate(value)])?;
             write!(c, "v{:x} = {}", vx, value)
         }
         Instruction::AddConstant(vx, value) => {
-            write!(f, "add  v{:x} {}", vx, value)?;
+            write_asm_tokens(f, "add", &[Register(vx), Immediate(value)])?;
             write!(c, "v{:x} += {}", vx, value)
         }
         Instruction::Set(vx, vy) => {
-            write!(f, "ld   v{:x} v{:x}", vx, vy)?;
+            write_asm_tokens(f, "ld", &[Register(vx), Register(vy)])?;
             write!(c, "v{:x} = v{:x}", vx, vy)
         }
         Instruction::Or(vx, vy) => {
-            write!(f, "or   v{:x} v{:x}", vx, vy)?;
+            write_asm_tokens(f, "or", &[Register(vx), Register(vy)])?;
             write!(c, "v{:x} |= v{:x}", vx, vy)
         }
         Instruction::And(vx, vy) => {
-            write!(f, "and  v{:x} v{:x}", vx, vy)?;
+            write_asm_tokens(f, "and", &[Register(vx), Register(vy)])?;
             write!(c, "v{:x} &= v{:x}", vx, vy)
         }
         Instruction::Xor(vx, vy) => {
-            write!(f, "xor  v{:x} v{:x}", vx, vy)?;
+            write_asm_tokens(f, "xor", &[Register(vx), Register(vy)])?;
             write!(c, "v{:x} ^= v{:x}", vx, vy)
         }
         Instruction::Add(vx, vy) => {
-            write!(f, "add  v{:x} v{:x}", vx, vy)?;
+            write_asm_tokens(f, "add", &[Register(vx), Register(vy)])?;
             write!(c, "v{:x} += v{:x}", vx, vy)
         }
         Instruction::Sub(vx, vy, vx_minus_vy) => {
-            if *vx_minus_vy {
-                write!(f, "sub  v{:x} v{:x}", vx, vy)?;
+            if vx_minus_vy {
+                write_asm_tokens(f, "sub", &[Register(vx), Register(vy)])?;
                 write!(c, "v{:x} -= v{:x}", vx, vy)
             } else {
-                write!(f, "subn v{:x} v{:x}", vx, vy)?;
+                write_asm_tokens(f, "subn", &[Register(vx), Register(vy)])?;
                 write!(c, "v{:x} = v{:x} - v{:x}", vx, vy, vx)
             }
         }
         Instruction::Shift(vx, vy, right) => {
-            if *right {
-                write!(f, "shr  v{:x} v{:x}", vx, vy)?;
+            if right {
+                write_asm_tokens(f, "shr", &[Register(vx), Register(vy)])?;
                 write!(c, "v{:x} = v{:x} >> 1", vx, vy)
             } else {
-                write!(f, "shl  v{:x} v{:x}", vx, vy)?;
+                write_asm_tokens(f, "shl", &[Register(vx), Register(vy)])?;
                 write!(c, "v{:x} = v{:x} << 1", vx, vy)
             }
         }
         Instruction::GetDelayTimer(vx) => {
-            write!(f, "ld   v{:x} dt", vx)?;
+            write_asm_tokens(f, "ld", &[Register(vx), Keyword("dt")])?;
             write!(c, "v{:x} = delay timer", vx)
         }
         Instruction::SetDelayTimer(vx) => {
-            write!(f, "ld   dt v{:x}", vx)?;
+            write_asm_tokens(f, "ld", &[Keyword("dt"), Register(vx)])?;
             write!(c, "delay timer = v{:x}", vx)
         }
         Instruction::SetSoundTimer(vx) => {
-            write!(f, "ld   st v{:x}", vx)?;
+            write_asm_tokens(f, "ld", &[Keyword("st"), Register(vx)])?;
             write!(c, "sound timer = v{:x}", vx)
         }
         Instruction::SetIndex(addr) => {
-            write!(f, "ld   i {:#05X}", addr)?;
+            write_asm_tokens(f, "ld", &[Keyword("i"), Address(addr)])?;
             write!(c, "i = {:#05X}", addr)
         }
         Instruction::SetIndexToLong(addr) => {
-            write!(f, "lld  i {:#06X}", addr)?;
+            write_asm_tokens(f, "lld", &[Keyword("i"), LongAddress(addr)])?;
             write!(c, "i = {:#06X}", addr)
         }
         Instruction::SetIndexToHexChar(vx) => {
-            write!(f, "ld   f v{:x}", vx)?;
+            write_asm_tokens(f, "ld", &[Keyword("f"), Register(vx)])?;
             write!(c, "i = hex v{:x}", vx)
         }
         Instruction::SetIndexToBigHexChar(vx) => {
-            write!(f, "ld   hf v{:x}", vx)?;
+            write_asm_tokens(f, "ld", &[Keyword("hf"), Register(vx)])?;
             write!(c, "i = big hex v{:x}", vx)
         }
         Instruction::AddToIndex(value) => {
-            write!(f, "add  i {}", value)?;
+            write_asm_tokens(f, "add", &[Keyword("i"), Immediate(value)])?;
             write!(c, "i += {} ({:#05X})", value, value)
         }
         Instruction::Load(vx) => {
-            write!(f, "ld   v{:x} i", vx)?;
+            write_asm_tokens(f, "ld", &[Register(vx), Keyword("i")])?;
             write!(c, "v0..={:x} <- mem", vx)
         }
         Instruction::Store(vx) => {
-            write!(f, "ld   i v{:x}", vx)?;
+            write_asm_tokens(f, "ld", &[Keyword("i"), Register(vx)])?;
             write!(c, "mem <- v0..={:x}", vx)
         }
         Instruction::LoadRange(vx, vy) => {
-            write!(f, "ld   v{:x} v{:x} i", vx, vy)?;
+            write_asm_tokens(f, "ld", &[Register(vx), Register(vy), Keyword("i")])?;
             write!(c, "v{:x}..={:x} <- mem", vx, vy)
         }
         Instruction::StoreRange(vx, vy) => {
-            write!(f, "ld   i v{:x} v{:x}", vx, vy)?;
+            write_asm_tokens(f, "ld", &[Keyword("i"), Register(vx), Register(vy)])?;
             write!(c, "mem <- v{:x}..={:x}", vx, vy)
         }
         Instruction::LoadFlags(vx) => {
-            write!(f, "ld   v{:x} r", vx)?;
+            write_asm_tokens(f, "ld", &[Register(vx), Keyword("r")])?;
             write!(c, "v0..={:x} <- flags", vx)
         }
         Instruction::StoreFlags(vx) => {
-            write!(f, "ld   r v{:x}", vx)?;
+            write_asm_tokens(f, "ld", &[Keyword("r"), Register(vx)])?;
             write!(c, "flags <- v0..={:x}", vx)
         }
         Instruction::StoreBinaryCodedDecimal(vx) => {
-            write!(f, "ld   b v{:x}", vx)?;
+            write_asm_tokens(f, "ld", &[Keyword("b"), Register(vx)])?;
             write!(c, "mem <- bcd v{:x}", vx)
         }
         Instruction::GenerateRandom(vx, bound) => {
-            write!(f, "rnd  v{:x} {}", vx, bound)?;
+            write_asm_tokens(f, "rnd", &[Register(vx), Immediate(bound)])?;
             write!(c, "v{:x} = rand 0..={}", vx, bound)
         }
         Instruction::SetPlane(n) => {
-            write!(f, "pln  {}", n)?;
+            write_asm_tokens(f, "pln", &[Immediate(n)])?;
             write!(c, "select plane {}", n)
         }
         Instruction::Draw(vx, vy, height) => {
-            write!(f, "drw  v{:x} v{:x} {}", vx, vy, height)?;
-            if *height == 0 && config.kind >= RomKind::SCHIP {
+            write_asm_tokens(f, "drw", &[Register(vx), Register(vy), Immediate(height)])?;
+            if height == 0 && config.kind >= RomKind::SCHIP {
                 write!(c, "draw 16x16 @ v{:x},v{:x}", vx, vy)
             } else {
                 write!(c, "draw 8x{} @ v{:x},v{:x}", height, vx, vy)
             }
         }
         Instruction::ScrollUp(n) => {
-            write!(f, "scu")?;
+            write_asm_tokens(f, "scu", &[])?;
             write!(c, "scroll {} up", n)
         }
         Instruction::ScrollDown(n) => {
-            write!(f, "scd")?;
+            write_asm_tokens(f, "scd", &[])?;
             write!(c, "scroll {} down", n)
         }
         Instruction::ScrollLeft => {
-            write!(f, "scl")?;
+            write_asm_tokens(f, "scl", &[])?;
             write!(c, "scroll left")
         }
         Instruction::ScrollRight => {
-            write!(f, "scr")?;
+            write_asm_tokens(f, "scr", &[])?;
             write!(c, "scroll right")
         }
         Instruction::LowResolution => {
-            write!(f, "low")?;
+            write_asm_tokens(f, "low", &[])?;
             write!(c, "lo-res display")
         }
         Instruction::HighResolution => {
-            write!(f, "high")?;
+            write_asm_tokens(f, "high", &[])?;
             write!(c, "hi-res display")
         }
         Instruction::ClearScreen => {
-            write!(f, "cls")?;
+            write_asm_tokens(f, "cls", &[])?;
             write!(c, "clear")
         }
         Instruction::LoadAudio => {
-            write!(f, "ld   a i")?;
+            write_asm_tokens(f, "ld", &[Keyword("a"), Keyword("i")])?;
             write!(c, "audio <- mem")
         }
         Instruction::SetPitch(vx) => {
-            write!(f, "ld   p v{:x}", vx)?;
+            write_asm_tokens(f, "ld", &[Keyword("p"), Register(vx)])?;
             write!(c, "pitch = v{:x}", vx)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembling_disassembly_gives_back_the_instruction() {
+        for kind in [RomKind::CLASSIC, RomKind::CHIP8, RomKind::SCHIP, RomKind::XOCHIP] {
+            let config = RomConfig {
+                kind,
+                quirks: kind.default_rom_quirks(),
+            };
+
+            let mut asm = String::new();
+            let mut comment = String::new();
+            for opcode in 0..=u16::MAX {
+                let Ok(instruction) = Instruction::try_from_u32((opcode as u32) << 16, kind) else {
+                    continue;
+                };
+
+                asm.clear();
+                write_inst_dasm(&instruction, config, &mut asm, &mut comment).ok();
+                match (instruction, assemble_inst(&asm, config)) {
+                    // the scroll amount is not written so it assembles to scrolling by 0
+                    (Instruction::ScrollUp(_), Ok(assembled)) => assert_eq!(assembled.0, 0x00D0),
+                    (Instruction::ScrollDown(_), Ok(assembled)) => assert_eq!(assembled.0, 0x00C0),
+                    (_, Ok(assembled)) => assert_eq!(assembled, (opcode, instruction), "{} on {}", asm, kind),
+                    (_, Err(e)) => {
+                        assert_eq!(instruction.size(), 4, "{} on {}: {}", asm, kind, e);
+                        assert_eq!(e, format!("\"{}\" is not a 2 byte instruction", asm));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn assembling_accepts_hex_and_decimal_and_rejects_other_kinds() {
+        let config = |kind: RomKind| RomConfig {
+            kind,
+            quirks: kind.default_rom_quirks(),
+        };
+
+        let chip8 = config(RomKind::CHIP8);
+        assert_eq!(assemble_inst("LD V0 0x05", chip8), Ok((0x6005, Instruction::SetConstant(0, 5))));
+        assert_eq!(assemble_inst("ld   i 520", chip8), Ok((0xA208, Instruction::SetIndex(0x208))));
+        assert_eq!(assemble_inst("8126", chip8), Ok((0x8126, Instruction::Shift(1, 2, true))));
+        assert_eq!(assemble_inst("scd 4", config(RomKind::SCHIP)).map(|(opcode, _)| opcode), Ok(0x00C4));
+
+        assert_eq!(
+            assemble_inst("ld v0 256", chip8),
+            Err(String::from("\"ld v0 256\" is not a CHIP8 instruction"))
+        );
+        assert_eq!(assemble_inst("exit", chip8), Err(String::from("\"exit\" is not a CHIP8 instruction")));
+        assert_eq!(
+            assemble_inst("ld r v8", config(RomKind::SCHIP)),
+            Err(String::from("\"ld r v8\" is not a SCHIP instruction"))
+        );
+        assert_eq!(
+            assemble_inst("lld i 0x1234", config(RomKind::XOCHIP)),
+            Err(String::from("\"lld i 0x1234\" is not a 2 byte instruction"))
+        );
+        assert!(assemble_inst("mov v0 v1", chip8).is_err());
+    }
+
+    #[test]
+    fn disassembly_tokens_are_classified_and_join_into_the_plain_string() {
+        use AsmToken::*;
+
+        let config = RomConfig {
+            kind: RomKind::XOCHIP,
+            quirks: RomKind::XOCHIP.default_rom_quirks(),
+        };
+        let tokens = |instruction: Instruction| {
+            let mut tokens = Vec::new();
+            write_inst_tokens(
+                &instruction,
+                config,
+                &mut |token, text| {
+                    tokens.push((token, text.to_owned()));
+                    Ok(())
+                },
+                &mut String::new(),
+            )
+            .expect("Writing instruction tokens failed");
+
+            let mut asm = String::new();
+            write_inst_dasm(&instruction, config, &mut asm, &mut String::new()).expect("Writing instruction failed");
+            assert_eq!(tokens.iter().map(|(_, text)| text.as_str()).collect::<String>(), asm);
+
+            tokens
+                .into_iter()
+                .filter(|&(token, _)| token != Space)
+                .collect::<Vec<_>>()
+        };
+        let expect = |expected: &[(AsmToken, &str)]| {
+            expected
+                .iter()
+                .map(|&(token, text)| (token, String::from(text)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(tokens(Instruction::SetConstant(0xA, 5)), expect(&[(Mnemonic, "ld"), (Register, "va"), (Immediate, "5")]));
+        assert_eq!(tokens(Instruction::SetIndex(0x208)), expect(&[(Mnemonic, "ld"), (Keyword, "i"), (Address, "0x208")]));
+        assert_eq!(
+            tokens(Instruction::SetIndexToLong(0x1234)),
+            expect(&[(Mnemonic, "lld"), (Keyword, "i"), (Address, "0x1234")])
+        );
+        assert_eq!(
+            tokens(Instruction::Draw(1, 2, 15)),
+            expect(&[(Mnemonic, "drw"), (Register, "v1"), (Register, "v2"), (Immediate, "15")])
+        );
+        assert_eq!(tokens(Instruction::WaitForKey(3)), expect(&[(Mnemonic, "ld"), (Register, "v3"), (Keyword, "k")]));
+        assert_eq!(tokens(Instruction::ClearScreen), expect(&[(Mnemonic, "cls")]));
+    }
+
+    #[test]
+    fn decode_coverage_counts_long_instructions_once_and_merges_undecodable_ranges() {
+        // cls, lld i 0x1234, two undecodable words, ld v0 1, then a trailing byte
+        let program = [0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0xFF, 0xFF, 0x5F, 0xF1, 0x60, 0x01, 0xFF];
+        let coverage = DecodeCoverage::from(&Interpreter::new(Rom::with_program(RomKind::XOCHIP, &program)));
+        assert_eq!((coverage.words, coverage.decodable_words), (7, 4));
+        assert_eq!(coverage.undecodable_ranges, vec![(0x206, 0x20A), (0x20C, 0x20E)]);
+    }
+
+    #[test]
+    fn sprite_rows_encode_left_aligned() {
+        let rows = ["x..x", ".##.", "X..X"];
+        assert_eq!(assemble_sprite(&rows, RomKind::CHIP8), Ok(vec![0x90, 0x60, 0x90]));
+
+        let rows = ["x.......x", "........x"];
+        assert_eq!(assemble_sprite(&rows, RomKind::SCHIP), Ok(vec![0x80, 0x80, 0x00, 0x80]));
+    }
+
+    #[test]
+    fn sprite_rows_are_validated() {
+        assert_eq!(
+            assemble_sprite(&["x.......x"], RomKind::CHIP8),
+            Err(String::from("Sprite rows must be 1 to 8 pixels wide for CHIP8 but \"x.......x\" is 9"))
+        );
+        assert_eq!(
+            assemble_sprite(&["xx", "x"], RomKind::CHIP8),
+            Err(String::from("Sprite rows must all be 2 pixels wide but \"x\" is 1"))
+        );
+        assert_eq!(
+            assemble_sprite(&["x-"], RomKind::CHIP8),
+            Err(String::from("Sprite row \"x-\" has '-' where #, x, or . was expected"))
+        );
+
+        // a pixel that takes more than one byte is still one pixel wide
+        assert_eq!(
+            assemble_sprite(&["x.", "x·"], RomKind::CHIP8),
+            Err(String::from("Sprite row \"x·\" has '·' where #, x, or . was expected"))
+        );
+        assert_eq!(
+            assemble_sprite(&["████████"], RomKind::CHIP8),
+            Err(String::from("Sprite row \"████████\" has '█' where #, x, or . was expected"))
+        );
+        assert!(assemble_sprite::<&str>(&[], RomKind::CHIP8).is_err());
+    }
+}
@@ -17,6 +17,7 @@ pub const AUDIO_BUFFER_SIZE_BYTES: usize = 16;
 const BASE_SAMPLE_RATE: f32 = 4000.0;
 const DEFAULT_VOLUME: f32 = 0.5;
 
+#[derive(Clone)]
 pub struct Audio {
     pub buffer: [u8; AUDIO_BUFFER_SIZE_BYTES],
     pub pitch: u8,
@@ -41,6 +42,7 @@ impl From<RomKind> for Audio {
     }
 }
 
+#[cfg(feature = "debugger")]
 impl Audio {
     pub fn sample_rate(&self) -> f32 {
         chip8_pitch_to_sample_rate(self.pitch)
@@ -255,6 +257,12 @@ fn chip8_pitch_to_sample_rate(pitch: u8) -> f32 {
     BASE_SAMPLE_RATE as f32 * 2.0_f32.powf((pitch as f32 - 64.0) / 48.0)
 }
 
+// a controller whose sink is never played, for running roms where there may be no audio device
+pub fn silent_audio_controller() -> AudioController {
+    let (sink, _queue) = Sink::new_idle();
+    AudioController::new(sink)
+}
+
 pub fn spawn_audio_stream() -> (OutputStream, AudioController) {
     // Get a output stream handle to the default physical sound device
     let (stream, stream_handle) =
@@ -1,6 +1,6 @@
-use super::{rom::RomConfig, vm::VM_FRAME_RATE};
+use super::{interp::StallReason, rom::RomConfig, vm::VM_FRAME_RATE};
 
-use crate::run::preset::COLOR_PRESETS;
+use crate::run::preset::GRAYSCALE_COLOR_PRESET;
 
 use tui::{
     buffer::Buffer,
@@ -25,8 +25,21 @@ impl DisplayMode {
     }
 
     pub fn window_dimensions(&self) -> (u16, u16) {
+        self.scaled_window_dimensions(1)
+    }
+
+    // each pixel is scale cells wide and scale half cells tall
+    pub fn scaled_window_dimensions(&self, scale: u16) -> (u16, u16) {
         let (width, height) = self.dimensions();
-        (width as u16 + 2, height as u16 / 2 + 2)
+        (width * scale + 2, height * scale / 2 + 2)
+    }
+
+    // the largest scale whose window fits in the area, which is never less than 1 so a cramped display is cut off
+    pub fn max_scale(&self, width: u16, height: u16) -> u16 {
+        let (display_width, display_height) = self.dimensions();
+        let scale_x = width.saturating_sub(2) / display_width;
+        let scale_y = 2 * height.saturating_sub(2) / display_height;
+        scale_x.min(scale_y).max(1)
     }
 }
 
@@ -43,12 +56,31 @@ const CLEAR_DISPLAY: DisplayBuffer = [0; HIRES_DISPLAY_HEIGHT as usize];
 // NOTE: The left-most pixel on the row corresponds to the most significant bit
 pub type DisplayBuffer = [u128; HIRES_DISPLAY_HEIGHT as usize];
 
+// Pixel hashes are FNV-1a over the rows as big endian bytes, so they only depend on which pixels are on and are the
+// same on every platform and build, unlike std's hashers. They are for cheaply comparing screens, not security
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+pub fn display_buffer_hash(buffer: &DisplayBuffer) -> u64 {
+    buffer
+        .iter()
+        .fold(FNV_OFFSET_BASIS, |hash, row| fnv1a(hash, &row.to_be_bytes()))
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Display {
     pub selected_plane_bitflags: u8,
     pub mode: DisplayMode,
     pub planes: [DisplayBuffer; 4],
-    pub colors: [Color; 16],
+    pub colors: [Color; 16], // indexed by the color index of each pixel
 }
 
 impl Default for Display {
@@ -57,12 +89,28 @@ impl Default for Display {
             selected_plane_bitflags: 0b0001,
             mode: DisplayMode::LowResolution,
             planes: [CLEAR_DISPLAY; 4],
-            colors: COLOR_PRESETS[0].1,
+            colors: GRAYSCALE_COLOR_PRESET.1,
         }
     }
 }
 
 impl Display {
+    // combines the resolution with every plane so the same pixels in a different mode or plane hash differently
+    pub fn pixel_hash(&self) -> u64 {
+        let (_, height) = self.mode.dimensions();
+        self.planes.iter().fold(fnv1a(FNV_OFFSET_BASIS, &[height as u8]), |hash, plane| {
+            fnv1a(hash, &display_buffer_hash(plane).to_be_bytes())
+        })
+    }
+
+    // index into colors of the pixel, made of one bit from each plane
+    pub fn color_index(&self, x: usize, y: usize) -> usize {
+        self.planes
+            .iter()
+            .enumerate()
+            .fold(0, |color_index, (i, plane)| color_index | ((plane[y] >> (127 - x) & 1) as usize) << i)
+    }
+
     pub fn set_mode(&mut self, mode: DisplayMode) {
         self.mode = mode;
         self.clear();
@@ -148,9 +196,8 @@ impl Display {
 
         let mut flag = false;
         for (i, plane) in self.selected_planes_mut().enumerate() {
-            let sprite_start = sprite_bytes * i;
-            let sprite = &memory[sprite_start..sprite_start + rendered_sprite_bytes];
-            flag |= draw_plane(plane, sprite, pos_x, pos_y, bytes_per_row, mask);
+            let sprite = &memory[sprite_bytes * i..sprite_bytes * (i + 1)];
+            flag |= draw_plane(plane, &sprite[..rendered_sprite_bytes], pos_x, pos_y, bytes_per_row, mask);
 
             if wrap {
                 let mut workspace = [0; 128];
@@ -160,7 +207,7 @@ impl Display {
                 if clipped_sprite_width < width {
                     slice_sprite(
                         &mut workspace,
-                        memory,
+                        sprite,
                         clipped_sprite_width,
                         0,
                         clipped_sprite_height,
@@ -172,7 +219,7 @@ impl Display {
                 if clipped_sprite_height < height {
                     slice_sprite(
                         &mut workspace,
-                        memory,
+                        sprite,
                         0,
                         clipped_sprite_height,
                         height - clipped_sprite_height,
@@ -184,7 +231,7 @@ impl Display {
                 if clipped_sprite_width < width && clipped_sprite_height < height {
                     slice_sprite(
                         &mut workspace,
-                        memory,
+                        sprite,
                         clipped_sprite_width,
                         clipped_sprite_height,
                         height - clipped_sprite_height,
@@ -196,6 +243,78 @@ impl Display {
         }
         flag
     }
+
+    // draws like draw but also reports every pixel the sprite erased as (plane, x, y)
+    // this compares the whole display before and after so it is for verifying rendering, not the step path
+    pub fn draw_reporting_erased(
+        &mut self,
+        memory: &[u8],
+        pos_x: u16,
+        pos_y: u16,
+        height: usize,
+        bytes_per_row: usize,
+        wrap: bool,
+    ) -> (bool, Vec<(usize, u16, u16)>) {
+        let prior_planes = self.planes;
+        let flag = self.draw(memory, pos_x, pos_y, height, bytes_per_row, wrap);
+
+        let (_, display_height) = self.mode.dimensions();
+        let mut erased = Vec::new();
+        for (i, (prior_plane, plane)) in prior_planes.iter().zip(self.planes.iter()).enumerate() {
+            for (y, (prior_row, row)) in prior_plane
+                .iter()
+                .zip(plane.iter())
+                .enumerate()
+                .take(display_height as usize)
+            {
+                let mut erased_row = prior_row & !row;
+                while erased_row != 0 {
+                    let x = erased_row.leading_zeros() as u16;
+                    erased.push((i, x, y as u16));
+                    erased_row &= !(1 << (127 - x));
+                }
+            }
+        }
+
+        (flag, erased)
+    }
+
+    // the pixels a draw would flip as (plane, x, y) without drawing, with the same wrapping and clipping as draw
+    // it goes pixel by pixel rather than row by row like draw so it doubles as a check on draw
+    pub fn draw_footprint(
+        &self,
+        memory: &[u8],
+        pos_x: u16,
+        pos_y: u16,
+        height: usize,
+        bytes_per_row: usize,
+        wrap: bool,
+    ) -> Vec<(usize, u16, u16)> {
+        let (display_width, display_height) = self.mode.dimensions();
+        let pos_x = pos_x % display_width;
+        let pos_y = pos_y % display_height;
+
+        let sprite_bytes = height * bytes_per_row;
+        let width = 8 * bytes_per_row as u16;
+
+        let mut footprint = Vec::new();
+        let selected_planes = (0..self.planes.len()).filter(|i| self.selected_plane_bitflags >> i & 1 == 1);
+        for (i, plane) in selected_planes.enumerate() {
+            let sprite = &memory[sprite_bytes * i..sprite_bytes * (i + 1)];
+            for (row, row_bytes) in sprite.chunks_exact(bytes_per_row).enumerate() {
+                for column in (0..width).filter(|column| row_bytes[*column as usize / 8] >> (7 - column % 8) & 1 == 1) {
+                    let (x, y) = (pos_x + column, pos_y + row as u16);
+                    if wrap {
+                        footprint.push((plane, x % display_width, y % display_height));
+                    } else if x < display_width && y < display_height {
+                        footprint.push((plane, x, y));
+                    }
+                }
+            }
+        }
+
+        footprint
+    }
 }
 
 fn slice_sprite(
@@ -263,16 +382,81 @@ fn draw_plane(
     flag
 }
 
+// Pixels that just turned off keep a dimmed copy of their color for a few frames, which hides the flicker of
+// sprites that are erased and redrawn every frame. It is stepped once per rendered frame so a pixel fades out
+// even when the display stops changing
+const PHOSPHOR_FADE_FRAMES: u8 = 2;
+
+#[derive(Clone, Default)]
+pub struct PhosphorFade {
+    width: usize,
+    color_indices: Vec<usize>,
+    fading: Vec<(u8, usize)>, // frames left and the color the pixel had before turning off
+}
+
+impl PhosphorFade {
+    // returns whether any pixel faded so the display is redrawn until the fade is over
+    pub fn step(&mut self, display: Option<&Display>) -> bool {
+        let mut faded = false;
+        for (frames, _) in self.fading.iter_mut().filter(|(frames, _)| *frames > 0) {
+            *frames -= 1;
+            faded = true;
+        }
+
+        let Some(display) = display else {
+            return faded;
+        };
+
+        let (width, height) = display.mode.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        let color_indices: Vec<usize> = (0..width * height)
+            .map(|i| display.color_index(i % width, i / width))
+            .collect();
+
+        // nothing fades across a resolution change since the pixels no longer line up
+        if color_indices.len() != self.color_indices.len() {
+            self.fading = vec![(0, 0); color_indices.len()];
+        } else {
+            for ((&old, &new), fade) in self.color_indices.iter().zip(color_indices.iter()).zip(self.fading.iter_mut()) {
+                if new != 0 {
+                    *fade = (0, 0);
+                } else if old != 0 {
+                    *fade = (PHOSPHOR_FADE_FRAMES, old);
+                    faded = true;
+                }
+            }
+        }
+
+        self.width = width;
+        self.color_indices = color_indices;
+        faded
+    }
+
+    fn faded_color(&self, colors: &[Color; 16], x: usize, y: usize) -> Option<Color> {
+        match self.fading.get(y * self.width + x) {
+            Some(&(frames, color_index)) if frames > 0 => Some(
+                (frames..PHOSPHOR_FADE_FRAMES).fold(dim_color(colors[color_index]), |color, _| dim_color(color)),
+            ),
+            _ => None,
+        }
+    }
+}
+
 pub struct DisplayWidget {
     pub display: Display,
+
     pub rom_name: String,
     pub rom_config: RomConfig,
     pub cycles_per_frame: u32,
+    pub stall_reason: Option<StallReason>,
+    pub scanlines: bool,
+    pub phosphor_fade: Option<PhosphorFade>,
+    pub scale: u16,
 }
 
 impl DisplayWidget {
     pub fn build_title(&self) -> Spans<'static> {
-        Spans::from(vec![
+        let mut title = Spans::from(vec![
             Span::raw(" "),
             Span::styled(
                 format!(" {} ", self.rom_config.kind),
@@ -288,19 +472,26 @@ impl DisplayWidget {
                 self.cycles_per_frame,
                 self.cycles_per_frame * VM_FRAME_RATE,
             )),
-        ])
-    }
+        ]);
+
+        // the display stops changing while a rom is stalled so make it clear why rather than looking frozen
+        // a vertical blank wait ends within the frame so it is not worth showing
+        let stall = match self.stall_reason {
+            Some(StallReason::WaitingForKey(vx)) => Some((format!("[waiting for key -> v{:x}]", vx), Color::Yellow)),
+            Some(StallReason::Halted) => Some((String::from("[halted]"), Color::Red)),
+            Some(StallReason::Paused) => Some((String::from("[paused]"), Color::Cyan)),
+            Some(StallReason::WaitingForVerticalBlank) | None => None,
+        };
 
-    fn pixel_stream(
-        plane: &DisplayBuffer,
-        width: usize,
-        height: usize,
-    ) -> impl Iterator<Item = bool> + '_ {
-        plane
-            .iter()
-            .take(height)
-            .map(move |plane_row| (0..width).map(|shift| (*plane_row >> (127 - shift) & 1 == 1)))
-            .flatten()
+        if let Some((text, color)) = stall {
+            title.0.push(Span::styled(
+                text,
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ));
+            title.0.push(Span::raw(" "));
+        }
+
+        title
     }
 }
 
@@ -312,39 +503,36 @@ impl Widget for DisplayWidget {
         // so for each pixel in the row of the terminal we can use half-block color and the background color to represent 2 pixels in the display
         // so for each row of the terminal we can fit 2 rows of the display
 
-        let rendered_display_width = area.width.min(display_width) as usize;
-        let rendered_display_height = 2 * area.height.min(display_height) as usize;
-
-        let mut pixel_streams = [0, 1, 2, 3].map(|i| {
-            (
-                i,
-                DisplayWidget::pixel_stream(
-                    &self.display.planes[i],
-                    rendered_display_width,
-                    rendered_display_height,
-                ),
-            )
-        });
+        // scaling repeats each pixel across scale columns and scale half rows
+        let scale = self.scale.max(1) as usize;
+        let rendered_display_width = (area.width as usize).min(display_width as usize * scale);
+        let rendered_display_height = (2 * area.height as usize).min(display_height as usize * scale);
 
         for i in 0..rendered_display_width * rendered_display_height {
-            let color = self.display.colors[pixel_streams.iter_mut().fold(
-                0,
-                |color_index, (plane_index, stream)| {
-                    color_index
-                        | (stream
-                            .next()
-                            .expect("Stream must be the size of the rendered area")
-                            as usize)
-                            << *plane_index
-                },
-            )];
-
-            let x = i % rendered_display_width;
-            let y = i / rendered_display_width;
-
-            let cell = buf.get_mut(area.left() + x as u16, area.top() + y as u16 / 2);
-
-            if y % 2 == 0 {
+            let column = i % rendered_display_width;
+            let half_row = i / rendered_display_width;
+            let x = column / scale;
+            let y = half_row / scale;
+
+            // a single plane rom only ever has color indices 0 and 1 so this is plain on and off
+            let color_index = self.display.color_index(x, y);
+
+            let faded_color = match self.phosphor_fade.as_ref() {
+                Some(fade) if color_index == 0 => fade.faded_color(&self.display.colors, x, y),
+                _ => None,
+            };
+            let color = faded_color.unwrap_or(self.display.colors[color_index]);
+
+            // scanlines dim whole terminal rows so both halves of a cell are dimmed together
+            let color = if self.scanlines && half_row / 2 % 2 == 1 {
+                dim_color(color)
+            } else {
+                color
+            };
+
+            let cell = buf.get_mut(area.left() + column as u16, area.top() + half_row as u16 / 2);
+
+            if half_row % 2 == 0 {
                 cell.set_bg(color);
             } else {
                 cell.set_fg(color).set_symbol("▄");
@@ -352,3 +540,157 @@ impl Widget for DisplayWidget {
         }
     }
 }
+
+fn dim_color(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => Color::Rgb(
+            (r as u16 * 3 / 5) as u8,
+            (g as u16 * 3 / 5) as u8,
+            (b as u16 * 3 / 5) as u8,
+        ),
+        Color::White => Color::Gray,
+        Color::Gray => Color::DarkGray,
+        Color::LightRed => Color::Red,
+        Color::LightGreen => Color::Green,
+        Color::LightYellow => Color::Yellow,
+        Color::LightBlue => Color::Blue,
+        Color::LightMagenta => Color::Magenta,
+        Color::LightCyan => Color::Cyan,
+        color => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_reports_the_pixels_it_erased() {
+        let mut display = Display::default();
+        assert!(!display.draw(&[0xFF, 0xFF], 0, 0, 2, 1, false));
+
+        // overlaps x 6 and 7 of the first row and x 2 of the second
+        let (collision, erased) = display.draw_reporting_erased(&[0x0F, 0x20], 2, 0, 2, 1, false);
+        assert!(collision);
+        assert_eq!(erased, vec![(0, 6, 0), (0, 7, 0), (0, 4, 1)]);
+    }
+
+    #[test]
+    fn draw_without_overlap_reports_nothing_erased() {
+        let mut display = Display::default();
+        display.draw(&[0xFF], 0, 0, 1, 1, false);
+
+        let (collision, erased) = display.draw_reporting_erased(&[0xFF], 0, 1, 1, 1, false);
+        assert!(!collision);
+        assert!(erased.is_empty());
+    }
+
+    #[test]
+    fn draw_lights_exactly_the_footprint_of_each_plane() {
+        // 2 rows per plane, plane 0 lights columns 0 and 1 and plane 1 columns 1 and 7
+        let sprite = [0xC0, 0xC0, 0x41, 0x41];
+
+        for wrap in [true, false] {
+            let mut display = Display::default();
+            display.selected_plane_bitflags = 0b11;
+            let mut footprint = display.draw_footprint(&sprite, 63, 31, 2, 1, wrap);
+            assert!(!display.draw(&sprite, 63, 31, 2, 1, wrap));
+
+            let (width, height) = display.mode.dimensions();
+            let mut lit = Vec::new();
+            for plane in 0..2 {
+                for y in 0..height {
+                    for x in 0..width {
+                        if display.color_index(x as usize, y as usize) >> plane & 1 == 1 {
+                            lit.push((plane, x, y));
+                        }
+                    }
+                }
+            }
+
+            footprint.sort_by_key(|&(plane, x, y)| (plane, y, x));
+            assert_eq!(lit, footprint, "wrap: {}", wrap);
+        }
+
+        let mut display = Display::default();
+        display.selected_plane_bitflags = 0b11;
+        let footprint = display.draw_footprint(&sprite, 63, 31, 2, 1, true);
+        assert_eq!(
+            footprint,
+            vec![(0, 63, 31), (0, 0, 31), (0, 63, 0), (0, 0, 0), (1, 0, 31), (1, 6, 31), (1, 0, 0), (1, 6, 0)]
+        );
+    }
+
+    #[test]
+    fn draw_flips_exactly_the_footprint_of_random_sprites() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(0xC8);
+        for _ in 0..2000 {
+            let mut display = Display::default();
+            if rng.gen() {
+                display.set_mode(DisplayMode::HighResolution);
+            }
+            display.selected_plane_bitflags = rng.gen_range(1..16);
+
+            let (width, height) = display.mode.dimensions();
+            for plane in display.planes.iter_mut() {
+                for row in plane.iter_mut().take(height as usize) {
+                    *row = rng.gen::<u128>() & u128::MAX << (128 - width);
+                }
+            }
+
+            let bytes_per_row = rng.gen_range(1..=2);
+            let sprite_height = rng.gen_range(1..=16);
+            let mut sprite = [0; 128];
+            rng.fill(&mut sprite[..]);
+            let (pos_x, pos_y) = (rng.gen(), rng.gen());
+            let wrap = rng.gen();
+
+            let prior_planes = display.planes;
+            let footprint = display.draw_footprint(&sprite, pos_x, pos_y, sprite_height, bytes_per_row, wrap);
+            let collision = display.draw(&sprite, pos_x, pos_y, sprite_height, bytes_per_row, wrap);
+
+            let mut flipped = [CLEAR_DISPLAY; 4];
+            for &(plane, x, y) in footprint.iter() {
+                flipped[plane][y as usize] |= 1 << (127 - x);
+            }
+            for (plane, flipped_plane) in flipped.iter().enumerate() {
+                for y in 0..HIRES_DISPLAY_HEIGHT as usize {
+                    assert_eq!(prior_planes[plane][y] ^ display.planes[plane][y], flipped_plane[y]);
+                }
+            }
+
+            let footprint_collision = footprint
+                .iter()
+                .any(|&(plane, x, y)| prior_planes[plane][y as usize] >> (127 - x) & 1 == 1);
+            assert_eq!(collision, footprint_collision);
+        }
+    }
+
+    #[test]
+    fn display_buffer_hash_is_fixed_across_platforms() {
+        assert_eq!(display_buffer_hash(&CLEAR_DISPLAY), 0x51d88627df287325);
+    }
+
+    #[test]
+    fn pixel_hash_only_depends_on_the_final_pixels() {
+        let mut whole = Display::default();
+        whole.draw(&[0xFF], 0, 0, 1, 1, false);
+
+        // the same row drawn in halves, after a sprite that was drawn and erased again
+        let mut halves = Display::default();
+        halves.draw(&[0x81], 8, 3, 1, 1, false);
+        halves.draw(&[0x81], 8, 3, 1, 1, false);
+        halves.draw(&[0xF0], 4, 0, 1, 1, false);
+        halves.draw(&[0xF0], 0, 0, 1, 1, false);
+
+        assert_eq!(whole.planes, halves.planes);
+        assert_eq!(display_buffer_hash(&whole.planes[0]), display_buffer_hash(&halves.planes[0]));
+        assert_eq!(whole.pixel_hash(), halves.pixel_hash());
+
+        halves.draw(&[0x80], 63, 31, 1, 1, false);
+        assert_ne!(display_buffer_hash(&whole.planes[0]), display_buffer_hash(&halves.planes[0]));
+        assert_ne!(whole.pixel_hash(), halves.pixel_hash());
+    }
+}
@@ -23,6 +23,7 @@ pub enum Key {
     V,
 }
 
+#[cfg(feature = "debugger")]
 pub const KEY_ORDERING: [Key; 16] = [
     Key::One,
     Key::Two,
@@ -196,6 +197,64 @@ impl TryFrom<CrosstermKey> for Key {
         }
     }
 }
+
+// Which host keys stand in for the 16 keys of the CHIP-8 keypad
+//
+//   QWERTY    VIP       keypad
+//   1 2 3 4   1 2 3 C   1 2 3 C
+//   Q W E R   4 5 6 D   4 5 6 D
+//   A S D F   7 8 9 E   7 8 9 E
+//   Z X C V   A 0 B F   A 0 B F
+//
+// QWERTY keeps the shape of the COSMAC VIP keypad on the left of the keyboard while VIP types each key by the
+// hex digit printed on it, so instructions like "press 5" in original manuals can be followed literally
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum KeypadLayout {
+    #[default]
+    Qwerty,
+    Vip,
+}
+
+impl KeypadLayout {
+    pub fn map_crossterm_key(self, key: CrosstermKey) -> Option<Key> {
+        match self {
+            KeypadLayout::Qwerty => Key::try_from(key).ok(),
+            KeypadLayout::Vip => match key {
+                CrosstermKey::Char(c) => Key::try_from(c.to_digit(16)? as u8).ok(),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn map_device_key(self, key: DeviceKey) -> Option<Key> {
+        match self {
+            KeypadLayout::Qwerty => Key::try_from(key).ok(),
+            KeypadLayout::Vip => {
+                let code: u8 = match key {
+                    DeviceKey::Key0 => 0x0,
+                    DeviceKey::Key1 => 0x1,
+                    DeviceKey::Key2 => 0x2,
+                    DeviceKey::Key3 => 0x3,
+                    DeviceKey::Key4 => 0x4,
+                    DeviceKey::Key5 => 0x5,
+                    DeviceKey::Key6 => 0x6,
+                    DeviceKey::Key7 => 0x7,
+                    DeviceKey::Key8 => 0x8,
+                    DeviceKey::Key9 => 0x9,
+                    DeviceKey::A => 0xA,
+                    DeviceKey::B => 0xB,
+                    DeviceKey::C => 0xC,
+                    DeviceKey::D => 0xD,
+                    DeviceKey::E => 0xE,
+                    DeviceKey::F => 0xF,
+                    _ => return None,
+                };
+                Key::try_from(code).ok()
+            }
+        }
+    }
+}
+
 // Keyboard holds state necessary for providing keyboard state to CHIP-8 interpeters
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Keyboard {
@@ -222,10 +281,25 @@ impl PartialEq for Keyboard {
 }
 
 impl Keyboard {
+    #[cfg(feature = "debugger")]
     pub fn clear(&mut self) {
         *self = Keyboard::default();
     }
 
+    // a focused keyboard in a state recorded earlier by state()
+    pub fn with_state(
+        down_keys: u16,
+        key_down_change: Option<u8>,
+        key_up_change: Option<u8>,
+    ) -> Self {
+        Keyboard {
+            focused: true,
+            focused_down_keys: down_keys,
+            key_down_change,
+            key_up_change,
+        }
+    }
+
     pub fn state(&self) -> (&u16, &Option<u8>, &Option<u8>) {
         (
             &self.focused_down_keys,
@@ -30,6 +30,7 @@ pub fn decode_nnnn(bits: u32) -> u16 {
     ((bits & 0x0000FFFF) >> 4 * 0) as u16
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum InstructionDecodeError {
     UnknownInstruction {
         parameters: InstructionParameters,
@@ -81,7 +82,7 @@ impl std::fmt::Display for InstructionDecodeError {
 }
 
 // Takes 16 bits (instruction size) and decomposes it into its parts
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct InstructionParameters {
     pub bits: u32,
     pub op: u8,
@@ -214,6 +215,182 @@ impl Instruction {
     }
 
     pub fn try_from_u32(bits: u32, kind: RomKind) -> Result<Instruction, InstructionDecodeError> {
+        let decode_family = OPCODE_FAMILIES[decode_op(bits) as usize];
+
+        let Some(instruction) = decode_family(bits, decode_x(bits), decode_y(bits), decode_n(bits))
+        else {
+            return Err(InstructionDecodeError::UnknownInstruction {
+                parameters: InstructionParameters::new(bits),
+            });
+        };
+
+        let expected_rom_kind = match instruction {
+            // the flag registers SCHIP added are reported before the extra ones XO-CHIP added
+            Instruction::LoadFlags(_) | Instruction::StoreFlags(_) if kind < RomKind::SCHIP => {
+                RomKind::SCHIP
+            }
+            _ => instruction.minimum_rom_kind(),
+        };
+        if kind < expected_rom_kind {
+            return Err(InstructionDecodeError::IncompatibleRomKind {
+                instruction,
+                parameters: InstructionParameters::new(bits),
+                expected_rom_kind,
+                actual_rom_kind: kind,
+            });
+        }
+
+        Ok(instruction)
+    }
+
+    // the earliest variant whose programs can use the instruction
+    pub fn minimum_rom_kind(&self) -> RomKind {
+        match *self {
+            Instruction::Exit
+            | Instruction::LowResolution
+            | Instruction::HighResolution
+            | Instruction::ScrollDown(_)
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::SetIndexToBigHexChar(_) => RomKind::SCHIP,
+            Instruction::ScrollUp(_)
+            | Instruction::LoadAudio
+            | Instruction::SetPitch(_)
+            | Instruction::LoadRange(_, _)
+            | Instruction::StoreRange(_, _)
+            | Instruction::SetIndexToLong(_)
+            | Instruction::SetPlane(_) => RomKind::XOCHIP,
+            // SCHIP only has 8 flag registers
+            Instruction::LoadFlags(vx) | Instruction::StoreFlags(vx) => {
+                if vx > 0x7 {
+                    RomKind::XOCHIP
+                } else {
+                    RomKind::SCHIP
+                }
+            }
+            _ => RomKind::CLASSIC,
+        }
+    }
+}
+
+// Opcodes are decoded by family, the first nibble, with each family decoding the remaining nibbles (x, y, n)
+// Families decode every opcode any variant defines since the variant is checked afterwards against
+// the instruction's minimum rom kind, so a new opcode only needs an arm here and a kind if it is an extension
+type OpcodeFamilyDecoder = fn(u32, u8, u8, u8) -> Option<Instruction>;
+
+const OPCODE_FAMILIES: [OpcodeFamilyDecoder; 16] = [
+    decode_system_family,
+    |bits, _, _, _| Some(Instruction::Jump(decode_nnn(bits))),
+    |bits, _, _, _| Some(Instruction::CallSubroutine(decode_nnn(bits))),
+    |bits, x, _, _| Some(Instruction::SkipIfEqualsConstant(x, decode_nn(bits))),
+    |bits, x, _, _| Some(Instruction::SkipIfNotEqualsConstant(x, decode_nn(bits))),
+    decode_register_pair_family,
+    |bits, x, _, _| Some(Instruction::SetConstant(x, decode_nn(bits))),
+    |bits, x, _, _| Some(Instruction::AddConstant(x, decode_nn(bits))),
+    decode_arithmetic_family,
+    |_, x, y, n| (n == 0x0).then_some(Instruction::SkipIfNotEquals(x, y)),
+    |bits, _, _, _| Some(Instruction::SetIndex(decode_nnn(bits))),
+    |bits, x, _, _| Some(Instruction::JumpWithOffset(decode_nnn(bits), x)),
+    |bits, x, _, _| Some(Instruction::GenerateRandom(x, decode_nn(bits))),
+    |_, x, y, n| Some(Instruction::Draw(x, y, n)),
+    decode_key_family,
+    decode_misc_family,
+];
+
+// 0___
+fn decode_system_family(_: u32, x: u8, y: u8, n: u8) -> Option<Instruction> {
+    if x != 0x0 {
+        return None;
+    }
+
+    Some(match (y, n) {
+        (0xE, 0x0) => Instruction::ClearScreen,
+        (0xE, 0xE) => Instruction::SubroutineReturn,
+        (0xC, __n) => Instruction::ScrollDown(n),
+        (0xD, __n) => Instruction::ScrollUp(n),
+        (0xF, 0xB) => Instruction::ScrollRight,
+        (0xF, 0xC) => Instruction::ScrollLeft,
+        (0xF, 0xD) => Instruction::Exit,
+        (0xF, 0xE) => Instruction::LowResolution,
+        (0xF, 0xF) => Instruction::HighResolution,
+        _ => return None,
+    })
+}
+
+// 5XY_
+fn decode_register_pair_family(_: u32, x: u8, y: u8, n: u8) -> Option<Instruction> {
+    Some(match n {
+        0x0 => Instruction::SkipIfEquals(x, y),
+        0x2 => Instruction::StoreRange(x, y),
+        0x3 => Instruction::LoadRange(x, y),
+        _ => return None,
+    })
+}
+
+// 8XY_
+fn decode_arithmetic_family(_: u32, x: u8, y: u8, n: u8) -> Option<Instruction> {
+    Some(match n {
+        0x0 => Instruction::Set(x, y),
+        0x1 => Instruction::Or(x, y),
+        0x2 => Instruction::And(x, y),
+        0x3 => Instruction::Xor(x, y),
+        0x4 => Instruction::Add(x, y),
+        0x5 => Instruction::Sub(x, y, true),
+        0x6 => Instruction::Shift(x, y, true),
+        0x7 => Instruction::Sub(x, y, false),
+        0xE => Instruction::Shift(x, y, false),
+        _ => return None,
+    })
+}
+
+// EX__
+fn decode_key_family(_: u32, x: u8, y: u8, n: u8) -> Option<Instruction> {
+    Some(match (y, n) {
+        (0x9, 0xE) => Instruction::SkipIfKeyDown(x),
+        (0xA, 0x1) => Instruction::SkipIfKeyNotDown(x),
+        _ => return None,
+    })
+}
+
+// FX__
+fn decode_misc_family(bits: u32, x: u8, y: u8, n: u8) -> Option<Instruction> {
+    Some(match (x, y, n) {
+        (0x0, 0x0, 0x0) => Instruction::SetIndexToLong(decode_nnnn(bits)),
+        (__x, 0x0, 0x1) => Instruction::SetPlane(x),
+        (0x0, 0x0, 0x2) => Instruction::LoadAudio,
+        (__x, 0x0, 0x7) => Instruction::GetDelayTimer(x),
+        (__x, 0x0, 0xA) => Instruction::WaitForKey(x),
+        (__x, 0x1, 0x5) => Instruction::SetDelayTimer(x),
+        (__x, 0x1, 0x8) => Instruction::SetSoundTimer(x),
+        (__x, 0x1, 0xE) => Instruction::AddToIndex(x),
+        (__x, 0x2, 0x9) => Instruction::SetIndexToHexChar(x),
+        (__x, 0x3, 0x0) => Instruction::SetIndexToBigHexChar(x),
+        (__x, 0x3, 0x3) => Instruction::StoreBinaryCodedDecimal(x),
+        (__x, 0x3, 0xA) => Instruction::SetPitch(x),
+        (__x, 0x5, 0x5) => Instruction::Store(x),
+        (__x, 0x6, 0x5) => Instruction::Load(x),
+        (__x, 0x7, 0x5) => Instruction::StoreFlags(x),
+        (__x, 0x8, 0x5) => Instruction::LoadFlags(x),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM_KINDS: [RomKind; 4] = [
+        RomKind::CLASSIC,
+        RomKind::CHIP8,
+        RomKind::SCHIP,
+        RomKind::XOCHIP,
+    ];
+
+    // the decoder from before it was split into families, copied verbatim, to check the families against
+    fn reference_try_from_u32(
+        bits: u32,
+        kind: RomKind,
+    ) -> Result<Instruction, InstructionDecodeError> {
         let op = decode_op(bits);
         let x = decode_x(bits);
         let y = decode_y(bits);
@@ -332,4 +509,28 @@ impl Instruction {
 
         Ok(instruction)
     }
+
+    type Decoder = fn(u32, RomKind) -> Result<Instruction, InstructionDecodeError>;
+
+    // errors compare by their message, which names the instruction and both rom kinds
+    fn decode(decoder: Decoder, bits: u32, kind: RomKind) -> Result<Instruction, String> {
+        decoder(bits, kind).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn opcode_families_decode_every_opcode_like_the_reference() {
+        for kind in ROM_KINDS {
+            for opcode in 0..=u16::MAX {
+                // the trailing bytes only matter to the 4 byte F000 NNNN
+                let bits = (opcode as u32) << 16 | 0xA55A;
+                assert!(
+                    decode(Instruction::try_from_u32, bits, kind)
+                        == decode(reference_try_from_u32, bits, kind),
+                    "{:04X} decoded differently for {}",
+                    opcode,
+                    kind
+                );
+            }
+        }
+    }
 }
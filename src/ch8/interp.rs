@@ -1,20 +1,92 @@
 use super::{
-    audio::{Audio, AUDIO_BUFFER_SIZE_BYTES},
-    disp::{Display, DisplayBuffer, DisplayMode},
-    input::Key,
-    instruct::Instruction,
+    audio::Audio,
+    disp::{Display, DisplayMode},
+    instruct::{Instruction, InstructionDecodeError},
     mem::*,
-    rom::{Rom, RomKind},
+    rom::{Rom, RomKind, RomQuirks},
 };
 
+#[cfg(feature = "debugger")]
+use super::{audio::AUDIO_BUFFER_SIZE_BYTES, disp::DisplayBuffer, input::Key};
+
 use rand::rngs::StdRng;
 use rand::{RngCore, SeedableRng};
 
+// What still keeps this module from building without std: memory and prefetch are Vecs sized at runtime,
+// Rom owns its data and name, and the rng seeds itself from the OS
+
 pub const VFLAG: usize = 15;
 
 pub const PROGRAM_STARTING_ADDRESS: u16 = 0x200;
+
+// SCHIP's depth, the deepest stack of the original interpreters
+pub const STACK_SIZE: usize = 16;
+
+// Return addresses of the subroutines being called, most recent last
+#[derive(Clone, Copy, Default)]
+pub struct Stack {
+    addresses: [u16; STACK_SIZE],
+    len: usize,
+}
+
+impl Stack {
+    // false when the stack is already full
+    pub fn push(&mut self, address: u16) -> bool {
+        let Some(slot) = self.addresses.get_mut(self.len) else {
+            return false;
+        };
+
+        *slot = address;
+        self.len += 1;
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<u16> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.addresses[self.len])
+    }
+}
+
+impl std::ops::Deref for Stack {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.addresses[..self.len]
+    }
+}
+
+// Why the interpreter stopped, only formatted into a message once something displays it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InterpreterError {
+    Decode { address: u16, error: InstructionDecodeError },
+    StackEmpty,
+    StackFull { address: u16 },
+    MissingHexChar(u8),
+    MissingBigHexChar(u8),
+    SkipPastEndOfMemory { address: u16 },
+}
+
+impl std::fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Decode { address, error } => write!(f, "Decode at {:#05X?} failed: {}", address, error),
+            Self::StackEmpty => write!(f, "Could not return from subroutine because stack is empty"),
+            Self::StackFull { address } => write!(
+                f,
+                "Could not call subroutine at {:#05X?} because the stack is full ({} deep)",
+                address, STACK_SIZE
+            ),
+            Self::MissingHexChar(c) => write!(f, "Failed to set index: hex char \"{:X}\" does not exist", c),
+            Self::MissingBigHexChar(c) => write!(f, "Failed to set index: big hex char \"{:X}\" does not exist", c),
+            Self::SkipPastEndOfMemory { address } => {
+                write!(f, "Skipping the instruction at {:#05X?} would leave addressable memory", address)
+            }
+        }
+    }
+}
+
 // State the interpreter pulls from IO is stored here
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct InterpreterInput {
     pub delay_timer: u8,
 
@@ -25,6 +97,36 @@ pub struct InterpreterInput {
     pub just_released_key: Option<u8>,
 }
 
+// What to do when the instruction at the pc cannot be decoded
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum UnknownOpcodeMode {
+    #[default]
+    Strict, // stop with an error
+    Nop,    // skip 2 bytes and keep going (partially understood roms and fuzzing)
+    Halt,   // stop as if the program exited
+}
+
+// What to do with a 0NNN machine code call, which ran a routine of the COSMAC VIP's own processor
+// the routine itself is never emulated so at best the call can be stepped over
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MachineCallMode {
+    #[default]
+    Unknown, // the same as any other undecodable opcode
+    Nop,     // skip it as a 2 byte no-op
+    Log,     // skip it and log the address it called
+}
+
+// Settings chosen when c8 starts rather than state of the program, so they outlast resets and restored copies
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct InterpreterSettings {
+    pub unknown_opcode_mode: UnknownOpcodeMode,
+    pub machine_call_mode: MachineCallMode,
+    pub warn_add_overflow: bool, // diagnostic for 7XNN wrapping around, which is valid but often a rom bug
+    pub warn_uninit_reads: bool, // diagnostic for reading a register nothing has written since reset
+    pub warn_stack_depth: Option<usize>, // diagnostic for calls nested deeper than real hardware allows
+    pub trace_erased_pixels: bool, // logs every pixel a draw erases for checking sprite rendering pixel by pixel
+}
+
 // Interpreter IO Request
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum InterpreterOutput {
@@ -35,12 +137,86 @@ pub enum InterpreterOutput {
     UpdateAudioBuffer,
 }
 
+// Registers an instruction reads and writes as bitmasks with v0 in the least significant bit
+fn register_access(inst: Instruction, quirks: RomQuirks) -> (u16, u16) {
+    fn reg(vx: u8) -> u16 {
+        1 << vx
+    }
+
+    fn range(vstart: u8, vend: u8) -> u16 {
+        (vstart.min(vend)..=vstart.max(vend)).fold(0, |mask, vx| mask | reg(vx))
+    }
+
+    let vf = reg(VFLAG as u8);
+
+    match inst {
+        Instruction::SkipIfEqualsConstant(vx, _)
+        | Instruction::SkipIfNotEqualsConstant(vx, _)
+        | Instruction::SkipIfKeyDown(vx)
+        | Instruction::SkipIfKeyNotDown(vx)
+        | Instruction::SetDelayTimer(vx)
+        | Instruction::SetSoundTimer(vx)
+        | Instruction::SetIndexToHexChar(vx)
+        | Instruction::SetIndexToBigHexChar(vx)
+        | Instruction::StoreBinaryCodedDecimal(vx)
+        | Instruction::SetPitch(vx) => (reg(vx), 0),
+        Instruction::SkipIfEquals(vx, vy) | Instruction::SkipIfNotEquals(vx, vy) => (reg(vx) | reg(vy), 0),
+        Instruction::WaitForKey(vx)
+        | Instruction::SetConstant(vx, _)
+        | Instruction::GetDelayTimer(vx)
+        | Instruction::GenerateRandom(vx, _) => (0, reg(vx)),
+        Instruction::AddConstant(vx, _) => (reg(vx), reg(vx)),
+        Instruction::Set(vx, vy) => (reg(vy), reg(vx)),
+        Instruction::Or(vx, vy)
+        | Instruction::And(vx, vy)
+        | Instruction::Xor(vx, vy)
+        | Instruction::Add(vx, vy)
+        | Instruction::Sub(vx, vy, _) => (reg(vx) | reg(vy), reg(vx) | vf),
+        Instruction::Shift(vx, vy, _) => (
+            if quirks.bit_shift_modifies_vx_in_place { reg(vx) } else { reg(vy) },
+            reg(vx) | vf,
+        ),
+        Instruction::AddToIndex(vx) => (
+            reg(vx),
+            if quirks.add_to_index_overflow_sets_flag_register { vf } else { 0 },
+        ),
+        Instruction::JumpWithOffset(_, vx) => (
+            if quirks.jump_with_offset_uses_vx { reg(vx) } else { reg(0) },
+            0,
+        ),
+        Instruction::Draw(vx, vy, _) => (reg(vx) | reg(vy), vf),
+        Instruction::Load(vx) | Instruction::LoadFlags(vx) => (0, range(0, vx)),
+        Instruction::Store(vx) | Instruction::StoreFlags(vx) => (range(0, vx), 0),
+        Instruction::LoadRange(vstart, vend) => (0, range(vstart, vend)),
+        Instruction::StoreRange(vstart, vend) => (range(vstart, vend), 0),
+        _ => (0, 0),
+    }
+}
+
+// Why the program is not advancing, which the interpreter and vm otherwise only expose as separate flags
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StallReason {
+    WaitingForKey(u8), // register the key will be stored in
+    WaitingForVerticalBlank,
+    Halted, // the instruction at the pc exits or cannot be decoded
+    Paused, // the cpu is frozen
+}
+
+// How often the program drew sprites and cleared the screen since the last reset, which characterizes how graphics
+// heavy it is. Undoing an instruction takes it back out of the counts so they follow the execution history
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DrawStats {
+    pub draws: u64,
+    pub clears: u64,
+}
+
+#[derive(Clone)]
 pub struct Interpreter {
     pub memory: Vec<u8>,
     pub memory_last_address: u16,
     pub pc: u16,
     pub index: u16,
-    pub stack: Vec<u16>,
+    pub stack: Stack,
     pub flags: [u8; 16],
     pub registers: [u8; 16],
     pub rom: Rom,
@@ -49,17 +225,27 @@ pub struct Interpreter {
     pub audio: Audio,
     pub input: InterpreterInput,
     pub output: Option<InterpreterOutput>,
+    pub settings: InterpreterSettings,
+    written_registers: u16,
+    stack_depth_warned: bool,
+    pub draw_stats: DrawStats,
     instruction: Option<(Instruction, u16)>,
     prefetch: Vec<Option<(Instruction, u16)>>,
     workspace: [u8; 128],
-    error: String,
-    valid: bool,
+    decode_error: Option<InstructionDecodeError>,
+    error: Option<InterpreterError>,
     rng: StdRng,
+    rng_seed: u64,
 }
 
 impl Interpreter {
     pub fn new(rom: Rom) -> Self {
-        let memory = allocate_memory(&rom);
+        let memory_size = rom.config.kind.default_memory_size();
+        Self::with_memory_size(rom, memory_size)
+    }
+
+    pub fn with_memory_size(rom: Rom, memory_size: usize) -> Self {
+        let memory = allocate_memory(&rom, memory_size);
         let memory_last_address = (memory.len() - 1) as u16;
         let prefetch = memory
             .instruction_parameters()
@@ -70,24 +256,30 @@ impl Interpreter {
                     .map(|inst| (inst, inst.size()))
             })
             .collect();
+        let rng_seed = StdRng::from_entropy().next_u64();
         let mut interp = Interpreter {
             memory_last_address,
             memory,
             pc: PROGRAM_STARTING_ADDRESS,
             index: 0,
-            stack: Vec::with_capacity(16),
+            stack: Stack::default(),
             flags: [0; 16],
-            registers: [0; 16],
-            rng: StdRng::from_entropy(),
+            registers: rom.fill.registers(),
+            rng: StdRng::seed_from_u64(rng_seed),
+            rng_seed,
             display: Default::default(),
             waiting: false,
             audio: Audio::from(rom.config.kind),
             input: Default::default(),
             output: None,
+            settings: InterpreterSettings::default(),
+            written_registers: 0,
+            stack_depth_warned: false,
+            draw_stats: DrawStats::default(),
             instruction: None,
             workspace: [0; 128],
-            error: String::new(),
-            valid: true,
+            decode_error: None,
+            error: None,
             prefetch,
             rom,
         };
@@ -96,43 +288,119 @@ impl Interpreter {
         interp
     }
 
-    pub fn reset(&mut self, preserve_rpl_flags: bool) {
-        let flags = self.flags;
-        let rom = self.rom.clone();
-
-        *self = Interpreter::new(rom);
-        if preserve_rpl_flags {
-            self.flags = flags;
-        }
+    // the seed the rng started from, which repeats the random numbers of a run when given back to seed_rng
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
     }
 
-    // TODO: this needs to be removed since all chip8 specifications wait for the key up in the Get Key (FX0A) instruction
-    pub fn pick_key<'a, 'b, T: TryInto<Key>>(
-        &'a self,
-        _: &'b Option<T>,
-        key_up: &'b Option<T>,
-    ) -> &'b Option<T> {
-        key_up
+    // only repeats a run when seeded before the program generates its first random number
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.rng_seed = seed;
     }
 
     pub fn instruction(&self) -> Option<Instruction> {
         self.instruction.map(|(inst, _)| inst)
     }
 
-    pub fn stop_result(&self) -> Result<bool, String> {
-        if self.valid {
-            Ok(false)
-        } else {
-            Err(self.error.clone())
+    // an instruction that failed while executing is not reported as halted since that is only known by executing it
+    pub fn stall_reason(&self) -> Option<StallReason> {
+        match self.instruction() {
+            Some(Instruction::WaitForKey(vx)) if self.waiting => Some(StallReason::WaitingForKey(vx)),
+            Some(Instruction::Draw(..)) if self.waiting => Some(StallReason::WaitingForVerticalBlank),
+            Some(Instruction::Exit) => Some(StallReason::Halted),
+            None if self.settings.unknown_opcode_mode != UnknownOpcodeMode::Nop && self.skipped_machine_call().is_none() => {
+                Some(StallReason::Halted)
+            }
+            _ => None,
         }
     }
 
+    // address of a hex digit's sprite in the font, the same one SetIndexToHexChar (FX29) points the index at
+    pub fn font_char_address(c: u8) -> Option<u16> {
+        (c <= 0xF).then(|| FONT_STARTING_ADDRESS + FONT_CHAR_DATA_SIZE as u16 * c as u16)
+    }
+
+    // address of a decimal digit's sprite in the big font, the same one SetIndexToBigHexChar (FX30) points the index at
+    pub fn big_font_char_address(c: u8) -> Option<u16> {
+        (c <= 0x9).then(|| BIG_FONT_STARTING_ADDRESS + BIG_FONT_CHAR_DATA_SIZE as u16 * c as u16)
+    }
+
+    // the font as it is in memory, which a rom may have overwritten
+    pub fn font_bytes(&self) -> &[u8] {
+        let start = FONT_STARTING_ADDRESS as usize;
+        &self.memory[start..start + FONT.len()]
+    }
+
+    // decodes the loaded program two bytes at a time from the load address,
+    // so the second half of a long instruction is also decoded on its own
+    pub fn decoded_instructions(&self) -> impl Iterator<Item = (u16, Result<Instruction, String>)> + '_ {
+        let kind = self.rom.config.kind;
+        let start = PROGRAM_STARTING_ADDRESS as u32;
+        (start..start + self.rom.data.len() as u32)
+            .step_by(2)
+            .map(move |address| {
+                let mut bytes = [0; Instruction::MAX_INSTRUCTION_SIZE as usize];
+                self.memory.export(address as u16, &mut bytes);
+                (
+                    address as u16,
+                    Instruction::try_from_u32(u32::from_be_bytes(bytes), kind)
+                        .map_err(|e| e.to_string()),
+                )
+            })
+    }
+
+    pub fn stop_result(&self) -> Result<bool, InterpreterError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(false),
+        }
+    }
+
+    // the address called by the undecodable opcode at the pc when it is a 0NNN machine code call to step over
+    // the opcodes 0NNN shares its family with always decode first, so anything left in the family is a call
+    fn skipped_machine_call(&self) -> Option<u16> {
+        if self.settings.machine_call_mode == MachineCallMode::Unknown || self.instruction.is_some() {
+            return None;
+        }
+
+        let mut bytes = [0; 2];
+        self.memory.export(self.pc, &mut bytes);
+        let opcode = u16::from_be_bytes(bytes);
+        (opcode & 0xF000 == 0).then_some(opcode & 0x0FFF)
+    }
+
     // interpret the current instruction
     #[inline(always)]
     pub fn step(&mut self) -> bool {
         let Some((instruction, instruction_size)) = self.instruction else {
-            self.valid = false;
-            self.error = format!("Decode at {:#05X?} failed: {}", self.pc, self.error);
+            if let Some(address) = self.skipped_machine_call() {
+                if self.settings.machine_call_mode == MachineCallMode::Log {
+                    log::warn!("Skipping unsupported machine code call to {:#05X?} at {:#05X?}", address, self.pc);
+                }
+                self.pc = self.pc.overflowing_add(2).0 & self.memory_last_address;
+                self.fetch_decode();
+                return true;
+            }
+
+            let decode_error = self.decode_error.expect("an instruction that is not decoded has a decode error");
+            match self.settings.unknown_opcode_mode {
+                UnknownOpcodeMode::Strict => {
+                    self.error = Some(InterpreterError::Decode {
+                        address: self.pc,
+                        error: decode_error,
+                    });
+                }
+                UnknownOpcodeMode::Nop => {
+                    log::debug!("Skipping undecodable opcode at {:#05X?}: {}", self.pc, decode_error);
+                    self.pc = self.pc.overflowing_add(2).0 & self.memory_last_address;
+                    self.fetch_decode();
+                    return true;
+                }
+                UnknownOpcodeMode::Halt => {
+                    log::info!("Halting at undecodable opcode at {:#05X?}: {}", self.pc, decode_error);
+                }
+            }
             return false;
         };
 
@@ -180,7 +448,7 @@ impl Interpreter {
             }
             Err(e) => {
                 self.instruction = None;
-                self.error = e.to_string();
+                self.decode_error = Some(e);
             }
         }
     }
@@ -189,9 +457,13 @@ impl Interpreter {
     fn exec(&mut self, inst: Instruction) -> bool {
         let mut skip_next_instruction = false;
 
+        if self.settings.warn_uninit_reads {
+            self.check_uninit_reads(inst);
+        }
+
         match inst {
             Instruction::Exit => {
-                self.valid = true;
+                self.error = None;
                 return false;
             }
 
@@ -208,14 +480,32 @@ impl Interpreter {
             }
 
             Instruction::CallSubroutine(address) => {
-                self.stack.push(self.pc);
+                if !self.stack.push(self.pc) {
+                    self.error = Some(InterpreterError::StackFull { address });
+                    return false;
+                }
+
+                // only the first time since a recursive rom would otherwise warn on every call
+                match self.settings.warn_stack_depth {
+                    Some(depth) if self.stack.len() > depth && !self.stack_depth_warned => {
+                        log::warn!(
+                            "call {:#05X} at {:#05X} nested the stack {} deep, past the {} real hardware allows",
+                            address,
+                            self.pc.wrapping_sub(2) & self.memory_last_address,
+                            self.stack.len(),
+                            depth
+                        );
+                        self.stack_depth_warned = true;
+                    }
+                    _ => (),
+                }
+
                 self.pc = address & self.memory_last_address;
             }
 
             Instruction::SubroutineReturn => {
                 let Some(pc) = self.stack.pop() else {
-                    self.valid = false;
-                    self.error = "Could not return from subroutine because stack is empty".to_string();
+                    self.error = Some(InterpreterError::StackEmpty);
                     return false
                 };
 
@@ -272,7 +562,20 @@ impl Interpreter {
             Instruction::SetConstant(vx, value) => self.registers[vx as usize] = value,
 
             Instruction::AddConstant(vx, change) => {
-                self.registers[vx as usize] = self.registers[vx as usize].overflowing_add(change).0
+                let value = self.registers[vx as usize];
+                let (result, overflowed) = value.overflowing_add(change);
+                if overflowed && self.settings.warn_add_overflow {
+                    log::warn!(
+                        "add v{:x} {} at {:#05X} overflowed: {} + {} wrapped to {}",
+                        vx,
+                        change,
+                        self.pc.wrapping_sub(2) & self.memory_last_address,
+                        value,
+                        change,
+                        result
+                    );
+                }
+                self.registers[vx as usize] = result
             }
 
             Instruction::Set(vx, vy) => self.registers[vx as usize] = self.registers[vy as usize],
@@ -316,6 +619,8 @@ impl Interpreter {
                 self.registers[VFLAG] = !overflowed as u8; // vf is 0 on overflow instead of 1 like add
             }
 
+            // the VIP shifts vy into vx while later interpreters shift vx in place. the source is read before any write
+            // and the shifted out bit is written last, so vx == vy reads the original value and vx == vf keeps the flag
             Instruction::Shift(vx, vy, right) => {
                 let bits = if self.rom.config.quirks.bit_shift_modifies_vx_in_place {
                     self.registers[vx as usize]
@@ -352,36 +657,33 @@ impl Interpreter {
 
             Instruction::SetIndexToHexChar(vx) => {
                 let c = self.registers[vx as usize];
-                if c > 0xF {
-                    self.valid = false;
-                    self.error =
-                        format!("Failed to set index: hex char \"{:X}\" does not exist", c);
+                let Some(address) = Self::font_char_address(c) else {
+                    self.error = Some(InterpreterError::MissingHexChar(c));
                     return false;
-                }
+                };
 
-                self.index = FONT_STARTING_ADDRESS + FONT_CHAR_DATA_SIZE as u16 * c as u16;
+                self.index = address;
             }
 
             Instruction::SetIndexToBigHexChar(vx) => {
                 let c = self.registers[vx as usize];
-                if c > 0x9 {
-                    self.valid = false;
-                    self.error = format!(
-                        "Failed to set index: big hex char \"{:X}\" does not exist",
-                        c
-                    );
+                let Some(address) = Self::big_font_char_address(c) else {
+                    self.error = Some(InterpreterError::MissingBigHexChar(c));
                     return false;
-                }
+                };
 
-                self.index = BIG_FONT_STARTING_ADDRESS + BIG_FONT_CHAR_DATA_SIZE as u16 * c as u16;
+                self.index = address;
             }
 
             Instruction::AddToIndex(vx) => {
-                self.index = self
-                    .index
-                    .overflowing_add(self.registers[vx as usize] as u16)
-                    .0
-                    & self.memory_last_address;
+                let index = self.index as u32 + self.registers[vx as usize] as u32;
+
+                // the Amiga interpreter sets vf when i leaves 12-bit addressable memory (Spacefight 2091! relies on it)
+                if self.rom.config.quirks.add_to_index_overflow_sets_flag_register {
+                    self.registers[VFLAG] = (index > 0x0FFF) as u8;
+                }
+
+                self.index = index as u16 & self.memory_last_address;
             }
 
             Instruction::Load(vx) => {
@@ -451,6 +753,8 @@ impl Interpreter {
             }
 
             Instruction::StoreBinaryCodedDecimal(vx) => {
+                // hundreds, tens, ones (255 -> 2 5 5, 0 -> 0 0 0) and like every other store the digits
+                // wrap to the start of memory when i is within 2 bytes of the end instead of failing
                 let decimal = self.registers[vx as usize];
                 self.workspace[..3]
                     .iter_mut()
@@ -479,6 +783,7 @@ impl Interpreter {
                 } else {
                     self.waiting = false;
                     self.exec_display_instruction(vx, vy, height);
+                    self.draw_stats.draws += 1;
                     self.output = Some(InterpreterOutput::Display);
                 }
             }
@@ -515,6 +820,7 @@ impl Interpreter {
 
             Instruction::ClearScreen => {
                 self.display.clear();
+                self.draw_stats.clears += 1;
                 self.output = Some(InterpreterOutput::Display);
             }
 
@@ -531,20 +837,23 @@ impl Interpreter {
 
         if skip_next_instruction {
             // NOTE: fetch decode is kinda expensive so check specifically for F000
-            self.pc = self
-                .pc
-                .overflowing_add(
-                    if self.rom.config.kind == RomKind::XOCHIP
-                        && self.memory[self.pc as usize] == 0xF0
-                        && self.memory[(self.pc as usize + 1) % self.memory.len()] == 0x00
-                    {
-                        4
-                    } else {
-                        2
-                    },
-                )
-                .0
-                & self.memory_last_address;
+            let skipped_pc = self.pc as u32
+                + if self.rom.config.kind == RomKind::XOCHIP
+                    && self.memory[self.pc as usize] == 0xF0
+                    && self.memory[(self.pc as usize + 1) % self.memory.len()] == 0x00
+                {
+                    4
+                } else {
+                    2
+                };
+
+            // fail here rather than wrapping around and failing later at a confusing address
+            if skipped_pc > self.memory_last_address as u32 {
+                self.error = Some(InterpreterError::SkipPastEndOfMemory { address: self.pc });
+                return false;
+            }
+
+            self.pc = skipped_pc as u16 & self.memory_last_address;
         }
 
         true
@@ -556,14 +865,55 @@ impl Interpreter {
         self.memory
             .export(self.index, &mut self.workspace[..total_bytes]);
 
-        self.registers[VFLAG] = self.display.draw(
-            &self.workspace,
+        let pos_x = self.registers[vx as usize] as u16;
+        let pos_y = self.registers[vy as usize] as u16;
+        let wrap = !self.rom.config.quirks.sprites_clip_at_screen_edges;
+
+        // erased pixels are only worth computing when someone asked to trace the draw pixel by pixel
+        let collision = if self.settings.trace_erased_pixels {
+            let (collision, erased) =
+                self.display
+                    .draw_reporting_erased(&self.workspace, pos_x, pos_y, height, bytes_per_row, wrap);
+            log::trace!(
+                "Draw at ({}, {}) erased {} pixels (plane, x, y): {:?}",
+                pos_x,
+                pos_y,
+                erased.len(),
+                erased
+            );
+            collision
+        } else {
+            self.display
+                .draw(&self.workspace, pos_x, pos_y, height, bytes_per_row, wrap)
+        };
+
+        // by default vf only reflects the latest draw, so a colliding draw followed by one that doesn't leaves vf at 0
+        // some interpreters instead accumulate collisions until the program overwrites vf
+        if self.rom.config.quirks.draw_ors_collision_into_flag_register {
+            self.registers[VFLAG] |= collision as u8;
+        } else {
+            self.registers[VFLAG] = collision as u8;
+        }
+    }
+
+    // the pixels the draw at the pc would flip as (plane, x, y), or nothing if the pc is not at a draw
+    pub fn draw_footprint(&self) -> Vec<(usize, u16, u16)> {
+        let Some(Instruction::Draw(vx, vy, n)) = self.instruction() else {
+            return Vec::new();
+        };
+
+        let (bytes_per_row, height, total_bytes) = self.get_sprite_draw_info(n);
+        let mut sprite = vec![0; total_bytes];
+        self.memory.export(self.index, &mut sprite);
+
+        self.display.draw_footprint(
+            &sprite,
             self.registers[vx as usize] as u16,
             self.registers[vy as usize] as u16,
             height,
             bytes_per_row,
             !self.rom.config.quirks.sprites_clip_at_screen_edges,
-        ) as u8;
+        )
     }
 
     // (bytes per row, rows per plane, total bytes to read)
@@ -583,19 +933,105 @@ impl Interpreter {
         }
     }
 
-    pub fn undo(&mut self, prior_state: &InterpreterHistoryFragment, memory_access_flags: &mut [u8]) {
-        let Some(instruction) = prior_state.instruction.as_ref() else {
-            unreachable!("Cannot undo to a state without an instruction")
-        };
+    // each register is only warned about once since an instruction waiting to finish executes repeatedly
+    fn check_uninit_reads(&mut self, inst: Instruction) {
+        let (reads, writes) = register_access(inst, self.rom.config.quirks);
+        let uninit_reads = reads & !self.written_registers;
+
+        for register in (0..16).filter(|register| uninit_reads >> register & 1 == 1) {
+            log::warn!(
+                "Instruction at {:#05X} read v{:x} before anything was written to it",
+                self.pc.wrapping_sub(inst.size()) & self.memory_last_address,
+                register
+            );
+        }
+
+        self.written_registers |= uninit_reads | writes;
+    }
+}
+
+// inspecting, editing, and rewinding the program on behalf of the debugger
+#[cfg(feature = "debugger")]
+impl Interpreter {
+    pub fn reset(&mut self, preserve_rpl_flags: bool) {
+        let flags = self.flags;
+        let rom = self.rom.clone();
+        let settings = self.settings;
+        let colors = self.display.colors;
+
+        *self = Interpreter::with_memory_size(rom, self.memory.len());
+        self.settings = settings;
+        self.display.colors = colors;
+        if preserve_rpl_flags {
+            self.flags = flags;
+        }
+    }
+
+    // takes on the program state of a copy made earlier while keeping the current settings
+    pub fn restore_copy(&mut self, copy: &Interpreter) {
+        let settings = self.settings;
+
+        *self = copy.clone();
+        self.settings = settings;
+    }
+
+    // TODO: this needs to be removed since all chip8 specifications wait for the key up in the Get Key (FX0A) instruction
+    pub fn pick_key<'a, 'b, T: TryInto<Key>>(
+        &'a self,
+        _: &'b Option<T>,
+        key_up: &'b Option<T>,
+    ) -> &'b Option<T> {
+        key_up
+    }
+
+    // names the region the pc is in when it is unlikely to hold code: the font or bytes only ever used as data
+    pub fn data_execution_region(&self, memory_access_flags: &[u8]) -> Option<&'static str> {
+        let font_range = FONT_STARTING_ADDRESS..FONT_STARTING_ADDRESS + FONT.len() as u16;
+        if font_range.contains(&self.pc) {
+            return Some("font");
+        }
+
+        let flags = memory_access_flags[self.pc as usize];
+        if flags & MEM_ACCESS_EXEC_FLAG == 0
+            && flags & (MEM_ACCESS_WRITE_FLAG | MEM_ACCESS_DRAW_FLAG) != 0
+        {
+            Some("data")
+        } else {
+            None
+        }
+    }
 
+    pub fn undo(&mut self, prior_state: &InterpreterHistoryFragment, memory_access_flags: &mut [u8]) {
         self.pc = prior_state.pc;
         self.index = prior_state.index;
         self.registers = prior_state.registers;
-        self.instruction = Some((*instruction, instruction.size()));
-        self.waiting = false;
-        
+        self.waiting = prior_state.waiting;
+
         memory_access_flags[self.pc as usize] = prior_state.pc_access_flags;
 
+        match prior_state.instruction {
+            Some(Instruction::Draw(..)) => self.draw_stats.draws = self.draw_stats.draws.saturating_sub(1),
+            Some(Instruction::ClearScreen) => self.draw_stats.clears = self.draw_stats.clears.saturating_sub(1),
+            _ => (),
+        }
+
+        // an injected instruction or memory edit never replaced the instruction at the pc by executing it
+        // so there is nothing else to revert
+        if prior_state.injected {
+            if let Some(extra) = prior_state.extra.as_deref() {
+                self.undo_extra(extra, memory_access_flags);
+            }
+            return;
+        }
+
+        // only an undecodable opcode skipped as a no-op is recorded without an instruction and it only moved the pc
+        let Some(instruction) = prior_state.instruction.as_ref() else {
+            self.fetch_decode();
+            return;
+        };
+
+        self.instruction = Some((*instruction, instruction.size()));
+
         match instruction {
             Instruction::CallSubroutine(_) => {
                 self.stack.pop();
@@ -607,10 +1043,12 @@ impl Interpreter {
             _ => (),
         }
 
-        let Some(extra) = prior_state.extra.as_deref() else {
-            return
-        };
+        if let Some(extra) = prior_state.extra.as_deref() {
+            self.undo_extra(extra, memory_access_flags);
+        }
+    }
 
+    fn undo_extra(&mut self, extra: &InterpreterHistoryFragmentExtra, memory_access_flags: &mut [u8]) {
         match extra {
             InterpreterHistoryFragmentExtra::WillGenerateRandom { prior_rng } => {
                 self.rng = prior_rng.as_ref().clone();
@@ -642,11 +1080,18 @@ impl Interpreter {
 
             InterpreterHistoryFragmentExtra::WillStoreInMemory {
                 prior_index_memory,
-                prior_index_access_flag_slice: index_access_flag_slice,
+                prior_index_access_flags,
             } => {
                 self.memory.import(prior_index_memory, self.index);
                 memory_access_flags
-                    .import(index_access_flag_slice, self.index);
+                    .import(prior_index_access_flags, self.index);
+
+                // restored bytes may be code so anything decoded from the overwritten bytes is stale
+                let (prefetch_range0, prefetch_range1) = self
+                    .memory
+                    .affected_instruction_range(self.index, prior_index_memory.len() as u16);
+                self.prefetch[prefetch_range0].fill(None);
+                self.prefetch[prefetch_range1].fill(None);
             }
 
             InterpreterHistoryFragmentExtra::WillStoreInFlags { prior_flags } => {
@@ -685,12 +1130,120 @@ impl Interpreter {
                 self.display.mode = *prior_display_mode;
                 self.display.planes = **prior_display_buffers;
             }
+
+            InterpreterHistoryFragmentExtra::WillEditMemory {
+                address,
+                prior_memory,
+                ..
+            } => {
+                self.write_memory(*address, prior_memory);
+            }
+
+            // the prior pc was already restored so only the instruction there needs to be fetched again
+            InterpreterHistoryFragmentExtra::WillSetProgramCounter { .. } => {
+                self.fetch_decode();
+            }
         }
     }
 
+    // execute an instruction on behalf of the debugger without moving the pc
+    pub fn inject(&mut self, instruction: Instruction) {
+        let pc = self.pc;
+        self.exec(instruction);
+        self.pc = pc;
+    }
+
     pub fn to_history_fragment(&self, memory_access_flags: &[u8]) -> InterpreterHistoryFragment {
         let instruction = self.instruction();
-        let extra = instruction.and_then(|instruction| match instruction {
+        InterpreterHistoryFragment {
+            pc: self.pc,
+            pc_access_flags: memory_access_flags[self.pc as usize],
+            waiting: self.waiting,
+            instruction,
+            injected: false,
+            index: self.index,
+            registers: self.registers,
+            extra: instruction
+                .and_then(|instruction| self.to_history_fragment_extra(instruction, memory_access_flags)),
+        }
+    }
+
+    // only what update_memory_access_flags reads, without the payloads needed to undo the instruction
+    pub fn to_executed_fragment(&self) -> InterpreterHistoryFragment {
+        InterpreterHistoryFragment {
+            pc: self.pc,
+            pc_access_flags: 0,
+            waiting: self.waiting,
+            instruction: self.instruction(),
+            injected: false,
+            index: self.index,
+            registers: self.registers,
+            extra: None,
+        }
+    }
+
+    pub fn to_injected_history_fragment(
+        &self,
+        instruction: Instruction,
+        memory_access_flags: &[u8],
+    ) -> InterpreterHistoryFragment {
+        InterpreterHistoryFragment {
+            pc: self.pc,
+            pc_access_flags: memory_access_flags[self.pc as usize],
+            waiting: self.waiting,
+            instruction: Some(instruction),
+            injected: true,
+            index: self.index,
+            registers: self.registers,
+            extra: self.to_history_fragment_extra(instruction, memory_access_flags),
+        }
+    }
+
+    // a debugger memory edit has no instruction so it keeps both the old and new bytes to undo and redo it
+    pub fn to_memory_edit_history_fragment(
+        &self,
+        address: u16,
+        bytes: &[u8],
+        memory_access_flags: &[u8],
+    ) -> InterpreterHistoryFragment {
+        let mut prior_memory = vec![0; bytes.len()];
+        self.memory.export(address, &mut prior_memory);
+
+        InterpreterHistoryFragment {
+            pc: self.pc,
+            pc_access_flags: memory_access_flags[self.pc as usize],
+            waiting: self.waiting,
+            instruction: None,
+            injected: true,
+            index: self.index,
+            registers: self.registers,
+            extra: Some(Box::new(InterpreterHistoryFragmentExtra::WillEditMemory {
+                address,
+                prior_memory,
+                memory: bytes.to_vec(),
+            })),
+        }
+    }
+
+    pub fn to_pc_edit_history_fragment(&self, pc: u16, memory_access_flags: &[u8]) -> InterpreterHistoryFragment {
+        InterpreterHistoryFragment {
+            pc: self.pc,
+            pc_access_flags: memory_access_flags[self.pc as usize],
+            waiting: self.waiting,
+            instruction: None,
+            injected: true,
+            index: self.index,
+            registers: self.registers,
+            extra: Some(Box::new(InterpreterHistoryFragmentExtra::WillSetProgramCounter { pc })),
+        }
+    }
+
+    fn to_history_fragment_extra(
+        &self,
+        instruction: Instruction,
+        memory_access_flags: &[u8],
+    ) -> Option<Box<InterpreterHistoryFragmentExtra>> {
+        match instruction {
             Instruction::GenerateRandom(_, _) => Some(Box::new(
                 InterpreterHistoryFragmentExtra::WillGenerateRandom {
                     prior_rng: Box::new(self.rng.clone()),
@@ -753,17 +1306,23 @@ impl Interpreter {
                 }
             })),
 
+            // capture exactly the bytes the store will overwrite so undo restores nothing more and nothing less
             Instruction::Store(_)
             | Instruction::StoreRange(_, _)
             | Instruction::StoreBinaryCodedDecimal(_) => Some(Box::new({
-                let mut index_memory = [0; 16];
-                let mut index_access_flag_slice = [0; 16];
-                self.memory.export(self.index, &mut index_memory);
+                let size = match instruction {
+                    Instruction::Store(vx) => vx as usize + 1,
+                    Instruction::StoreRange(vstart, vend) => vstart.abs_diff(vend) as usize + 1,
+                    _ => 3,
+                };
+                let mut prior_index_memory = vec![0; size];
+                let mut prior_index_access_flags = vec![0; size];
+                self.memory.export(self.index, &mut prior_index_memory);
                 memory_access_flags
-                    .export(self.index, &mut index_access_flag_slice);
+                    .export(self.index, &mut prior_index_access_flags);
                 InterpreterHistoryFragmentExtra::WillStoreInMemory {
-                    prior_index_memory: index_memory,
-                    prior_index_access_flag_slice: index_access_flag_slice,
+                    prior_index_memory,
+                    prior_index_access_flags,
                 }
             })),
 
@@ -796,15 +1355,6 @@ impl Interpreter {
             }
 
             _ => None,
-        });
-
-        InterpreterHistoryFragment {
-            pc: self.pc,
-            pc_access_flags: memory_access_flags[self.pc as usize],
-            instruction,
-            index: self.index,
-            registers: self.registers,
-            extra,
         }
     }
 
@@ -899,7 +1449,32 @@ impl Interpreter {
     }
 }
 
+// tests set up programs through these as well
+#[cfg(any(feature = "debugger", test))]
+impl Interpreter {
+    // move the pc on behalf of the debugger, abandoning any instruction that was waiting to finish
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc & self.memory_last_address;
+        self.waiting = false;
+        self.fetch_decode();
+    }
+
+    // write memory on behalf of the debugger, refetching in case the bytes at the pc changed
+    pub fn write_memory(&mut self, address: u16, bytes: &[u8]) {
+        self.memory.import(bytes, address);
+
+        let (prefetch_range0, prefetch_range1) = self
+            .memory
+            .affected_instruction_range(address, bytes.len() as u16);
+        self.prefetch[prefetch_range0].fill(None);
+        self.prefetch[prefetch_range1].fill(None);
+
+        self.fetch_decode();
+    }
+}
+
 #[derive(Eq, PartialEq, Debug)]
+#[cfg(feature = "debugger")]
 pub enum InterpreterHistoryFragmentExtra {
     WillGenerateRandom {
         prior_rng: Box<StdRng>,
@@ -918,8 +1493,8 @@ pub enum InterpreterHistoryFragmentExtra {
         prior_index_access_flags: Vec<u8>,
     },
     WillStoreInMemory {
-        prior_index_memory: [u8; 16],
-        prior_index_access_flag_slice: [u8; 16],
+        prior_index_memory: Vec<u8>,
+        prior_index_access_flags: Vec<u8>,
     },
     WillStoreInFlags {
         prior_flags: [u8; 16],
@@ -934,19 +1509,51 @@ pub enum InterpreterHistoryFragmentExtra {
         prior_buffer: [u8; AUDIO_BUFFER_SIZE_BYTES],
         prior_index_access_flag_slice: [u8; 16],
     },
+    WillEditMemory {
+        address: u16,
+        prior_memory: Vec<u8>,
+        memory: Vec<u8>,
+    },
+    WillSetProgramCounter {
+        pc: u16,
+    },
 }
 
 #[derive(PartialEq, Eq, Debug)]
+#[cfg(feature = "debugger")]
 pub struct InterpreterHistoryFragment {
     pub instruction: Option<Instruction>,
+    pub injected: bool, // instruction was executed by the debugger rather than fetched from the pc
     pub pc: u16,
     pub pc_access_flags: u8,
+    pub waiting: bool, // the instruction at the pc had already run without finishing (e.g. no key was released yet)
     pub index: u16,
     pub registers: [u8; 16],
     pub extra: Option<Box<InterpreterHistoryFragmentExtra>>,
 }
 
+#[cfg(feature = "debugger")]
 impl InterpreterHistoryFragment {
+    // the address, prior bytes, and written bytes of a debugger memory edit
+    pub fn memory_edit(&self) -> Option<(u16, &[u8], &[u8])> {
+        match self.extra.as_deref() {
+            Some(InterpreterHistoryFragmentExtra::WillEditMemory {
+                address,
+                prior_memory,
+                memory,
+            }) => Some((*address, prior_memory.as_slice(), memory.as_slice())),
+            _ => None,
+        }
+    }
+
+    // the pc a debugger jump moved to
+    pub fn pc_edit(&self) -> Option<u16> {
+        match self.extra.as_deref() {
+            Some(InterpreterHistoryFragmentExtra::WillSetProgramCounter { pc }) => Some(*pc),
+            _ => None,
+        }
+    }
+
     pub fn log_diff(&self, other: &Self) {
         if self.instruction != other.instruction {
             log::debug!(
@@ -965,6 +1572,9 @@ impl InterpreterHistoryFragment {
                 other.pc_access_flags
             );
         }
+        if self.waiting != other.waiting {
+            log::debug!("Waiting difference: {:?} -> {:?}", self.waiting, other.waiting);
+        }
         if self.index != other.index {
             log::debug!("Index difference: {:?} -> {:?}", self.index, other.index);
         }
@@ -980,3 +1590,491 @@ impl InterpreterHistoryFragment {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testlog;
+
+    fn interpreter(kind: RomKind, quirks: RomQuirks, program: &[u8]) -> Interpreter {
+        let mut rom = Rom::with_program(kind, program);
+        rom.config.quirks = quirks;
+        Interpreter::new(rom)
+    }
+
+    fn chip8(program: &[u8]) -> Interpreter {
+        interpreter(RomKind::CHIP8, RomKind::CHIP8.default_rom_quirks(), program)
+    }
+
+    fn run(interp: &mut Interpreter, steps: usize) {
+        for _ in 0..steps {
+            assert!(interp.step(), "step at {:#05X} failed: {:?}", interp.pc, interp.stop_result());
+        }
+    }
+
+    #[test]
+    fn add_to_index_overflow_sets_flag_register_only_with_quirk() {
+        // ld vf 5, ld i 0xFF0, ld v0 0x20, add i v0
+        let program = [0x6F, 0x05, 0xAF, 0xF0, 0x60, 0x20, 0xF0, 0x1E];
+
+        let mut interp = chip8(&program);
+        run(&mut interp, 4);
+        assert_eq!(interp.index, 0x010);
+        assert_eq!(interp.registers[VFLAG], 5);
+
+        let mut quirks = RomKind::CHIP8.default_rom_quirks();
+        quirks.add_to_index_overflow_sets_flag_register = true;
+        let mut interp = interpreter(RomKind::CHIP8, quirks, &program);
+        run(&mut interp, 4);
+        assert_eq!(interp.index, 0x010);
+        assert_eq!(interp.registers[VFLAG], 1);
+    }
+
+    #[test]
+    fn add_to_index_within_bounds_clears_flag_register_with_quirk() {
+        // ld vf 5, ld i 0xFE0, ld v0 0x1F, add i v0
+        let program = [0x6F, 0x05, 0xAF, 0xE0, 0x60, 0x1F, 0xF0, 0x1E];
+
+        let mut quirks = RomKind::CHIP8.default_rom_quirks();
+        quirks.add_to_index_overflow_sets_flag_register = true;
+        let mut interp = interpreter(RomKind::CHIP8, quirks, &program);
+        run(&mut interp, 4);
+        assert_eq!(interp.index, 0xFFF);
+        assert_eq!(interp.registers[VFLAG], 0);
+    }
+
+    // se v0 0 at 0xFFC skips the instruction at 0xFFE, which would put the pc at 0x1000
+    fn skip_at_top_of_memory() -> Interpreter {
+        let mut interp = chip8(&[0x00, 0xE0]);
+        interp.write_memory(0xFFC, &[0x30, 0x00, 0x00, 0xE0]);
+        interp.set_pc(0xFFC);
+        interp
+    }
+
+    #[test]
+    fn skip_past_end_of_memory_is_error() {
+        let mut interp = skip_at_top_of_memory();
+        assert!(!interp.step());
+        assert_eq!(interp.pc, 0xFFC);
+        assert_eq!(interp.stop_result(), Err(InterpreterError::SkipPastEndOfMemory { address: 0xFFE }));
+    }
+
+    #[test]
+    fn skip_not_taken_at_top_of_memory_is_not_error() {
+        let mut interp = skip_at_top_of_memory();
+        interp.registers[0] = 1;
+        run(&mut interp, 1);
+        assert_eq!(interp.pc, 0xFFE);
+    }
+
+    #[test]
+    fn call_past_stack_size_is_error() {
+        // call 0x200 from 0x200, nesting one deeper every step
+        let mut interp = chip8(&[0x22, 0x00]);
+        run(&mut interp, STACK_SIZE);
+        assert_eq!(interp.stack.len(), STACK_SIZE);
+        assert!(interp.stack.iter().all(|&address| address == 0x202));
+
+        assert!(!interp.step());
+        assert_eq!(interp.pc, 0x200);
+        assert_eq!(interp.stack.len(), STACK_SIZE);
+        assert_eq!(interp.stop_result(), Err(InterpreterError::StackFull { address: 0x200 }));
+    }
+
+    #[test]
+    fn return_with_empty_stack_is_error() {
+        let mut interp = chip8(&[0x00, 0xEE]);
+        assert!(!interp.step());
+        assert_eq!(interp.stop_result(), Err(InterpreterError::StackEmpty));
+    }
+
+    // draws the 0 glyph at (0, 0) twice, colliding the second time, then at (10, 0) without colliding
+    const CHAINED_DRAWS: [u8; 12] = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05, 0x61, 0x0A, 0xD1, 0x05];
+
+    #[test]
+    fn draw_flag_register_reflects_only_the_latest_draw_by_default() {
+        let mut interp = chip8(&CHAINED_DRAWS);
+        run(&mut interp, 4);
+        assert_eq!(interp.registers[VFLAG], 1);
+        run(&mut interp, 2);
+        assert_eq!(interp.registers[VFLAG], 0);
+    }
+
+    #[test]
+    fn draw_flag_register_accumulates_collisions_with_quirk() {
+        let mut quirks = RomKind::CHIP8.default_rom_quirks();
+        quirks.draw_ors_collision_into_flag_register = true;
+
+        let mut interp = interpreter(RomKind::CHIP8, quirks, &CHAINED_DRAWS);
+        run(&mut interp, 3);
+        assert_eq!(interp.registers[VFLAG], 0);
+        run(&mut interp, 1);
+        assert_eq!(interp.registers[VFLAG], 1);
+        run(&mut interp, 2);
+        assert_eq!(interp.registers[VFLAG], 1);
+    }
+
+    // ld b v0 with v0 and i set directly so any value and address can be stored
+    fn store_decimal(value: u8, index: u16) -> Interpreter {
+        let mut interp = chip8(&[0xF0, 0x33]);
+        interp.registers[0] = value;
+        interp.index = index;
+        run(&mut interp, 1);
+        interp
+    }
+
+    #[test]
+    fn store_decimal_writes_hundreds_tens_and_ones() {
+        let cases = [
+            (0, [0, 0, 0]),
+            (7, [0, 0, 7]),
+            (42, [0, 4, 2]),
+            (100, [1, 0, 0]),
+            (255, [2, 5, 5]),
+        ];
+        for (value, digits) in cases {
+            let interp = store_decimal(value, 0x300);
+            assert_eq!(interp.memory[0x300..0x303], digits, "value {}", value);
+        }
+
+        for value in 0..=u8::MAX {
+            let interp = store_decimal(value, 0x300);
+            let digits = &interp.memory[0x300..0x303];
+            assert!(digits.iter().all(|&digit| digit < 10), "value {}", value);
+            assert_eq!(
+                digits[0] as u16 * 100 + digits[1] as u16 * 10 + digits[2] as u16,
+                value as u16
+            );
+        }
+    }
+
+    #[test]
+    fn store_decimal_at_end_of_memory_wraps_to_start() {
+        let len = chip8(&[]).memory.len();
+
+        let interp = store_decimal(123, (len - 3) as u16);
+        assert_eq!(interp.memory[len - 3..], [1, 2, 3]);
+        assert_eq!(interp.memory[..2], [0, 0]);
+
+        let interp = store_decimal(123, (len - 2) as u16);
+        assert_eq!(interp.memory[len - 2..], [1, 2]);
+        assert_eq!(interp.memory[..2], [3, 0]);
+
+        let interp = store_decimal(123, (len - 1) as u16);
+        assert_eq!(interp.memory[len - 1..], [1]);
+        assert_eq!(interp.memory[..2], [2, 3]);
+    }
+
+    // 8XY8 is not an instruction on any platform, followed by ld v0 1
+    fn unknown_opcode(mode: UnknownOpcodeMode) -> Interpreter {
+        let mut interp = chip8(&[0x80, 0x18, 0x60, 0x01]);
+        interp.settings.unknown_opcode_mode = mode;
+        interp
+    }
+
+    #[test]
+    fn unknown_opcode_is_error_when_strict() {
+        let mut interp = unknown_opcode(UnknownOpcodeMode::Strict);
+        assert!(!interp.step());
+        assert_eq!(interp.pc, 0x200);
+
+        assert!(
+            matches!(
+                interp.stop_result(),
+                Err(InterpreterError::Decode { address: 0x200, error: InstructionDecodeError::UnknownInstruction { .. } })
+            ),
+            "{:?}",
+            interp.stop_result()
+        );
+    }
+
+    #[test]
+    fn unknown_opcode_is_skipped_as_nop() {
+        let mut interp = unknown_opcode(UnknownOpcodeMode::Nop);
+        run(&mut interp, 1);
+        assert_eq!(interp.pc, 0x202);
+
+        run(&mut interp, 1);
+        assert_eq!(interp.registers[0], 1);
+    }
+
+    #[test]
+    fn unknown_opcode_halts_without_error() {
+        let mut interp = unknown_opcode(UnknownOpcodeMode::Halt);
+        assert!(!interp.step());
+        assert_eq!(interp.pc, 0x200);
+        assert!(matches!(interp.stop_result(), Ok(false)));
+    }
+
+    #[test]
+    fn draw_traces_erased_pixels_only_with_setting() {
+        // ld v0 0, ld f v0, then draw the 0 glyph at (0, 0) twice
+        let program = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05];
+
+        testlog::capture();
+        let mut interp = chip8(&program);
+        run(&mut interp, 4);
+        assert!(testlog::traces().is_empty());
+
+        let mut interp = chip8(&program);
+        interp.settings.trace_erased_pixels = true;
+        run(&mut interp, 4);
+        let traces = testlog::traces();
+        assert_eq!(traces.len(), 2, "{:?}", traces);
+        assert!(traces[0].starts_with("Draw at (0, 0) erased 0 pixels"), "{}", traces[0]);
+        assert!(traces[1].starts_with("Draw at (0, 0) erased 14 pixels"), "{}", traces[1]);
+    }
+
+    #[test]
+    fn add_constant_overflow_warns_only_with_diagnostic() {
+        // ld v0 0xFF, add v0 2, add v0 3
+        let program = [0x60, 0xFF, 0x70, 0x02, 0x70, 0x03];
+
+        testlog::capture();
+        let mut interp = chip8(&program);
+        run(&mut interp, 3);
+        assert_eq!(interp.registers[0], 4);
+        assert!(testlog::warnings().is_empty());
+
+        let mut interp = chip8(&program);
+        interp.settings.warn_add_overflow = true;
+        run(&mut interp, 3);
+        assert_eq!(interp.registers[0], 4);
+        assert_eq!(interp.registers[VFLAG], 0);
+        assert_eq!(testlog::warnings(), vec!["add v0 2 at 0x202 overflowed: 255 + 2 wrapped to 1"]);
+    }
+
+    #[test]
+    fn reading_an_unwritten_register_warns_only_with_diagnostic() {
+        // ld v0 5, add v1 v0, add v1 v0
+        let program = [0x60, 0x05, 0x81, 0x04, 0x81, 0x04];
+
+        testlog::capture();
+        let mut interp = chip8(&program);
+        run(&mut interp, 3);
+        assert!(testlog::warnings().is_empty());
+
+        // the second add reads v1 too, but by then the first add has written it
+        let mut interp = chip8(&program);
+        interp.settings.warn_uninit_reads = true;
+        run(&mut interp, 3);
+        assert_eq!(interp.registers[1], 10);
+        assert_eq!(
+            testlog::warnings(),
+            vec!["Instruction at 0x202 read v1 before anything was written to it"]
+        );
+    }
+
+    #[test]
+    fn hex_char_index_lands_on_the_font_char_address() {
+        // ld v0 0xA, ld f v0
+        let mut interp = chip8(&[0x60, 0x0A, 0xF0, 0x29]);
+        run(&mut interp, 2);
+        assert_eq!(Interpreter::font_char_address(0xA), Some(0x082));
+        assert_eq!(interp.index, 0x082);
+
+        // the A glyph
+        let offset = interp.index as usize - 0x050;
+        assert_eq!(interp.font_bytes()[offset..offset + 5], [0xF0, 0x90, 0xF0, 0x90, 0x90]);
+        assert_eq!(Interpreter::font_char_address(0x10), None);
+    }
+
+    fn filled(fill: MemoryFill) -> Interpreter {
+        let mut rom = Rom::with_program(RomKind::CHIP8, &[0x60, 0x01]);
+        rom.fill = fill;
+        Interpreter::new(rom)
+    }
+
+    #[test]
+    fn fill_applies_everywhere_but_the_program_and_font() {
+        let interp = filled(MemoryFill::Ones);
+        let font_range = FONT_STARTING_ADDRESS as usize..FONT_STARTING_ADDRESS as usize + FONT.len();
+        let program_range = PROGRAM_STARTING_ADDRESS as usize..PROGRAM_STARTING_ADDRESS as usize + 2;
+
+        assert_eq!(interp.memory[font_range.clone()], FONT);
+        assert_eq!(interp.memory[program_range.clone()], [0x60, 0x01]);
+        assert!(interp
+            .memory
+            .iter()
+            .enumerate()
+            .filter(|(address, _)| !font_range.contains(address) && !program_range.contains(address))
+            .all(|(_, &byte)| byte == 0xFF));
+        assert_eq!(interp.registers, [0xFF; 16]);
+
+        assert!(filled(MemoryFill::Zero).memory[..font_range.start].iter().all(|&byte| byte == 0));
+        assert_eq!(filled(MemoryFill::Zero).registers, [0; 16]);
+    }
+
+    #[test]
+    fn random_fill_is_repeatable_with_the_same_seed() {
+        let first = filled(MemoryFill::Random(8));
+        let second = filled(MemoryFill::Random(8));
+        assert_eq!(first.memory, second.memory);
+        assert_eq!(first.registers, second.registers);
+        assert_ne!(first.memory, filled(MemoryFill::Random(9)).memory);
+        assert_ne!(first.memory, filled(MemoryFill::Zero).memory);
+    }
+
+    // ld v0 62, ld v1 31, ld i 0x208, drw v0 v1 2 with a sprite lighting (0, 0), (1, 0), and (0, 1)
+    fn draw_at_bottom_right_corner(clip: bool) -> Interpreter {
+        let mut quirks = RomKind::CHIP8.default_rom_quirks();
+        quirks.sprites_clip_at_screen_edges = clip;
+        let mut interp = interpreter(
+            RomKind::CHIP8,
+            quirks,
+            &[0x60, 0x3E, 0x61, 0x1F, 0xA2, 0x08, 0xD0, 0x12, 0xC0, 0x80],
+        );
+        assert!(interp.draw_footprint().is_empty());
+        run(&mut interp, 3);
+        interp
+    }
+
+    #[test]
+    fn draw_footprint_wraps_past_the_screen_edges() {
+        let interp = draw_at_bottom_right_corner(false);
+        assert_eq!(interp.draw_footprint(), vec![(0, 62, 31), (0, 63, 31), (0, 62, 0)]);
+        assert_eq!(interp.display, Display::default());
+    }
+
+    #[test]
+    fn draw_footprint_clips_at_the_screen_edges_with_quirk() {
+        let interp = draw_at_bottom_right_corner(true);
+        assert_eq!(interp.draw_footprint(), vec![(0, 62, 31), (0, 63, 31)]);
+        assert_eq!(interp.display, Display::default());
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn settings_outlast_reset_and_restored_copies() {
+        let settings = InterpreterSettings {
+            unknown_opcode_mode: UnknownOpcodeMode::Halt,
+            machine_call_mode: MachineCallMode::Log,
+            warn_add_overflow: true,
+            warn_uninit_reads: true,
+            warn_stack_depth: Some(12),
+            trace_erased_pixels: true,
+        };
+
+        let mut interp = chip8(&[0x60, 0x01]);
+        let copy = interp.clone();
+        interp.settings = settings;
+
+        run(&mut interp, 1);
+        interp.reset(false);
+        assert_eq!(interp.registers[0], 0);
+        assert_eq!(interp.settings, settings);
+
+        run(&mut interp, 1);
+        interp.restore_copy(&copy);
+        assert_eq!(interp.pc, 0x200);
+        assert_eq!(interp.settings, settings);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn palette_outlasts_reset() {
+        let colors = crate::run::preset::OCTO_COLOR_PRESET.1;
+        let mut interp = chip8(&[0x60, 0x01]);
+        interp.display.colors = colors;
+
+        interp.reset(false);
+        assert_eq!(interp.display.colors, colors);
+    }
+
+    fn shift(kind: RomKind, x: u8, y: u8, right: bool, vx: u8, vy: u8) -> [u8; 16] {
+        // ld vx, ld vy, then shr/shl vx vy
+        let op = if right { 0x06 } else { 0x0E };
+        let program = [0x60 | x, vx, 0x60 | y, vy, 0x80 | x, y << 4 | op];
+        let mut interp = interpreter(kind, kind.default_rom_quirks(), &program);
+        run(&mut interp, 3);
+        interp.registers
+    }
+
+    #[test]
+    fn shift_sources_vy_by_default_and_vx_with_quirk() {
+        // (kind, right, v1 after, vf after); v1 starts at 0x81 and v2 at 0x43
+        let cases = [
+            (RomKind::CLASSIC, true, 0x21, 1),
+            (RomKind::CLASSIC, false, 0x86, 0),
+            (RomKind::CHIP8, true, 0x40, 1),
+            (RomKind::CHIP8, false, 0x02, 1),
+        ];
+
+        for (kind, right, result, flag) in cases {
+            let registers = shift(kind, 1, 2, right, 0x81, 0x43);
+            assert_eq!(registers[1], result, "{kind} shift right: {right}");
+            assert_eq!(registers[0xF], flag, "{kind} shift right: {right}");
+            assert_eq!(registers[2], 0x43, "{kind} shift right: {right}");
+        }
+    }
+
+    #[test]
+    fn shift_of_a_register_by_itself_agrees_across_quirks() {
+        for kind in [RomKind::CLASSIC, RomKind::CHIP8] {
+            let registers = shift(kind, 1, 1, true, 0x81, 0x81);
+            assert_eq!((registers[1], registers[0xF]), (0x40, 1), "{kind} shift right");
+
+            let registers = shift(kind, 1, 1, false, 0x81, 0x81);
+            assert_eq!((registers[1], registers[0xF]), (0x02, 1), "{kind} shift left");
+        }
+    }
+
+    #[test]
+    fn shift_into_the_flag_register_keeps_only_the_flag() {
+        // (kind, right, vf after); vf starts at 0x81 and v2 at 0x42
+        let cases = [
+            (RomKind::CLASSIC, true, 0),
+            (RomKind::CLASSIC, false, 0),
+            (RomKind::CHIP8, true, 1),
+            (RomKind::CHIP8, false, 1),
+        ];
+
+        for (kind, right, flag) in cases {
+            let registers = shift(kind, 0xF, 2, right, 0x81, 0x42);
+            assert_eq!(registers[0xF], flag, "{kind} shift right: {right}");
+        }
+    }
+
+    #[test]
+    fn shift_out_of_the_flag_register_reads_it_before_the_flag_is_set() {
+        // shr v1 vf and shl v1 vf with vf at 0x81
+        let registers = shift(RomKind::CLASSIC, 1, 0xF, true, 0x00, 0x81);
+        assert_eq!((registers[1], registers[0xF]), (0x40, 1));
+
+        let registers = shift(RomKind::CLASSIC, 1, 0xF, false, 0x00, 0x81);
+        assert_eq!((registers[1], registers[0xF]), (0x02, 1));
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn undoing_a_store_restores_every_overwritten_byte() {
+        // ld [i] v9, ld i 0x400; i points into bytes written before the store
+        let program = [0xF9, 0x55, 0xA4, 0x00];
+        let prior_memory: Vec<u8> = (0x80..0x90).collect();
+
+        // chip8 leaves i where it was while classic moves it past the stored bytes
+        for (kind, index_after_store) in [(RomKind::CHIP8, 0x304), (RomKind::CLASSIC, 0x30E)] {
+            let mut interp = interpreter(kind, kind.default_rom_quirks(), &program);
+            let mut memory_access_flags = vec![0; interp.memory.len()];
+            interp.write_memory(0x300, &prior_memory);
+            interp.registers = [0x11; 16];
+            interp.index = 0x304;
+
+            let before_store = interp.to_history_fragment(&memory_access_flags);
+            run(&mut interp, 1);
+            assert_eq!(interp.memory[0x304..0x30E], [0x11; 10], "{kind}");
+            assert_eq!(interp.index, index_after_store, "{kind}");
+
+            let before_load_index = interp.to_history_fragment(&memory_access_flags);
+            run(&mut interp, 1);
+            assert_eq!(interp.index, 0x400, "{kind}");
+
+            interp.undo(&before_load_index, &mut memory_access_flags);
+            assert_eq!(interp.index, index_after_store, "{kind}");
+            interp.undo(&before_store, &mut memory_access_flags);
+            assert_eq!(interp.index, 0x304, "{kind}");
+            assert_eq!(interp.pc, 0x200, "{kind}");
+            assert_eq!(interp.memory[0x300..0x310], prior_memory, "{kind}");
+        }
+    }
+}
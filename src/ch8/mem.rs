@@ -4,13 +4,20 @@ use super::{
     rom::{Rom, RomKind},
 };
 
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
 use std::{ops::Range, slice::Windows};
 
+#[cfg(feature = "debugger")]
 pub const MEM_ACCESS_DRAW_FLAG: u8 = 0b1;
+#[cfg(feature = "debugger")]
 pub const MEM_ACCESS_READ_FLAG: u8 = 0b10;
+#[cfg(feature = "debugger")]
 pub const MEM_ACCESS_WRITE_FLAG: u8 = 0b100;
+#[cfg(feature = "debugger")]
 pub const MEM_ACCESS_EXEC_FLAG: u8 = 0b1000;
 
+#[cfg(feature = "debugger")]
 pub fn extract_access_flags(flag: u8) -> (bool, bool, bool, bool) {
     (
         flag & MEM_ACCESS_DRAW_FLAG == MEM_ACCESS_DRAW_FLAG,
@@ -59,6 +66,42 @@ pub const BIG_FONT: [u8; 100] = [
 pub const DEFAULT_PROGRAM_MEMORY_SIZE: usize = 4096;
 pub const XOCHIP_PROGRAM_MEMORY_SIZE: usize = 65536;
 
+// Real hardware did not clear memory or registers at power on, so roms that read them before writing them can
+// behave differently there. A fill reproduces that garbage, and a random fill is seeded so a run can be repeated
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum MemoryFill {
+    #[default]
+    Zero,
+    Ones,
+    Random(u64),
+}
+
+impl MemoryFill {
+    pub fn apply(self, bytes: &mut [u8]) {
+        match self {
+            MemoryFill::Zero => bytes.fill(0),
+            MemoryFill::Ones => bytes.fill(0xFF),
+            MemoryFill::Random(seed) => StdRng::seed_from_u64(seed).fill_bytes(bytes),
+        }
+    }
+
+    // registers are filled from another seed so they do not just repeat the first bytes of memory
+    pub fn registers(self) -> [u8; 16] {
+        let mut registers = [0; 16];
+        match self {
+            MemoryFill::Random(seed) => MemoryFill::Random(!seed).apply(&mut registers),
+            fill => fill.apply(&mut registers),
+        }
+        registers
+    }
+}
+
+// custom memory sizes must hold the program area and stay addressable by 16 bits
+pub fn is_valid_memory_size(memory_size: usize) -> bool {
+    memory_size.is_power_of_two()
+        && (DEFAULT_PROGRAM_MEMORY_SIZE..=XOCHIP_PROGRAM_MEMORY_SIZE).contains(&memory_size)
+}
+
 pub trait MemoryRef {
     fn address_add(&self, lhs: u16, rhs: u16) -> u16
     where
@@ -207,16 +250,12 @@ impl<'a> Iterator for MemoryInstructionBytesIterator<'a> {
     }
 }
 
-pub fn allocate_memory(rom: &Rom) -> Vec<u8> {
-    let mut memory = vec![
-        0;
-        if rom.config.kind == RomKind::XOCHIP {
-            XOCHIP_PROGRAM_MEMORY_SIZE
-        } else {
-            DEFAULT_PROGRAM_MEMORY_SIZE
-        }
-    ];
+// memory_size must be a power of two since the last address doubles as the mask for wrapping addresses
+pub fn allocate_memory(rom: &Rom, memory_size: usize) -> Vec<u8> {
+    debug_assert!(is_valid_memory_size(memory_size));
+    let mut memory = vec![0; memory_size];
 
+    rom.fill.apply(&mut memory);
     memory.import(&rom.data, PROGRAM_STARTING_ADDRESS);
     memory.import(&FONT, FONT_STARTING_ADDRESS);
     if rom.config.kind >= RomKind::SCHIP {
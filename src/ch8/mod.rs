@@ -6,5 +6,8 @@ pub mod interp;
 pub mod mem;
 pub mod rom;
 pub mod run;
+pub mod script;
 pub mod stats;
+pub mod timing;
+pub mod trace;
 pub mod vm;
@@ -1,8 +1,11 @@
 use super::{
     interp::PROGRAM_STARTING_ADDRESS,
-    mem::{DEFAULT_PROGRAM_MEMORY_SIZE, XOCHIP_PROGRAM_MEMORY_SIZE},
+    mem::{MemoryFill, DEFAULT_PROGRAM_MEMORY_SIZE, XOCHIP_PROGRAM_MEMORY_SIZE},
 };
 
+#[cfg(feature = "debugger")]
+use super::instruct::Instruction;
+
 use crate::asm::Disassembler;
 
 use std::{ffi::OsStr, fmt::Display, fs::read, io, path::Path};
@@ -13,7 +16,7 @@ pub struct RomConfig {
     pub quirks: RomQuirks,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum RomKind {
     CLASSIC,
     CHIP8,
@@ -21,6 +24,13 @@ pub enum RomKind {
     XOCHIP,
 }
 
+// a quirk set explicitly (e.g. with --quirk), which takes precedence over the rom kind default
+#[derive(Clone, Copy, Debug)]
+pub struct QuirkOverride {
+    pub name: &'static str,
+    pub value: bool,
+}
+
 #[derive(Clone, Copy)]
 pub struct RomQuirks {
     pub bit_shift_modifies_vx_in_place: bool,
@@ -29,14 +39,103 @@ pub struct RomQuirks {
     pub and_or_xor_clears_flag_register: bool,
     pub sprites_clip_at_screen_edges: bool,
     pub wait_for_vertical_sync: bool,
+    pub add_to_index_overflow_sets_flag_register: bool,
+    pub draw_ors_collision_into_flag_register: bool,
+}
+
+impl RomQuirks {
+    pub fn named(&self) -> [(&'static str, bool); 8] {
+        [
+            ("bit_shift_modifies_vx_in_place", self.bit_shift_modifies_vx_in_place),
+            ("load_store_leaves_index_unchanged", self.load_store_leaves_index_unchanged),
+            ("jump_with_offset_uses_vx", self.jump_with_offset_uses_vx),
+            ("and_or_xor_clears_flag_register", self.and_or_xor_clears_flag_register),
+            ("sprites_clip_at_screen_edges", self.sprites_clip_at_screen_edges),
+            ("wait_for_vertical_sync", self.wait_for_vertical_sync),
+            ("add_to_index_overflow_sets_flag_register", self.add_to_index_overflow_sets_flag_register),
+            ("draw_ors_collision_into_flag_register", self.draw_ors_collision_into_flag_register),
+        ]
+    }
+
+    fn named_mut(&mut self) -> [(&'static str, &mut bool); 8] {
+        [
+            ("bit_shift_modifies_vx_in_place", &mut self.bit_shift_modifies_vx_in_place),
+            ("load_store_leaves_index_unchanged", &mut self.load_store_leaves_index_unchanged),
+            ("jump_with_offset_uses_vx", &mut self.jump_with_offset_uses_vx),
+            ("and_or_xor_clears_flag_register", &mut self.and_or_xor_clears_flag_register),
+            ("sprites_clip_at_screen_edges", &mut self.sprites_clip_at_screen_edges),
+            ("wait_for_vertical_sync", &mut self.wait_for_vertical_sync),
+            ("add_to_index_overflow_sets_flag_register", &mut self.add_to_index_overflow_sets_flag_register),
+            ("draw_ors_collision_into_flag_register", &mut self.draw_ors_collision_into_flag_register),
+        ]
+    }
+
+    // names are matched case insensitively with - and _ interchangeable
+    pub fn find_name(name: &str) -> Option<&'static str> {
+        let name = name.replace('-', "_");
+        RomKind::CHIP8
+            .default_rom_quirks()
+            .named()
+            .into_iter()
+            .map(|(quirk, _)| quirk)
+            .find(|quirk| quirk.eq_ignore_ascii_case(&name))
+    }
+
+    pub fn with_overrides(mut self, overrides: &[QuirkOverride]) -> Self {
+        for quirk_override in overrides {
+            for (name, value) in self.named_mut() {
+                if name == quirk_override.name {
+                    *value = quirk_override.value;
+                }
+            }
+        }
+        self
+    }
+
+    // the quirks that change what an instruction does, for explaining it
+    #[cfg(feature = "debugger")]
+    pub fn affecting(&self, instruction: &Instruction) -> Vec<(&'static str, bool)> {
+        let names: &[&str] = match instruction {
+            Instruction::Shift(..) => &["bit_shift_modifies_vx_in_place"],
+            Instruction::Load(_) | Instruction::Store(_) => &["load_store_leaves_index_unchanged"],
+            Instruction::JumpWithOffset(..) => &["jump_with_offset_uses_vx"],
+            Instruction::Or(..) | Instruction::And(..) | Instruction::Xor(..) => &["and_or_xor_clears_flag_register"],
+            Instruction::Draw(..) => &[
+                "sprites_clip_at_screen_edges",
+                "wait_for_vertical_sync",
+                "draw_ors_collision_into_flag_register",
+            ],
+            Instruction::AddToIndex(_) => &["add_to_index_overflow_sets_flag_register"],
+            _ => &[],
+        };
+
+        self.named()
+            .into_iter()
+            .filter(|(name, _)| names.contains(name))
+            .collect()
+    }
+
+    // explicit quirks are allowed to disagree with the kind but that is easy to do by accident so it is reported
+    pub fn warn_overrides(&self, kind: RomKind) {
+        let defaults = kind.default_rom_quirks().named();
+        for ((name, value), (_, default)) in self.named().into_iter().zip(defaults) {
+            if value != default {
+                log::warn!("Quirk {} overridden to {} (the {} default is {})", name, value, kind, default);
+            }
+        }
+    }
 }
 
 impl RomKind {
     pub fn max_size(self) -> usize {
+        self.default_memory_size() - PROGRAM_STARTING_ADDRESS as usize
+    }
+
+    pub fn default_memory_size(self) -> usize {
         if self == RomKind::XOCHIP {
-            XOCHIP_PROGRAM_MEMORY_SIZE - PROGRAM_STARTING_ADDRESS as usize
+            XOCHIP_PROGRAM_MEMORY_SIZE
         } else {
-            DEFAULT_PROGRAM_MEMORY_SIZE - PROGRAM_STARTING_ADDRESS as usize
+            DEFAULT_PROGRAM_MEMORY_SIZE
         }
     }
 
@@ -58,6 +157,8 @@ impl RomKind {
                 and_or_xor_clears_flag_register: true,
                 sprites_clip_at_screen_edges: true,
                 wait_for_vertical_sync: true,
+                add_to_index_overflow_sets_flag_register: false,
+                draw_ors_collision_into_flag_register: false,
             },
             Self::CHIP8 => RomQuirks {
                 bit_shift_modifies_vx_in_place: true,
@@ -66,6 +167,8 @@ impl RomKind {
                 and_or_xor_clears_flag_register: false,
                 sprites_clip_at_screen_edges: true,
                 wait_for_vertical_sync: false,
+                add_to_index_overflow_sets_flag_register: false,
+                draw_ors_collision_into_flag_register: false,
             },
             Self::SCHIP => RomQuirks {
                 bit_shift_modifies_vx_in_place: true,
@@ -74,6 +177,8 @@ impl RomKind {
                 and_or_xor_clears_flag_register: false,
                 sprites_clip_at_screen_edges: true,
                 wait_for_vertical_sync: false,
+                add_to_index_overflow_sets_flag_register: false,
+                draw_ors_collision_into_flag_register: false,
             },
             Self::XOCHIP => RomQuirks {
                 bit_shift_modifies_vx_in_place: false,
@@ -82,6 +187,8 @@ impl RomKind {
                 and_or_xor_clears_flag_register: false,
                 sprites_clip_at_screen_edges: false,
                 wait_for_vertical_sync: false,
+                add_to_index_overflow_sets_flag_register: false,
+                draw_ors_collision_into_flag_register: false,
             },
         }
     }
@@ -103,11 +210,81 @@ pub struct Rom {
     pub config: RomConfig,
     pub data: Vec<u8>,
     pub name: String,
+    pub fill: MemoryFill, // what memory and registers hold before the program and font are loaded
 }
 
+// a program this small can barely do anything, so it is more likely a truncated file or the wrong one
+const SUSPICIOUSLY_SMALL_ROM_SIZE: usize = 8;
+
 impl Rom {
-    pub fn read<P: AsRef<Path>>(path: P, kind: Option<RomKind>, quirks: Option<RomQuirks>) -> io::Result<Rom> {
-        let data = read(path.as_ref())?;
+    // a program with the kind defaults, for tests that build one in place rather than reading a file
+    #[cfg(test)]
+    pub fn with_program(kind: RomKind, program: &[u8]) -> Rom {
+        Rom {
+            config: RomConfig {
+                kind,
+                quirks: kind.default_rom_quirks(),
+            },
+            data: program.to_vec(),
+            name: String::from("Test"),
+            fill: MemoryFill::Zero,
+        }
+    }
+
+    // confirms what was loaded and where since nothing else does before the program starts running
+    pub fn log_load(&self) {
+        log::info!(
+            "Loaded {} ({}B, {}) into {:#05X}..{:#05X}",
+            self.name,
+            self.data.len(),
+            self.config.kind,
+            PROGRAM_STARTING_ADDRESS,
+            PROGRAM_STARTING_ADDRESS as usize + self.data.len()
+        );
+
+        if !self.data.is_empty() && self.data.len() < SUSPICIOUSLY_SMALL_ROM_SIZE {
+            log::warn!("{} is only {}B, check that the right file was loaded", self.name, self.data.len());
+        }
+    }
+
+    // an empty program for entering instructions by hand in the debugger
+    pub fn blank(kind: RomKind) -> Rom {
+        Rom {
+            config: RomConfig {
+                kind,
+                quirks: kind.default_rom_quirks(),
+            },
+            data: Vec::new(),
+            name: String::from("Blank"),
+            fill: MemoryFill::Zero,
+        }
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P, kind: Option<RomKind>, quirk_overrides: &[QuirkOverride]) -> io::Result<Rom> {
+        Self::read_skipping(path, 0, kind, quirk_overrides)
+    }
+
+    // some dumps start with a header or metadata that is not part of the program, so the first skip bytes of the
+    // file are discarded and only the rest is loaded
+    pub fn read_skipping<P: AsRef<Path>>(
+        path: P,
+        skip: usize,
+        kind: Option<RomKind>,
+        quirk_overrides: &[QuirkOverride],
+    ) -> io::Result<Rom> {
+        let mut data = read(path.as_ref())?;
+        if skip >= data.len() && skip > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Cannot skip {}B of a {}B file, nothing would be left to load", skip, data.len()),
+            ));
+        }
+        data.drain(..skip);
+
+        if is_intel_hex(&data) {
+            data = parse_intel_hex(&String::from_utf8_lossy(&data))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
         let kind =
             kind.unwrap_or_else(|| match path.as_ref().extension().and_then(OsStr::to_str) {
                 Some("sc8") => RomKind::SCHIP,
@@ -123,6 +300,7 @@ impl Rom {
                             },
                             data: data.clone(),
                             name: String::new(),
+                            fill: MemoryFill::Zero,
                         });
 
                         dasm.run();
@@ -130,7 +308,8 @@ impl Rom {
                         let suggested_rom_kind = dasm.suggested_rom_kind();
                         while suggested_rom_kind != dasm.rom.config.kind {
                             dasm.rom.config.kind = suggested_rom_kind;
-                            dasm.rom.config.quirks = quirks.unwrap_or(suggested_rom_kind.default_rom_quirks());
+                            dasm.rom.config.quirks =
+                                suggested_rom_kind.default_rom_quirks().with_overrides(quirk_overrides);
                             dasm.reset();
                             dasm.run();
                         }
@@ -140,6 +319,11 @@ impl Rom {
                 }
             });
 
+        let quirks = kind.default_rom_quirks().with_overrides(quirk_overrides);
+        if !quirk_overrides.is_empty() {
+            quirks.warn_overrides(kind);
+        }
+
         let rom = Rom {
             name: path
                 .as_ref()
@@ -149,9 +333,10 @@ impl Rom {
                 .into(),
             config: RomConfig {
                 kind,
-                quirks: quirks.unwrap_or(kind.default_rom_quirks())
+                quirks
             },
             data,
+            fill: MemoryFill::Zero,
         };
 
         let max_rom_size = rom.config.kind.max_size();
@@ -175,3 +360,179 @@ impl Rom {
         }
     }
 }
+
+// Intel HEX is plain ASCII starting with a record mark, which a raw binary is very unlikely to be since
+// the first instruction would have to decode from ':' and every byte after it would have to be printable
+fn is_intel_hex(data: &[u8]) -> bool {
+    let mut bytes = data.iter().skip_while(|byte| byte.is_ascii_whitespace());
+    bytes.next() == Some(&b':') && data.is_ascii()
+}
+
+// Records are ":LLAAAATT<data>CC" where LL is the data length, AAAA the address, TT the type, and CC the checksum
+// Only data, end of file, and the extended address types are meaningful for a rom, start address records are ignored
+// Assemblers place the program at the address it is loaded to so addresses from 0x200 are relative to it
+fn parse_intel_hex(text: &str) -> Result<Vec<u8>, String> {
+    const MAX_ADDRESS: usize = u16::MAX as usize + 1;
+
+    let mut chunks: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut base_address = 0;
+    let mut ended = false;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let malformed = |reason: &str| format!("Malformed Intel HEX record on line {}: {}", number + 1, reason);
+
+        if ended {
+            return Err(malformed("record after end of file record"));
+        }
+
+        let Some(digits) = line.strip_prefix(':') else {
+            return Err(malformed("expected record to start with ':'"));
+        };
+
+        if digits.len() % 2 != 0 {
+            return Err(malformed("odd number of hex digits"));
+        }
+
+        let bytes = (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| malformed("invalid hex digit"))?;
+
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(malformed("length does not match the record"));
+        }
+
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(malformed("checksum mismatch"));
+        }
+
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+        let record = &bytes[4..bytes.len() - 1];
+
+        match bytes[3] {
+            0x00 => {
+                let address = base_address + address;
+                if address + record.len() > MAX_ADDRESS {
+                    return Err(malformed("data is outside of addressable memory"));
+                }
+                chunks.push((address, record.to_vec()));
+            }
+            0x01 => ended = true,
+            0x02 | 0x04 if record.len() == 2 => {
+                let segment = u16::from_be_bytes([record[0], record[1]]) as usize;
+                base_address = if bytes[3] == 0x02 { segment << 4 } else { segment << 16 };
+            }
+            0x03 | 0x05 => (),
+            0x02 | 0x04 => return Err(malformed("extended address record must hold 2 bytes")),
+            kind => return Err(malformed(&format!("unknown record type {:02X}", kind))),
+        }
+    }
+
+    let Some(start) = chunks.iter().map(|(address, _)| *address).min() else {
+        return Err(String::from("Intel HEX file has no data records"));
+    };
+
+    let offset = if start >= PROGRAM_STARTING_ADDRESS as usize {
+        PROGRAM_STARTING_ADDRESS as usize
+    } else {
+        0
+    };
+
+    let end = chunks
+        .iter()
+        .map(|(address, record)| address + record.len())
+        .max()
+        .unwrap_or(offset);
+
+    let mut data = vec![0; end - offset];
+    for (address, record) in chunks {
+        data[address - offset..address - offset + record.len()].copy_from_slice(&record);
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch8::interp::{Interpreter, PROGRAM_STARTING_ADDRESS};
+
+    use std::{env, fs, path::PathBuf};
+
+    // cls, ld i 0x22A, ld v0 12, ld v1 8, drw v0 v1 15, jp 0x20A
+    const PROGRAM: [u8; 12] = [0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0x61, 0x08, 0xD0, 0x1F, 0x12, 0x0A];
+    const PROGRAM_HEX: &str = ":0602000000E0A22A600CE0\n:060206006108D01F120A7E\n:00000001FF\n";
+
+    fn write_temp(name: &str, contents: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("c8-test-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).expect("Failed to write test rom");
+        path
+    }
+
+    fn read_rom(name: &str, contents: &[u8]) -> io::Result<Rom> {
+        read_rom_skipping(name, contents, 0)
+    }
+
+    fn read_rom_skipping(name: &str, contents: &[u8], skip: usize) -> io::Result<Rom> {
+        let path = write_temp(name, contents);
+        let rom = Rom::read_skipping(&path, skip, Some(RomKind::CHIP8), &[]);
+        fs::remove_file(&path).ok();
+        rom
+    }
+
+    #[test]
+    fn intel_hex_loads_the_same_as_raw_binary() {
+        let raw = read_rom("raw.ch8", &PROGRAM).expect("Raw rom should load");
+        let hex = read_rom("hex.ch8", PROGRAM_HEX.as_bytes()).expect("Intel HEX rom should load");
+        assert_eq!(hex.data, PROGRAM);
+        assert_eq!(hex.data, raw.data);
+        assert_eq!(Interpreter::new(hex).memory, Interpreter::new(raw).memory);
+    }
+
+    #[test]
+    fn intel_hex_checksum_mismatch_reports_the_line() {
+        let corrupted = PROGRAM_HEX.replace("120A7E", "120A7F");
+        let Err(error) = read_rom("corrupted.ch8", corrupted.as_bytes()) else {
+            panic!("Corrupted rom should not load");
+        };
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Malformed Intel HEX record on line 2: checksum mismatch");
+    }
+
+    #[test]
+    fn intel_hex_record_after_end_of_file_is_malformed() {
+        let text = format!("{}:0602000000E0A22A600CE0\n", PROGRAM_HEX);
+        assert_eq!(
+            parse_intel_hex(&text),
+            Err(String::from("Malformed Intel HEX record on line 4: record after end of file record"))
+        );
+    }
+
+    #[test]
+    fn skipped_header_is_not_loaded_into_program_memory() {
+        let mut contents = b"C8DUMP\x01\x00".to_vec();
+        contents.extend_from_slice(&PROGRAM);
+
+        let rom = read_rom_skipping("header.ch8", &contents, 8).expect("Rom with a skipped header should load");
+        assert_eq!(rom.data, PROGRAM);
+
+        let start = PROGRAM_STARTING_ADDRESS as usize;
+        let interp = Interpreter::new(rom);
+        assert_eq!(interp.memory[start..start + PROGRAM.len()], PROGRAM);
+    }
+
+    #[test]
+    fn skipping_the_whole_file_is_rejected() {
+        let Err(error) = read_rom_skipping("short.ch8", &PROGRAM, PROGRAM.len()) else {
+            panic!("Rom with nothing left after the skip should not load");
+        };
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Cannot skip 12B of a 12B file, nothing would be left to load");
+    }
+}
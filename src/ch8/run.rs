@@ -1,6 +1,7 @@
 use super::{
-    stats::C8Stats,
-    vm::{VMEvent, VM, VM_FRAME_DURATION, VM_FRAME_RATE},
+    stats::{BenchStats, C8Stats},
+    timing::FrameSchedule,
+    vm::{VMError, VMEvent, VM, VM_FRAME_DURATION, VM_FRAME_RATE},
 };
 
 use crate::dbg::Debugger;
@@ -20,7 +21,7 @@ use std::{
 pub type C8 = (VM, Option<Debugger>);
 pub type C8Lock = Arc<Mutex<C8>>;
 
-pub type RunResult = Result<C8Stats, String>;
+pub type RunResult = Result<C8Stats, VMError>;
 pub type RunControlResult = Result<(), &'static str>;
 
 pub struct Runner {
@@ -28,7 +29,9 @@ pub struct Runner {
 
     thread_handle: JoinHandle<RunResult>,
     thread_continue_sender: Sender<bool>,
+    #[cfg(feature = "debugger")]
     thread_frequency_sender: Sender<u32>,
+    thread_speed_multiplier_sender: Sender<u32>,
 
     vm_event_sender: Sender<VMEvent>,
 }
@@ -42,10 +45,6 @@ impl Runner {
         self.vm_event_sender.clone()
     }
 
-    pub fn pause(&mut self) -> RunControlResult {
-        self.send_vm_can_continue(false)
-    }
-
     pub fn resume(&mut self) -> RunControlResult {
         self.send_vm_can_continue(true)
     }
@@ -54,10 +53,11 @@ impl Runner {
         self.thread_handle.is_finished()
     }
 
-    pub fn set_execution_frequency(&mut self, frequency: u32) -> Result<(), &'static str> {
-        self.thread_frequency_sender
-            .send(frequency)
-            .map_err(|_| "Failed to send instruction frequency to vm thread")
+    // runs that many frames worth of instructions each frame while the timers keep ticking at 60hz (turbo)
+    pub fn set_speed_multiplier(&mut self, multiplier: u32) -> RunControlResult {
+        self.thread_speed_multiplier_sender
+            .send(multiplier.max(1))
+            .map_err(|_| "Failed to send speed multiplier to vm thread")
     }
 
     pub fn new(
@@ -69,6 +69,7 @@ impl Runner {
         let (vm_event_sender, vm_event_receiver) = channel::<VMEvent>();
         let (thread_continue_sender, thread_continue_receiver) = channel::<bool>();
         let (thread_frequency_sender, thread_frequency_receiver) = channel::<u32>();
+        let (thread_speed_multiplier_sender, thread_speed_multiplier_receiver) = channel::<u32>();
 
         let mut cycles_per_frame = vm.cycles_per_frame();
         let mut stats = C8Stats::new(vm.interpreter().rom.name.clone());
@@ -99,7 +100,11 @@ impl Runner {
                 let mut burst_elapsed = Duration::ZERO;
                 let mut burst_just_started = true;
 
-                let mut frame_start = Instant::now();
+                let mut frame_schedule = FrameSchedule::new(VM_FRAME_DURATION, Instant::now());
+                let mut speed_multiplier = 1;
+
+                // the lock is released while sleeping so the sleep is recorded once the next frame holds it
+                let mut frame_sleep: Option<(Duration, Duration)> = None;
 
                 // 1 frame of work
                 loop {
@@ -107,6 +112,10 @@ impl Runner {
                     let mut _guard = c8.lock().expect("Failed to lock C8 for main loop");
                     let (vm, maybe_dbg) = _guard.deref_mut();
 
+                    if let Some((sleep, late)) = frame_sleep.take() {
+                        vm.frame_timing_mut().record(sleep, late);
+                    }
+
                     let mut step_can_continue = false;
 
                     if continuation.try_cont() {
@@ -116,20 +125,29 @@ impl Runner {
                             burst_just_started = false;
                             vm_event_receiver.try_iter().last();
                             vm.resume_audio();
-                            frame_start = Instant::now();
+
+                            // timers tick from executed cycles rather than wall time, so a long pause (the debugger,
+                            // system sleep) never drains them. the schedule restarts instead of catching up so the
+                            // frames after the pause don't run back to back either
+                            frame_schedule.restart(Instant::now());
                         } else {
                             vm.queue_events(vm_event_receiver.try_iter());
                         }
 
+                        if let Some(multiplier) = thread_speed_multiplier_receiver.try_iter().last() {
+                            speed_multiplier = multiplier;
+                        }
+                        let frame_cycles = cycles_per_frame * speed_multiplier;
+
                         vm.update_audio();
-                        vm.set_cycles_per_frame(cycles_per_frame);
+                        vm.set_cycles_per_frame(frame_cycles);
 
                         let now = Instant::now();
                         if let Some(dbg) = maybe_dbg {
-                            step_can_continue = dbg.step(vm, cycles_per_frame as usize);
+                            step_can_continue = dbg.step(vm, frame_cycles as usize);
                         } else {
                             step_can_continue =
-                                vm.flush_external_input_and_stepn(cycles_per_frame)?
+                                vm.flush_external_input_and_stepn(frame_cycles)?
                         }
 
                         let elapsed = now.elapsed();
@@ -137,7 +155,7 @@ impl Runner {
                         if step_can_continue {
                             log::trace!(
                                 "Completed {} cycles in {} us",
-                                cycles_per_frame,
+                                frame_cycles,
                                 elapsed.as_micros()
                             );
                         }
@@ -150,27 +168,22 @@ impl Runner {
                         if continuation.cont {
                             drop(_guard);
 
-                            frame_start = frame_start
-                                .checked_add(VM_FRAME_DURATION)
-                                .expect("Could not calculate next frame start");
                             let sleep_start = Instant::now();
-                            let sleep_duration = frame_start.saturating_duration_since(sleep_start);
+                            let sleep_duration = frame_schedule.sleep(sleep_start);
                             spin_sleep::sleep(sleep_duration);
+                            let late = frame_schedule.late(sleep_start, sleep_duration, Instant::now());
 
                             if sleep_duration.is_zero() {
                                 log::warn!(
                                     "Overran frame budget by {} us! Skipping sleep and starting next frame immediately", 
-                                    sleep_start.duration_since(frame_start).as_micros()
+                                    late.as_micros()
                                 );
-                                frame_start = sleep_start;
                             } else {
-                                log::trace!(
-                                    "Overslept remaining frame budget by {} us",
-                                    Instant::now().duration_since(frame_start).as_micros()
-                                );
+                                log::trace!("Overslept remaining frame budget by {} us", late.as_micros());
                             }
+                            frame_sleep = Some((sleep_duration, late));
 
-                            freq_instructions_executed += cycles_per_frame as u64;
+                            freq_instructions_executed += frame_cycles as u64;
                             total_simulated_time += 1.0 / VM_FRAME_RATE as f64;
                             burst_elapsed = burst_start.elapsed();
                             continue;
@@ -178,6 +191,7 @@ impl Runner {
                     }
 
                     vm.pause_audio();
+                    stats.draw_stats = vm.interpreter().draw_stats;
 
                     drop(_guard);
 
@@ -220,12 +234,18 @@ impl Runner {
             })
         };
 
+        // only the debugger changes the frequency, without it the run thread just sees the channel closed
+        #[cfg(not(feature = "debugger"))]
+        drop(thread_frequency_sender);
+
         Runner {
             c8,
             thread_handle,
             vm_event_sender,
             thread_continue_sender,
+            #[cfg(feature = "debugger")]
             thread_frequency_sender,
+            thread_speed_multiplier_sender,
         }
     }
 
@@ -250,6 +270,41 @@ impl Runner {
     }
 }
 
+#[cfg(feature = "debugger")]
+impl Runner {
+    pub fn pause(&mut self) -> RunControlResult {
+        self.send_vm_can_continue(false)
+    }
+
+    pub fn set_execution_frequency(&mut self, frequency: u32) -> Result<(), &'static str> {
+        self.thread_frequency_sender
+            .send(frequency)
+            .map_err(|_| "Failed to send instruction frequency to vm thread")
+    }
+}
+
+// runs the vm back to back without sleeping, input, or rendering to measure raw interpreter throughput
+// only instructions the interpreter ran are counted, a wait for a key or the vertical blank skips the rest of its frame
+pub fn run_bench(mut vm: VM, duration: Duration) -> Result<BenchStats, VMError> {
+    let cycles_per_frame = vm.cycles_per_frame();
+    let executed_instructions = vm.executed_instructions();
+
+    vm.pause_audio();
+
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        if !vm.stepn(cycles_per_frame)? {
+            break;
+        }
+    }
+
+    Ok(BenchStats {
+        rom_name: vm.interpreter().rom.name.clone(),
+        duration: start.elapsed(),
+        instructions: vm.executed_instructions() - executed_instructions,
+    })
+}
+
 struct RunContinuation {
     cont: bool,
     recv: Receiver<bool>,
@@ -0,0 +1,225 @@
+use super::rom::RomKind;
+
+use std::fmt::{self, Display};
+
+// Input scripts are plain text that replay a recorded run from the start of the program:
+//
+//   # c8 input script
+//   kind CHIP8
+//   rom pong
+//   seed 1234
+//   cpf 10
+//   keys 0000 - - 1200
+//   wait 35
+//   keys 0010 - 4 1
+//
+// The header holds the rom kind and name and the seed of the rng. After it, each line applies in order:
+//   - cpf sets the cycles per frame the following cycles run at
+//   - keys holds the keypad in the same state for a number of instructions, as a bitmap of the keys down with key 0
+//     as the lowest bit, then the key just pressed and the key just released (- for none)
+//   - wait runs cycles where the next instruction waits (e.g. for a key to be released or the vertical blank)
+//   - idle runs cycles with the cpu frozen so only the timers advance
+// Counting cycles that did not complete an instruction keeps the timers where they were when the run was recorded
+
+pub const INPUT_SCRIPT_HEADER: &str = "# c8 input script";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputScriptStep {
+    CyclesPerFrame(u32),
+    Keys {
+        down_keys: u16,
+        pressed: Option<u8>,
+        released: Option<u8>,
+        instructions: u64,
+    },
+    Wait(u64),
+    Idle(u64),
+}
+
+pub struct InputScript {
+    pub kind: RomKind,
+    pub rom_name: String,
+    pub seed: u64,
+    pub steps: Vec<InputScriptStep>,
+}
+
+impl InputScript {
+    pub fn instructions(&self) -> u64 {
+        self.steps
+            .iter()
+            .map(|step| match step {
+                &InputScriptStep::Keys { instructions, .. } => instructions,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()));
+
+        if lines.next().map(|(_, line)| line) != Some(INPUT_SCRIPT_HEADER) {
+            return Err(format!(
+                "Input script does not start with \"{}\"",
+                INPUT_SCRIPT_HEADER
+            ));
+        }
+
+        let mut kind = None;
+        let mut rom_name = None;
+        let mut seed = None;
+        let mut steps = Vec::new();
+
+        for (line_number, line) in
+            lines.filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        {
+            let malformed = || format!("Malformed input script line {}: {}", line_number, line);
+            let (name, value) = line.split_once(' ').ok_or_else(malformed)?;
+            let number = |value: &str| value.parse::<u64>().map_err(|_| malformed());
+
+            match name {
+                "kind" => {
+                    let parsed = [
+                        RomKind::CLASSIC,
+                        RomKind::CHIP8,
+                        RomKind::SCHIP,
+                        RomKind::XOCHIP,
+                    ]
+                    .into_iter()
+                    .find(|kind| kind.to_string() == value);
+                    kind = Some(parsed.ok_or_else(malformed)?);
+                }
+                "rom" => rom_name = Some(value.to_string()),
+                "seed" => seed = Some(number(value)?),
+                "cpf" => steps.push(InputScriptStep::CyclesPerFrame(
+                    value.parse().map_err(|_| malformed())?,
+                )),
+                "wait" => steps.push(InputScriptStep::Wait(number(value)?)),
+                "idle" => steps.push(InputScriptStep::Idle(number(value)?)),
+                "keys" => {
+                    let key = |value: &str| match value {
+                        "-" => Ok(None),
+                        value => u8::from_str_radix(value, 16)
+                            .ok()
+                            .filter(|&key| key < 0x10)
+                            .map(Some)
+                            .ok_or_else(malformed),
+                    };
+
+                    let fields: Vec<&str> = value.split_whitespace().collect();
+                    let &[down_keys, pressed, released, instructions] = fields.as_slice() else {
+                        return Err(malformed());
+                    };
+                    steps.push(InputScriptStep::Keys {
+                        down_keys: u16::from_str_radix(down_keys, 16).map_err(|_| malformed())?,
+                        pressed: key(pressed)?,
+                        released: key(released)?,
+                        instructions: number(instructions)?,
+                    });
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(Self {
+            kind: kind.ok_or("Input script has no kind")?,
+            rom_name: rom_name.ok_or("Input script has no rom")?,
+            seed: seed.ok_or("Input script has no seed")?,
+            steps,
+        })
+    }
+}
+
+#[cfg(feature = "debugger")]
+impl InputScript {
+    pub fn new(kind: RomKind, rom_name: String, seed: u64) -> Self {
+        Self {
+            kind,
+            rom_name,
+            seed,
+            steps: Vec::new(),
+        }
+    }
+
+    // the cycles per frame only gets a line when it changes
+    pub fn push_cycles_per_frame(&mut self, cycles_per_frame: u32) {
+        let current = self.steps.iter().rev().find_map(|step| match step {
+            &InputScriptStep::CyclesPerFrame(cycles_per_frame) => Some(cycles_per_frame),
+            _ => None,
+        });
+
+        if current != Some(cycles_per_frame) {
+            self.steps
+                .push(InputScriptStep::CyclesPerFrame(cycles_per_frame));
+        }
+    }
+
+    // cycles between instructions either waited on the next instruction or had the cpu frozen
+    pub fn push_cycles(&mut self, cycles: u64, waiting: bool) {
+        if cycles > 0 {
+            self.steps.push(if waiting {
+                InputScriptStep::Wait(cycles)
+            } else {
+                InputScriptStep::Idle(cycles)
+            });
+        }
+    }
+
+    pub fn push_instruction(&mut self, down_keys: u16, pressed: Option<u8>, released: Option<u8>) {
+        if let Some(InputScriptStep::Keys {
+            down_keys: last_down_keys,
+            pressed: last_pressed,
+            released: last_released,
+            instructions,
+        }) = self.steps.last_mut()
+        {
+            if (*last_down_keys, *last_pressed, *last_released) == (down_keys, pressed, released) {
+                *instructions += 1;
+                return;
+            }
+        }
+
+        self.steps.push(InputScriptStep::Keys {
+            down_keys,
+            pressed,
+            released,
+            instructions: 1,
+        });
+    }
+}
+
+impl Display for InputScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = |key: Option<u8>| key.map_or(String::from("-"), |key| format!("{:X}", key));
+
+        writeln!(f, "{}", INPUT_SCRIPT_HEADER)?;
+        writeln!(f, "kind {}", self.kind)?;
+        writeln!(f, "rom {}", self.rom_name)?;
+        writeln!(f, "seed {}", self.seed)?;
+        for step in self.steps.iter() {
+            match *step {
+                InputScriptStep::CyclesPerFrame(cycles_per_frame) => {
+                    writeln!(f, "cpf {}", cycles_per_frame)?
+                }
+                InputScriptStep::Keys {
+                    down_keys,
+                    pressed,
+                    released,
+                    instructions,
+                } => writeln!(
+                    f,
+                    "keys {:04X} {} {} {}",
+                    down_keys,
+                    key(pressed),
+                    key(released),
+                    instructions
+                )?,
+                InputScriptStep::Wait(cycles) => writeln!(f, "wait {}", cycles)?,
+                InputScriptStep::Idle(cycles) => writeln!(f, "idle {}", cycles)?,
+            }
+        }
+        Ok(())
+    }
+}
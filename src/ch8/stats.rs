@@ -1,3 +1,5 @@
+use super::interp::DrawStats;
+
 use std::{collections::BTreeMap, fmt::Display, time::Duration};
 
 use crossterm::style::Stylize;
@@ -10,6 +12,7 @@ pub struct C8Stats {
     pub up_time: Duration,
     pub simulated_time: f64,
     pub rom_name: String,
+    pub draw_stats: DrawStats,
 }
 
 impl C8Stats {
@@ -19,6 +22,7 @@ impl C8Stats {
             up_time: Duration::ZERO,
             simulated_time: 0.0,
             rom_name,
+            draw_stats: DrawStats::default(),
         }
     }
 
@@ -106,6 +110,58 @@ impl Display for C8Stats {
             )?;
         }
 
+        write!(f, "\n    {}", format!("|").blue().bold())?;
+        write!(
+            f,
+            "\n    {}    Sprite Draws: {}",
+            format!("=").blue().bold(),
+            self.draw_stats.draws
+        )?;
+        write!(
+            f,
+            "\n    {}   Screen Clears: {}",
+            format!("=").blue().bold(),
+            self.draw_stats.clears
+        )?;
+
         Ok(())
     }
 }
+
+pub struct BenchStats {
+    pub rom_name: String,
+    pub duration: Duration,
+    pub instructions: u64,
+}
+
+impl Display for BenchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ips = self.instructions as f64 / self.duration.as_secs_f64();
+
+        write!(
+            f,
+            "{} \"{}\" throughput",
+            format!("Benchmarked").green().bold(),
+            self.rom_name
+        )?;
+        write!(f, "\n    {}", format!("|").blue().bold())?;
+        write!(
+            f,
+            "\n    {}    Instructions: {}",
+            format!("=").blue().bold(),
+            self.instructions
+        )?;
+        write!(
+            f,
+            "\n    {}        Duration: {:.3}s",
+            format!("=").blue().bold(),
+            self.duration.as_secs_f64()
+        )?;
+        write!(
+            f,
+            "\n    {}      Throughput: {:.3} MIPS",
+            format!("=").blue().bold(),
+            if ips.is_finite() { ips / 1_000_000.0 } else { 0.0 }
+        )
+    }
+}
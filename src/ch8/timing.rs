@@ -0,0 +1,206 @@
+use super::instruct::Instruction;
+
+use std::{
+    collections::HashMap,
+    mem::Discriminant,
+    time::{Duration, Instant},
+};
+
+// Wall time spent executing each kind of instruction, which shows where the interpreter itself spends its time
+// rather than how often the program uses an instruction. Timing adds an Instant::now per step so it is opt-in
+
+pub struct InstructionTiming {
+    pub count: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+#[cfg(feature = "debugger")]
+impl InstructionTiming {
+    pub fn avg(&self) -> Duration {
+        Duration::from_nanos((self.total.as_nanos() / self.count.max(1) as u128) as u64)
+    }
+}
+
+// each kind keeps the first instruction of that kind seen so it can be named in the report
+#[derive(Default)]
+pub struct InstructionTimings {
+    kinds: HashMap<Discriminant<Instruction>, (Instruction, InstructionTiming)>,
+}
+
+impl InstructionTimings {
+    pub fn record(&mut self, instruction: Instruction, elapsed: Duration) {
+        let (_, timing) = self.kinds.entry(std::mem::discriminant(&instruction)).or_insert_with(|| {
+            let timing = InstructionTiming {
+                count: 0,
+                total: Duration::ZERO,
+                min: Duration::MAX,
+                max: Duration::ZERO,
+            };
+            (instruction, timing)
+        });
+
+        timing.count += 1;
+        timing.total += elapsed;
+        timing.min = timing.min.min(elapsed);
+        timing.max = timing.max.max(elapsed);
+    }
+
+    // most expensive kinds first, named by variant since that is enough to tell kinds apart (e.g. Draw(1, 2, 5) -> Draw)
+    #[cfg(feature = "debugger")]
+    pub fn report(&self) -> Vec<(String, &InstructionTiming)> {
+        let mut timings: Vec<(String, &InstructionTiming)> = self
+            .kinds
+            .values()
+            .map(|(instruction, timing)| {
+                let name = format!("{:?}", instruction);
+                (name.split('(').next().unwrap_or_default().to_string(), timing)
+            })
+            .collect();
+        timings.sort_unstable_by(|(_, a), (_, b)| b.total.cmp(&a.total));
+        timings
+    }
+}
+
+// The run thread's 60hz frame schedule. Each frame is due one frame duration after the last, and a frame that
+// overruns its budget restarts the schedule from where it ended instead of running the frames after it back to back
+// to catch up. Taking the clock as an argument keeps the schedule free of real sleeps
+pub struct FrameSchedule {
+    frame_duration: Duration,
+    frame_start: Instant,
+}
+
+impl FrameSchedule {
+    pub fn new(frame_duration: Duration, now: Instant) -> Self {
+        Self {
+            frame_duration,
+            frame_start: now,
+        }
+    }
+
+    pub fn restart(&mut self, now: Instant) {
+        self.frame_start = now;
+    }
+
+    // how long to sleep for the next frame to start on time when this frame's work finished at now
+    pub fn sleep(&mut self, now: Instant) -> Duration {
+        self.frame_start = self
+            .frame_start
+            .checked_add(self.frame_duration)
+            .expect("Could not calculate next frame start");
+        self.frame_start.saturating_duration_since(now)
+    }
+
+    // how late the next frame starts, which is the overrun when there was nothing to sleep and the oversleep otherwise
+    pub fn late(&mut self, sleep_start: Instant, sleep: Duration, woke: Instant) -> Duration {
+        if sleep.is_zero() {
+            let overrun = sleep_start.saturating_duration_since(self.frame_start);
+            self.frame_start = sleep_start;
+            overrun
+        } else {
+            woke.saturating_duration_since(self.frame_start)
+        }
+    }
+}
+
+// How closely the run thread keeps to the 60hz frame schedule. Every frame sleeps off what is left of its budget
+// and either oversleeps a little or, when the frame ran over its budget, skips the sleep. Both put the wall clock
+// behind the schedule, and drift is all of it added up since the program started
+#[derive(Clone, Copy, Default)]
+pub struct FrameTiming {
+    frames: u64,
+    drift: Duration,
+    last_sleep: Duration,
+    last_oversleep: Duration,
+    max_oversleep: Duration,
+    overruns: u64,
+}
+
+impl FrameTiming {
+    // the sleep is zero when the frame overran its budget, in which case late is by how much
+    pub fn record(&mut self, sleep: Duration, late: Duration) {
+        self.frames += 1;
+        self.drift = self.drift.saturating_add(late);
+        self.last_sleep = sleep;
+
+        if sleep.is_zero() {
+            self.overruns += 1;
+            self.last_oversleep = Duration::ZERO;
+        } else {
+            self.last_oversleep = late;
+            self.max_oversleep = self.max_oversleep.max(late);
+        }
+    }
+}
+
+#[cfg(feature = "debugger")]
+impl FrameTiming {
+    pub fn last_sleep(&self) -> Duration {
+        self.last_sleep
+    }
+
+    pub fn last_oversleep(&self) -> Duration {
+        self.last_oversleep
+    }
+}
+
+#[cfg(any(feature = "debugger", test))]
+impl FrameTiming {
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn drift(&self) -> Duration {
+        self.drift
+    }
+
+    pub fn avg_drift(&self) -> Duration {
+        Duration::from_nanos((self.drift.as_nanos() / self.frames.max(1) as u128) as u64)
+    }
+
+    pub fn max_oversleep(&self) -> Duration {
+        self.max_oversleep
+    }
+
+    pub fn overruns(&self) -> u64 {
+        self.overruns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch8::vm::VM_FRAME_DURATION;
+
+    #[test]
+    fn drift_per_frame_stays_bounded_under_load_that_overruns_the_budget() {
+        // four frames of work over budget then one well under it, which is slower than 60hz on average, and every
+        // sleep oversleeps a little. a frame is never later than its own overrun plus the oversleep before it
+        let load = [20, 20, 20, 20, 5].map(Duration::from_millis);
+        let oversleep = Duration::from_micros(500);
+        let max_late = Duration::from_millis(20) - VM_FRAME_DURATION + oversleep;
+
+        let mut now = Instant::now();
+        let mut schedule = FrameSchedule::new(VM_FRAME_DURATION, now);
+        let mut timing = FrameTiming::default();
+
+        for work in load.iter().cycle().take(1000) {
+            now += *work;
+            let sleep_start = now;
+            let sleep = schedule.sleep(sleep_start);
+            if !sleep.is_zero() {
+                now += sleep + oversleep;
+            }
+
+            let late = schedule.late(sleep_start, sleep, now);
+            assert!(late <= max_late, "frame {} started {:?} late", timing.frames(), late);
+            timing.record(sleep, late);
+        }
+
+        assert_eq!(timing.frames(), 1000);
+        assert_eq!(timing.overruns(), 800);
+        assert_eq!(timing.max_oversleep(), oversleep);
+        assert!(timing.avg_drift() <= max_late, "average drift {:?}", timing.avg_drift());
+    }
+}
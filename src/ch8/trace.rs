@@ -0,0 +1,437 @@
+use super::{
+    instruct::Instruction,
+    interp::{Interpreter, InterpreterInput},
+    mem::MemoryRef,
+};
+
+use crate::asm::write_inst_dasm;
+
+use std::{
+    fmt::Write as _,
+    fs::File,
+    fmt,
+    io::{self, BufRead, BufReader, BufWriter, Lines, Write},
+    path::Path,
+};
+
+// Executed instructions are written as newline-delimited JSON, one object per instruction:
+//
+//   {"cycle":0,"pc":512,"opcode":"00E0","mnemonic":"cls","registers":[0,...],"index":0,"flags":[0,...]}
+//
+// cycle counts executed instructions from 0, opcode is the raw instruction in hex,
+// and registers, index, and flags (RPL user flags) are the interpreter state after the instruction executed
+//
+// A filter limits the trace to instructions matching a mnemonic (e.g. drw) or instruction name (e.g. draw) so
+// frequent instructions do not drown out the ones of interest. Cycles still count every executed instruction
+//
+// A trace starts with the seed of the rng, and the input an instruction saw (keys, delay timer, vertical blank) is
+// written before it whenever it changed since the last input written, even when the filter leaves the instruction out:
+//
+//   {"seed":1234}
+//   {"cycle":0,"keys":0,"pressed":null,"released":null,"delay":0,"vblank":false}
+//
+// A tracer can also verify execution against a previously written trace, which makes a recorded known-good
+// run a regression test. Verifying seeds the rng and hands every instruction the recorded input in place of the
+// keyboard and timers, so the run plays out as it was recorded
+
+enum TraceSink {
+    Write(BufWriter<File>),
+    Verify(Lines<BufReader<File>>),
+    Done,
+}
+
+// names are kept lowercase
+#[derive(Clone, Debug)]
+pub struct TraceFilter(Vec<String>);
+
+impl TraceFilter {
+    // a comma separated list of mnemonics or instruction names (e.g. drw,call)
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let names: Vec<String> = value
+            .split(',')
+            .map(|name| name.trim().to_ascii_lowercase())
+            .collect();
+
+        match names.iter().find(|name| name.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic())) {
+            Some(name) => Err(format!("\"{}\" is not a mnemonic or instruction name", name)),
+            None => Ok(TraceFilter(names)),
+        }
+    }
+
+    fn matches(&self, instruction: &Instruction, mnemonic: &str) -> bool {
+        let debug_name = format!("{:?}", instruction);
+        let variant = debug_name.split('(').next().unwrap_or_default();
+        self.0
+            .iter()
+            .any(|name| mnemonic.eq_ignore_ascii_case(name) || variant.eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for TraceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join(","))
+    }
+}
+
+pub struct InstructionTracer {
+    sink: TraceSink,
+    filter: Option<TraceFilter>,
+    divergence: Option<String>,
+    seed: Option<u64>,
+    input: InterpreterInput,
+    written_input: Option<InterpreterInput>,
+    replayed_input: Option<InterpreterInput>,
+    peeked_line: Option<io::Result<String>>,
+    cycle: u64,
+    pc: u16,
+    opcode: u32,
+    instruction: Option<Instruction>,
+    line: String,
+    asm: String,
+    asm_desc: String,
+}
+
+impl InstructionTracer {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(TraceSink::Write(BufWriter::new(File::create(path)?))))
+    }
+
+    pub fn verify<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let Some(seed) = parse_seed_line(&header) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Trace does not start with a seed: {}", header.trim_end()),
+            ));
+        };
+
+        let mut tracer = Self::new(TraceSink::Verify(lines));
+        tracer.seed = Some(seed);
+        Ok(tracer)
+    }
+
+    fn new(sink: TraceSink) -> Self {
+        InstructionTracer {
+            sink,
+            filter: None,
+            divergence: None,
+            seed: None,
+            input: InterpreterInput::default(),
+            written_input: None,
+            replayed_input: None,
+            peeked_line: None,
+            cycle: 0,
+            pc: 0,
+            opcode: 0,
+            instruction: None,
+            line: String::new(),
+            asm: String::new(),
+            asm_desc: String::new(),
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: Option<TraceFilter>) {
+        self.filter = filter;
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn filter(&self) -> Option<&TraceFilter> {
+        self.filter.as_ref()
+    }
+
+    // called once before the program runs, a written trace starts with the seed and a verified one seeds the rng with it
+    pub fn attach(&mut self, interp: &mut Interpreter) {
+        match (&mut self.sink, self.seed) {
+            (TraceSink::Write(_), _) => {
+                self.line.clear();
+                write!(self.line, "{{\"seed\":{}}}", interp.rng_seed()).ok();
+                self.write_line();
+            }
+            (TraceSink::Verify(_), Some(seed)) => interp.seed_rng(seed),
+            _ => (),
+        }
+    }
+
+    // the first difference from the verified trace, after which nothing else is compared
+    pub fn take_divergence(&mut self) -> Option<String> {
+        self.divergence.take()
+    }
+
+    // capture the instruction about to be executed since executing it can move the pc or overwrite it
+    #[inline(always)]
+    pub fn prepare(&mut self, interp: &mut Interpreter) {
+        self.pc = interp.pc;
+        self.instruction = interp.instruction();
+
+        let mut bytes = [0; Instruction::MAX_INSTRUCTION_SIZE as usize];
+        interp.memory.export(interp.pc, &mut bytes);
+        self.opcode = u32::from_be_bytes(bytes);
+
+        match self.sink {
+            TraceSink::Write(_) => self.input.clone_from(&interp.input),
+            TraceSink::Verify(_) => self.replay_input(interp),
+            TraceSink::Done => (),
+        }
+    }
+
+    // the input recorded for an instruction comes right before it and holds until the next input recorded
+    // a waiting instruction is handed the input that ended its wait so it finishes on the first cycle
+    fn replay_input(&mut self, interp: &mut Interpreter) {
+        loop {
+            match self.next_line() {
+                Some(Ok(line)) => match parse_input_line(&line) {
+                    Some((cycle, input)) if cycle <= self.cycle => self.replayed_input = Some(input),
+                    _ => {
+                        self.peeked_line = Some(Ok(line));
+                        break;
+                    }
+                },
+                line => {
+                    self.peeked_line = line;
+                    break;
+                }
+            }
+        }
+
+        if let Some(input) = self.replayed_input.as_ref() {
+            interp.input.clone_from(input);
+        }
+    }
+
+    fn next_line(&mut self) -> Option<io::Result<String>> {
+        match (self.peeked_line.take(), &mut self.sink) {
+            (Some(line), _) => Some(line),
+            (None, TraceSink::Verify(lines)) => lines.next(),
+            (None, _) => None,
+        }
+    }
+
+    fn write_line(&mut self) {
+        if let TraceSink::Write(writer) = &mut self.sink {
+            if let Err(e) = writeln!(writer, "{}", self.line) {
+                log::error!("Failed to write instruction trace, tracing stopped: {}", e);
+                self.sink = TraceSink::Done;
+            }
+        }
+    }
+
+    pub fn trace(&mut self, interp: &Interpreter) {
+        if let TraceSink::Done = self.sink {
+            return;
+        }
+
+        let Some(instruction) = self.instruction else {
+            return
+        };
+
+        let size = instruction.size();
+
+        self.asm.clear();
+        self.asm_desc.clear();
+        write_inst_dasm(&instruction, interp.rom.config, &mut self.asm, &mut self.asm_desc).ok();
+
+        let cycle = self.cycle;
+        self.cycle += 1;
+
+        if matches!(self.sink, TraceSink::Write(_)) && self.written_input.as_ref() != Some(&self.input) {
+            self.line.clear();
+            write_input_line(&mut self.line, cycle, &self.input);
+            self.write_line();
+            self.written_input = Some(self.input.clone());
+        }
+
+        if let Some(filter) = self.filter.as_ref() {
+            if !filter.matches(&instruction, self.asm.split_whitespace().next().unwrap_or_default()) {
+                return;
+            }
+        }
+
+        // mnemonics are padded for column alignment which is noise to tooling
+        self.line.clear();
+        write!(
+            self.line,
+            "{{\"cycle\":{},\"pc\":{},\"opcode\":\"{:0width$X}\",\"mnemonic\":\"",
+            cycle,
+            self.pc,
+            self.opcode >> (32 - 8 * size),
+            width = 2 * size as usize
+        )
+        .ok();
+        for (i, token) in self.asm.split_whitespace().enumerate() {
+            if i > 0 {
+                self.line.push(' ');
+            }
+            self.line.push_str(token);
+        }
+        write!(
+            self.line,
+            "\",\"registers\":{:?},\"index\":{},\"flags\":{:?}}}",
+            interp.registers, interp.index, interp.flags
+        )
+        .ok();
+
+        match self.sink {
+            TraceSink::Write(_) => self.write_line(),
+            TraceSink::Verify(_) => match self.next_line() {
+                Some(Ok(expected)) => {
+                    if expected.trim_end() != self.line {
+                        self.divergence = Some(format!(
+                            "Trace diverged at cycle {}: expected {} but got {}",
+                            cycle,
+                            expected.trim_end(),
+                            self.line
+                        ));
+                        self.sink = TraceSink::Done;
+                    }
+                }
+                Some(Err(e)) => {
+                    self.divergence = Some(format!("Failed to read trace at cycle {}: {}", cycle, e));
+                    self.sink = TraceSink::Done;
+                }
+                None => {
+                    log::info!("Trace verified for every recorded instruction before cycle {}", cycle);
+                    self.sink = TraceSink::Done;
+                }
+            },
+            TraceSink::Done => (),
+        }
+    }
+}
+
+fn parse_seed_line(line: &str) -> Option<u64> {
+    line.trim_end().strip_prefix("{\"seed\":")?.strip_suffix('}')?.parse().ok()
+}
+
+fn write_input_line(line: &mut String, cycle: u64, input: &InterpreterInput) {
+    let key = |key: Option<u8>| key.map_or(String::from("null"), |key| key.to_string());
+    write!(
+        line,
+        "{{\"cycle\":{},\"keys\":{},\"pressed\":{},\"released\":{},\"delay\":{},\"vblank\":{}}}",
+        cycle,
+        input.down_keys,
+        key(input.just_pressed_key),
+        key(input.just_released_key),
+        input.delay_timer,
+        input.vertical_blank
+    )
+    .ok();
+}
+
+// instruction lines are not input lines since their mnemonic and register fields do not parse as input
+fn parse_input_line(line: &str) -> Option<(u64, InterpreterInput)> {
+    let key = |value: &str| match value {
+        "null" => Some(None),
+        value => value.parse().ok().map(Some),
+    };
+
+    let mut cycle = None;
+    let mut input = InterpreterInput::default();
+    for field in line.trim_end().strip_prefix('{')?.strip_suffix('}')?.split(',') {
+        let (name, value) = field.split_once(':')?;
+        match name {
+            "\"cycle\"" => cycle = Some(value.parse().ok()?),
+            "\"keys\"" => input.down_keys = value.parse().ok()?,
+            "\"pressed\"" => input.just_pressed_key = key(value)?,
+            "\"released\"" => input.just_released_key = key(value)?,
+            "\"delay\"" => input.delay_timer = value.parse().ok()?,
+            "\"vblank\"" => input.vertical_blank = value.parse().ok()?,
+            _ => return None,
+        }
+    }
+
+    Some((cycle?, input))
+}
+
+impl Drop for InstructionTracer {
+    fn drop(&mut self) {
+        if let TraceSink::Write(writer) = &mut self.sink {
+            if let Err(e) = writer.flush() {
+                log::error!("Failed to flush instruction trace: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch8::{
+        audio::silent_audio_controller,
+        input::Key,
+        rom::{Rom, RomKind},
+        vm::{VMError, VM},
+    };
+
+    use std::{env, fs, path::PathBuf};
+
+    // ld v1 5, rnd v0 0xFF, sknp v1, add v2 1, jp 0x202
+    const PROGRAM: [u8; 10] = [0x61, 0x05, 0xC0, 0xFF, 0xE1, 0xA1, 0x72, 0x01, 0x12, 0x02];
+
+    fn vm(program: &[u8]) -> VM {
+        let kind = RomKind::CHIP8;
+        VM::new(
+            Rom::with_program(kind, program),
+            kind.default_memory_size(),
+            kind.default_cycles_per_frame(),
+            silent_audio_controller(),
+        )
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("c8-test-{}-{}", std::process::id(), name))
+    }
+
+    // random numbers throughout and key 5 held for the middle third
+    fn record(path: &PathBuf) -> VM {
+        let key = Key::try_from(0x5).unwrap();
+        let mut vm = vm(&PROGRAM);
+        vm.set_tracer(Some(InstructionTracer::create(path).expect("Failed to create trace")));
+        assert_eq!(vm.stepn(10), Ok(true));
+        vm.press_key(key);
+        assert_eq!(vm.stepn(10), Ok(true));
+        vm.release_key(key);
+        assert_eq!(vm.stepn(10), Ok(true));
+
+        // flushes the trace
+        vm.set_tracer(None);
+        vm
+    }
+
+    #[test]
+    fn verifying_replays_the_recorded_seed_and_keys() {
+        let path = temp_path("replay.jsonl");
+        let recorded = record(&path);
+
+        let mut vm = vm(&PROGRAM);
+        vm.set_tracer(Some(InstructionTracer::verify(&path).expect("Failed to read trace")));
+        let result = vm.stepn(30);
+        fs::remove_file(&path).ok();
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(vm.interpreter().rng_seed(), recorded.interpreter().rng_seed());
+        assert_eq!(vm.interpreter().registers, recorded.interpreter().registers);
+        assert_ne!(vm.interpreter().registers[2], 0);
+    }
+
+    #[test]
+    fn verifying_a_different_program_reports_the_first_divergence() {
+        let path = temp_path("diverge.jsonl");
+        record(&path);
+
+        // add v2 2 in place of add v2 1
+        let mut program = PROGRAM;
+        program[7] = 0x02;
+
+        let mut vm = vm(&program);
+        vm.set_tracer(Some(InstructionTracer::verify(&path).expect("Failed to read trace")));
+        let result = vm.stepn(30);
+        fs::remove_file(&path).ok();
+
+        let Err(VMError::TraceDivergence(error)) = result else {
+            panic!("A different program should not verify");
+        };
+        assert!(error.starts_with("Trace diverged at cycle "), "{error}");
+        assert!(error.contains("\"mnemonic\":\"add v2 1\""), "{error}");
+    }
+}
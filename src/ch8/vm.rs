@@ -2,16 +2,53 @@ use super::{
     audio::{AudioController, AudioEvent},
     disp::{Display, DisplayWidget},
     input::{Key, Keyboard},
-    instruct::Instruction,
     interp::*,
     rom::Rom,
+    script::{InputScript, InputScriptStep},
+    timing::{FrameTiming, InstructionTimings},
+    trace::InstructionTracer,
 };
 
-use std::time::Duration;
+#[cfg(feature = "debugger")]
+use super::instruct::Instruction;
+
+use tui::style::Color;
+
+use std::time::{Duration, Instant};
 
 pub const VM_FRAME_RATE: u32 = 60;
 pub const VM_FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / VM_FRAME_RATE as u64); // 60 FPS
 
+// why run_until returned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunUntilStop {
+    Matched,   // the predicate held
+    StepLimit, // the predicate never held within the step cap
+    Stopped,   // the program exited or halted before the predicate held
+}
+
+// why stepping the vm failed, only formatted into a message once something displays it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VMError {
+    Interpreter(InterpreterError),
+    TraceDivergence(String),
+}
+
+impl From<InterpreterError> for VMError {
+    fn from(error: InterpreterError) -> Self {
+        Self::Interpreter(error)
+    }
+}
+
+impl std::fmt::Display for VMError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Interpreter(error) => write!(f, "{}", error),
+            Self::TraceDivergence(divergence) => write!(f, "{}", divergence),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum VMEvent {
     KeyUp(Key),
@@ -34,12 +71,15 @@ pub struct VM {
     cycles_per_frame: u32,
 
     interpreter: Interpreter,
+    executed_instructions: u64,
+    cycles: u64, // every cycle stepped since the program started, including those spent waiting or frozen
 
     // Event receiver and queue
     event_queue: Vec<VMEvent>,
 
     // Virtualized IO
     display: bool, // TODO handle new frame indication outside like sound
+    display_updates: u64,
     keyboard: Keyboard,
     audio: AudioController,
 
@@ -52,16 +92,25 @@ pub struct VM {
 
     delay_timer: u8,
     delay_timer_cycle_offset: u32,
+
+    // Debugging aids that gate the cpu or the delay and sound timers independently
+    cpu_frozen: bool,
+    timers_frozen: bool,
+
+    tracer: Option<InstructionTracer>,
+    timings: Option<InstructionTimings>,
+    frame_timing: FrameTiming,
 }
 
 impl VM {
     pub fn new(
         rom: Rom,
+        memory_size: usize,
         cycles_per_frame: u32,
         mut audio: AudioController,
     ) -> Self {
         let vsync_enabled = rom.config.quirks.wait_for_vertical_sync;
-        let interpreter = Interpreter::new(rom);
+        let interpreter = Interpreter::with_memory_size(rom, memory_size);
 
         audio.apply_event(AudioEvent::SetBuffer(interpreter.audio.buffer));
         audio.apply_event(AudioEvent::SetPitch(interpreter.audio.pitch));
@@ -70,10 +119,13 @@ impl VM {
             cycles_per_frame,
 
             interpreter,
+            executed_instructions: 0,
+            cycles: 0,
 
             event_queue: Vec::new(),
 
             display: true,
+            display_updates: 0,
             keyboard: Keyboard::default(),
             audio,
 
@@ -86,23 +138,14 @@ impl VM {
 
             delay_timer: 0,
             delay_timer_cycle_offset: 0,
-        }
-    }
-
-    pub fn reset(&mut self, preserve_rpl_flags: bool) {
-        self.interpreter.reset(preserve_rpl_flags);
-        self.event_queue.clear();
-        self.keyboard = Keyboard::default();
-        self.display = true;
-
-        self.vsync_timer = 0;
-        self.vsync_timer_cycle_offset = 0;
 
-        self.sound_timer = 0;
-        self.sound_timer_cycle_offset = 0;
+            cpu_frozen: false,
+            timers_frozen: false,
 
-        self.delay_timer = 0;
-        self.delay_timer_cycle_offset = 0;
+            tracer: None,
+            timings: None,
+            frame_timing: FrameTiming::default(),
+        }
     }
 
     pub fn set_cycles_per_frame(&mut self, cycles_per_frame: u32) {
@@ -125,44 +168,6 @@ impl VM {
         self.cycles_per_frame
     }
 
-    pub fn undo(&mut self, state: &VMHistoryFragment, memory_access_flags: &mut [u8]) {
-        self.cycles_per_frame = state.cycles_per_frame;
-        self.keyboard = state.keyboard;
-        self.vsync_timer = state.vsync_timer;
-        self.vsync_timer_cycle_offset = state.vsync_timer_cycle_offset;
-        self.sound_timer = state.sound_timer;
-        self.sound_timer_cycle_offset = state.sound_timer_cycle_offset;
-        self.delay_timer = state.delay_timer;
-        self.delay_timer_cycle_offset = state.delay_timer_cycle_offset;
-
-        self.audio
-            .apply_event(AudioEvent::SetTimer(Duration::from_secs_f32(
-                self.precise_sound_timer() / VM_FRAME_RATE as f32,
-            )));
-
-        if let Some(Instruction::Draw(_, _, _)) = state.interpreter.instruction {
-            self.display = true;
-        }
-
-        match state.interpreter.extra.as_deref() {
-            Some(&InterpreterHistoryFragmentExtra::WillDrawEntireDisplay { .. }) => {
-                self.display = true;
-            }
-
-            Some(&InterpreterHistoryFragmentExtra::WillLoadAudio { prior_buffer, .. }) => {
-                self.audio.apply_event(AudioEvent::SetBuffer(prior_buffer));
-            }
-
-            Some(&InterpreterHistoryFragmentExtra::WillSetPitch { prior_pitch }) => {
-                self.audio.apply_event(AudioEvent::SetPitch(prior_pitch));
-            }
-
-            _ => (),
-        }
-
-        self.interpreter.undo(&state.interpreter, memory_access_flags);
-    }
-
     pub fn interpreter(&self) -> &Interpreter {
         &self.interpreter
     }
@@ -171,10 +176,6 @@ impl VM {
         &self.keyboard
     }
 
-    pub fn keyboard_mut(&mut self) -> &mut Keyboard {
-        &mut self.keyboard
-    }
-
     pub fn audio(&self) -> &AudioController {
         &self.audio
     }
@@ -191,39 +192,63 @@ impl VM {
         self.audio.apply_event(AudioEvent::Resume)
     }
 
-    pub fn delay_timer(&self) -> u8 {
-        self.delay_timer
+    pub fn stall_reason(&self) -> Option<StallReason> {
+        if self.cpu_frozen {
+            Some(StallReason::Paused)
+        } else {
+            self.interpreter.stall_reason()
+        }
     }
 
-    pub fn precise_sound_timer(&self) -> f32 {
-        (self.sound_timer as f32
-            - self.sound_timer_cycle_offset as f32 / self.cycles_per_frame as f32)
-            .max(0.0)
+    pub fn set_settings(&mut self, settings: InterpreterSettings) {
+        self.interpreter.settings = settings;
     }
 
-    pub fn precise_delay_timer(&self) -> f32 {
-        (self.delay_timer as f32
-            - self.delay_timer_cycle_offset as f32 / self.cycles_per_frame as f32)
-            .max(0.0)
+    pub fn set_palette(&mut self, colors: [Color; 16]) {
+        self.interpreter.display.colors = colors;
     }
 
-    pub fn precise_vsync_progress(&self) -> f32 {
-        if self.vsync_timer == 0 {
-            1.0
-        } else {
-            self.vsync_timer_cycle_offset as f32 / self.cycles_per_frame as f32
+    pub fn frame_timing_mut(&mut self) -> &mut FrameTiming {
+        &mut self.frame_timing
+    }
+
+    pub fn set_tracer(&mut self, mut tracer: Option<InstructionTracer>) {
+        if let Some(tracer) = tracer.as_mut() {
+            tracer.attach(&mut self.interpreter);
         }
+        self.tracer = tracer;
     }
 
-    pub fn queue_events(&mut self, events: impl Iterator<Item = VMEvent>) {
-        self.event_queue.extend(events);
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
     }
 
-    pub fn clear_event_queue(&mut self) {
-        self.event_queue.clear();
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn queue_events(&mut self, events: impl Iterator<Item = VMEvent>) {
+        self.event_queue.extend(events);
     }
 
     pub fn drain_event_queue(&mut self) {
+        // a key released in the same batch it was pressed in would never be seen down by the program
+        // so the release and everything after it wait for the next flush
+        let mut pressed_keys = 0_u16;
+        let deferred_start = self
+            .event_queue
+            .iter()
+            .position(|event| match *event {
+                VMEvent::KeyDown(key) | VMEvent::FocusingKeyDown(key) => {
+                    pressed_keys |= 1 << key.to_code();
+                    false
+                }
+                VMEvent::KeyUp(key) => pressed_keys >> key.to_code() & 1 == 1,
+                _ => false,
+            })
+            .unwrap_or(self.event_queue.len());
+        let deferred_events = self.event_queue.split_off(deferred_start);
+
         for event in self.event_queue.drain(..) {
             log::debug!("Processing Event {:?}", event);
             match event {
@@ -243,6 +268,8 @@ impl VM {
                 }
             }
         }
+
+        self.event_queue = deferred_events;
     }
 
     pub fn extract_new_display(&mut self) -> Option<Display> {
@@ -254,6 +281,11 @@ impl VM {
         }
     }
 
+    // how many instructions the interpreter has run, where cycles skipped while waiting are not counted
+    pub fn executed_instructions(&self) -> u64 {
+        self.executed_instructions
+    }
+
     pub fn clear_ephemeral_state(&mut self) {
         self.keyboard.clear_ephemeral_state();
     }
@@ -263,7 +295,7 @@ impl VM {
         self.keyboard.flush(&mut self.interpreter.input);
     }
 
-    pub fn flush_external_input_and_stepn(&mut self, amt: u32) -> Result<bool, String> {
+    pub fn flush_external_input_and_stepn(&mut self, amt: u32) -> Result<bool, VMError> {
         self.flush_external_input();
 
         let should_continue = self.stepn(1)?;
@@ -277,8 +309,12 @@ impl VM {
         self.stepn(amt - 1)
     }
 
-    pub fn stepn(&mut self, mut amt: u32) -> Result<bool, String> {
+    pub fn stepn(&mut self, mut amt: u32) -> Result<bool, VMError> {
         self.flush_timers(VMSprint::default());
+
+        // nothing a waiting instruction depends on (vertical blank, key input) changes until the batch ends
+        // so once the interpreter waits the rest of the batch only elapses for the timers
+        let mut waiting = false;
         while amt > 0 {
             let sprint_amt = amt.min(self.min_cycles_before_timer_tick());
             let mut sprint = VMSprint {
@@ -286,14 +322,47 @@ impl VM {
                 ..Default::default()
             };
 
-            for cycle in 1..=sprint_amt {
+            // a frozen or waiting cpu lets the cycles elapse for the timers without executing anything
+            let executed_cycles = if self.cpu_frozen || waiting { 0 } else { sprint_amt };
+
+            for cycle in 1..=executed_cycles {
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.prepare(&mut self.interpreter);
+                }
+
+                let timing = match self.timings {
+                    Some(_) => self.interpreter.instruction().map(|instruction| (instruction, Instant::now())),
+                    None => None,
+                };
+
                 if !self.interpreter.step() {
-                    return self.interpreter.stop_result();
+                    return self.interpreter.stop_result().map_err(VMError::from);
+                }
+
+                if self.interpreter.waiting {
+                    waiting = true;
+                    break;
+                }
+
+                self.executed_instructions += 1;
+
+                if let (Some(timings), Some((instruction, start))) = (self.timings.as_mut(), timing) {
+                    timings.record(instruction, start.elapsed());
+                }
+
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.trace(&self.interpreter);
+                    if let Some(divergence) = tracer.take_divergence() {
+                        return Err(VMError::TraceDivergence(divergence));
+                    }
                 }
 
                 if let Some(output) = self.interpreter.output.take() {
                     match output {
-                        InterpreterOutput::Display => self.display = true,
+                        InterpreterOutput::Display => {
+                            self.display = true;
+                            self.display_updates += 1;
+                        }
                         InterpreterOutput::SetDelayTimer(ticks) => {
                             sprint.set_delay_timer_cycle = cycle;
                             self.interpreter.input.delay_timer = ticks;
@@ -304,10 +373,12 @@ impl VM {
                             sprint.set_sound_timer_cycle = cycle;
                             self.sound_timer = ticks;
                             self.sound_timer_cycle_offset = 0;
-                            self.audio
-                                .apply_event(AudioEvent::SetTimer(Duration::from_secs_f32(
-                                    ticks as f32 / VM_FRAME_RATE as f32,
-                                )));
+                            if !self.timers_frozen {
+                                self.audio
+                                    .apply_event(AudioEvent::SetTimer(Duration::from_secs_f32(
+                                        ticks as f32 / VM_FRAME_RATE as f32,
+                                    )));
+                            }
                         }
                         InterpreterOutput::UpdateAudioBuffer => {
                             self.audio
@@ -328,53 +399,122 @@ impl VM {
             }
 
             amt -= sprint_amt;
+            self.cycles += sprint_amt as u64;
             self.flush_timers(sprint);
         }
 
         Ok(true)
     }
 
+    // steps one instruction at a time, so timers and input behave as they do in the debugger, until the predicate
+    // holds or max_steps have run. the predicate is checked before the first step so a state that already matches
+    // runs nothing. steps count cycles, including those spent waiting for a key or the vertical blank
+    pub fn run_until<F: Fn(&Interpreter) -> bool>(
+        &mut self,
+        predicate: F,
+        max_steps: u64,
+    ) -> Result<(u64, RunUntilStop), VMError> {
+        let mut steps = 0;
+        loop {
+            if predicate(&self.interpreter) {
+                return Ok((steps, RunUntilStop::Matched));
+            }
+
+            if steps == max_steps {
+                return Ok((steps, RunUntilStop::StepLimit));
+            }
+
+            let should_continue = self.stepn(1)?;
+            steps += 1;
+
+            if !should_continue {
+                return Ok((steps, RunUntilStop::Stopped));
+            }
+        }
+    }
+
+    // replays a recorded run from the start of the program one cycle at a time, as the debugger steps, so the timers
+    // and the vertical blank land on the same cycles. returns whether the program can keep running afterwards
+    pub fn replay(&mut self, script: &InputScript) -> Result<bool, VMError> {
+        self.interpreter.seed_rng(script.seed);
+
+        for step in script.steps.iter() {
+            let (cycles, keyboard, cpu_frozen) = match *step {
+                InputScriptStep::CyclesPerFrame(cycles_per_frame) => {
+                    self.set_cycles_per_frame(cycles_per_frame);
+                    continue;
+                }
+                InputScriptStep::Keys {
+                    down_keys,
+                    pressed,
+                    released,
+                    instructions,
+                } => (
+                    instructions,
+                    Keyboard::with_state(down_keys, pressed, released),
+                    self.cpu_frozen,
+                ),
+                // nothing that would end the wait changes, so the keys stay down without any changes
+                InputScriptStep::Wait(cycles) => {
+                    let (&down_keys, _, _) = self.keyboard.state();
+                    (
+                        cycles,
+                        Keyboard::with_state(down_keys, None, None),
+                        self.cpu_frozen,
+                    )
+                }
+                InputScriptStep::Idle(cycles) => (cycles, self.keyboard, true),
+            };
+
+            let prior_cpu_frozen = std::mem::replace(&mut self.cpu_frozen, cpu_frozen);
+            self.keyboard = keyboard;
+            self.keyboard.flush(&mut self.interpreter.input);
+
+            let mut result = Ok(true);
+            for _ in 0..cycles {
+                result = self.stepn(1);
+                if result != Ok(true) {
+                    break;
+                }
+            }
+
+            self.cpu_frozen = prior_cpu_frozen;
+            if result != Ok(true) {
+                return result;
+            }
+        }
+
+        Ok(true)
+    }
+
     pub fn to_display_widget(&self) -> DisplayWidget {
         DisplayWidget {
             display: self.interpreter.display.clone(),
             rom_name: self.interpreter.rom.name.clone(),
             rom_config: self.interpreter.rom.config.clone(),
             cycles_per_frame: self.cycles_per_frame,
+            stall_reason: self.stall_reason(),
+            scanlines: false,
+            phosphor_fade: None,
+            scale: 1,
         }
     }
 
-    pub fn to_history_fragment(&self, memory_access_flags: &[u8]) -> VMHistoryFragment {
-        VMHistoryFragment {
-            cycles_per_frame: self.cycles_per_frame,
-            keyboard: self.keyboard,
-            interpreter: self.interpreter.to_history_fragment(memory_access_flags),
-            vsync_timer: self.vsync_timer,
-            vsync_timer_cycle_offset: self.vsync_timer_cycle_offset,
-            sound_timer: self.sound_timer,
-            sound_timer_cycle_offset: self.sound_timer_cycle_offset,
-            delay_timer: self.delay_timer,
-            delay_timer_cycle_offset: self.delay_timer_cycle_offset,
-        }
-    }
-
-    pub fn update_memory_access_flags(&mut self, executed_fragment: &InterpreterHistoryFragment, memory_access_flags: &mut [u8]) {
-        self.interpreter
-            .update_memory_access_flags(executed_fragment, memory_access_flags);
-    }
-
     fn flush_timers(&mut self, sprint: VMSprint) {
-        update_timer(
-            sprint.cycles - sprint.set_sound_timer_cycle,
-            self.cycles_per_frame,
-            &mut self.sound_timer,
-            &mut self.sound_timer_cycle_offset,
-        );
-        update_timer(
-            sprint.cycles - sprint.set_delay_timer_cycle,
-            self.cycles_per_frame,
-            &mut self.delay_timer,
-            &mut self.delay_timer_cycle_offset,
-        );
+        if !self.timers_frozen {
+            update_timer(
+                sprint.cycles - sprint.set_sound_timer_cycle,
+                self.cycles_per_frame,
+                &mut self.sound_timer,
+                &mut self.sound_timer_cycle_offset,
+            );
+            update_timer(
+                sprint.cycles - sprint.set_delay_timer_cycle,
+                self.cycles_per_frame,
+                &mut self.delay_timer,
+                &mut self.delay_timer_cycle_offset,
+            );
+        }
         update_timer(
             sprint.cycles,
             self.cycles_per_frame,
@@ -412,6 +552,277 @@ impl VM {
     }
 }
 
+// what the debugger reaches into the vm for, left out of builds without it
+#[cfg(feature = "debugger")]
+impl VM {
+    pub fn reset(&mut self, preserve_rpl_flags: bool) {
+        self.interpreter.reset(preserve_rpl_flags);
+        self.cycles = 0;
+        self.event_queue.clear();
+        self.keyboard = Keyboard::default();
+        self.display = true;
+
+        self.vsync_timer = 0;
+        self.vsync_timer_cycle_offset = 0;
+
+        self.sound_timer = 0;
+        self.sound_timer_cycle_offset = 0;
+
+        self.delay_timer = 0;
+        self.delay_timer_cycle_offset = 0;
+    }
+
+    pub fn to_checkpoint(&self) -> VMCheckpoint {
+        VMCheckpoint {
+            interpreter: self.interpreter.clone(),
+            cycles: self.cycles,
+            cycles_per_frame: self.cycles_per_frame,
+            keyboard: self.keyboard,
+            vsync_timer: self.vsync_timer,
+            vsync_timer_cycle_offset: self.vsync_timer_cycle_offset,
+            sound_timer: self.sound_timer,
+            sound_timer_cycle_offset: self.sound_timer_cycle_offset,
+            delay_timer: self.delay_timer,
+            delay_timer_cycle_offset: self.delay_timer_cycle_offset,
+        }
+    }
+
+    pub fn restore_checkpoint(&mut self, checkpoint: &VMCheckpoint) {
+        self.interpreter.restore_copy(&checkpoint.interpreter);
+        self.event_queue.clear();
+        self.display = true;
+
+        self.cycles = checkpoint.cycles;
+        self.cycles_per_frame = checkpoint.cycles_per_frame;
+        self.keyboard = checkpoint.keyboard;
+        self.vsync_timer = checkpoint.vsync_timer;
+        self.vsync_timer_cycle_offset = checkpoint.vsync_timer_cycle_offset;
+        self.sound_timer = checkpoint.sound_timer;
+        self.sound_timer_cycle_offset = checkpoint.sound_timer_cycle_offset;
+        self.delay_timer = checkpoint.delay_timer;
+        self.delay_timer_cycle_offset = checkpoint.delay_timer_cycle_offset;
+
+        // the audio device plays on its own so it is brought in line with the restored sound state
+        self.audio.apply_event(AudioEvent::SetBuffer(self.interpreter.audio.buffer));
+        self.audio.apply_event(AudioEvent::SetPitch(self.interpreter.audio.pitch));
+        if !self.timers_frozen {
+            self.audio.apply_event(AudioEvent::SetTimer(Duration::from_secs_f32(
+                self.precise_sound_timer() / VM_FRAME_RATE as f32,
+            )));
+        }
+    }
+
+    pub fn undo(&mut self, state: &VMHistoryFragment, memory_access_flags: &mut [u8]) {
+        self.cycles = state.cycle;
+        self.cycles_per_frame = state.cycles_per_frame;
+        self.keyboard = state.keyboard;
+        self.vsync_timer = state.vsync_timer;
+        self.vsync_timer_cycle_offset = state.vsync_timer_cycle_offset;
+        self.sound_timer = state.sound_timer;
+        self.sound_timer_cycle_offset = state.sound_timer_cycle_offset;
+        self.delay_timer = state.delay_timer;
+        self.delay_timer_cycle_offset = state.delay_timer_cycle_offset;
+
+        self.audio
+            .apply_event(AudioEvent::SetTimer(Duration::from_secs_f32(
+                self.precise_sound_timer() / VM_FRAME_RATE as f32,
+            )));
+
+        if let Some(Instruction::Draw(_, _, _)) = state.interpreter.instruction {
+            self.display = true;
+        }
+
+        match state.interpreter.extra.as_deref() {
+            Some(&InterpreterHistoryFragmentExtra::WillDrawEntireDisplay { .. }) => {
+                self.display = true;
+            }
+
+            Some(&InterpreterHistoryFragmentExtra::WillLoadAudio { prior_buffer, .. }) => {
+                self.audio.apply_event(AudioEvent::SetBuffer(prior_buffer));
+            }
+
+            Some(&InterpreterHistoryFragmentExtra::WillSetPitch { prior_pitch }) => {
+                self.audio.apply_event(AudioEvent::SetPitch(prior_pitch));
+            }
+
+            _ => (),
+        }
+
+        self.interpreter.undo(&state.interpreter, memory_access_flags);
+    }
+
+    pub fn keyboard_mut(&mut self) -> &mut Keyboard {
+        &mut self.keyboard
+    }
+
+    pub fn is_cpu_frozen(&self) -> bool {
+        self.cpu_frozen
+    }
+
+    pub fn set_cpu_frozen(&mut self, frozen: bool) {
+        self.cpu_frozen = frozen;
+    }
+
+    pub fn are_timers_frozen(&self) -> bool {
+        self.timers_frozen
+    }
+
+    pub fn set_timers_frozen(&mut self, frozen: bool) {
+        if self.timers_frozen == frozen {
+            return;
+        }
+
+        self.timers_frozen = frozen;
+
+        // sound is silenced while frozen and resumes from the held sound timer
+        // setting the timer restarts the audio countdown from now so no time elapses across the freeze
+        self.audio.apply_event(AudioEvent::SetTimer(if frozen {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f32(self.precise_sound_timer() / VM_FRAME_RATE as f32)
+        }));
+    }
+
+    // executes an instruction outside of the program (e.g. the debugger clearing the display)
+    // only display output is forwarded since that is all the debugger injects
+    pub fn inject(&mut self, instruction: Instruction) {
+        self.interpreter.inject(instruction);
+        if let Some(InterpreterOutput::Display) = self.interpreter.output.take() {
+            self.display = true;
+        }
+    }
+
+    pub fn write_memory(&mut self, address: u16, bytes: &[u8]) {
+        self.interpreter.write_memory(address, bytes);
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.interpreter.set_pc(pc);
+    }
+
+    // timings are discarded when turned off so turning them back on starts a fresh measurement
+    pub fn set_timing(&mut self, enabled: bool) {
+        if enabled != self.timings.is_some() {
+            self.timings = enabled.then(InstructionTimings::default);
+        }
+    }
+
+    pub fn timings(&self) -> Option<&InstructionTimings> {
+        self.timings.as_ref()
+    }
+
+    // recorded by the run thread which is the only one that knows how its frames were scheduled
+    pub fn frame_timing(&self) -> &FrameTiming {
+        &self.frame_timing
+    }
+
+    pub fn tracer_mut(&mut self) -> Option<&mut InstructionTracer> {
+        self.tracer.as_mut()
+    }
+
+    pub fn precise_sound_timer(&self) -> f32 {
+        (self.sound_timer as f32
+            - self.sound_timer_cycle_offset as f32 / self.cycles_per_frame as f32)
+            .max(0.0)
+    }
+
+    pub fn precise_delay_timer(&self) -> f32 {
+        (self.delay_timer as f32
+            - self.delay_timer_cycle_offset as f32 / self.cycles_per_frame as f32)
+            .max(0.0)
+    }
+
+    pub fn precise_vsync_progress(&self) -> f32 {
+        if self.vsync_timer == 0 {
+            1.0
+        } else {
+            self.vsync_timer_cycle_offset as f32 / self.cycles_per_frame as f32
+        }
+    }
+
+    pub fn clear_event_queue(&mut self) {
+        self.event_queue.clear();
+    }
+
+    // how many cycles have been stepped, where cycles spent waiting or with the cpu frozen are counted
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // how many executed instructions have changed the display, unlike the new display flag it is never consumed
+    pub fn display_updates(&self) -> u64 {
+        self.display_updates
+    }
+
+    pub fn to_history_fragment(&self, memory_access_flags: &[u8]) -> VMHistoryFragment {
+        self.wrap_history_fragment(self.interpreter.to_history_fragment(memory_access_flags))
+    }
+
+    pub fn to_injected_history_fragment(
+        &self,
+        instruction: Instruction,
+        memory_access_flags: &[u8],
+    ) -> VMHistoryFragment {
+        self.wrap_history_fragment(
+            self.interpreter
+                .to_injected_history_fragment(instruction, memory_access_flags),
+        )
+    }
+
+    pub fn to_memory_edit_history_fragment(
+        &self,
+        address: u16,
+        bytes: &[u8],
+        memory_access_flags: &[u8],
+    ) -> VMHistoryFragment {
+        self.wrap_history_fragment(
+            self.interpreter
+                .to_memory_edit_history_fragment(address, bytes, memory_access_flags),
+        )
+    }
+
+    pub fn to_pc_edit_history_fragment(&self, pc: u16, memory_access_flags: &[u8]) -> VMHistoryFragment {
+        self.wrap_history_fragment(self.interpreter.to_pc_edit_history_fragment(pc, memory_access_flags))
+    }
+
+    fn wrap_history_fragment(&self, interpreter: InterpreterHistoryFragment) -> VMHistoryFragment {
+        VMHistoryFragment {
+            cycle: self.cycles,
+            cycles_per_frame: self.cycles_per_frame,
+            keyboard: self.keyboard,
+            interpreter,
+            vsync_timer: self.vsync_timer,
+            vsync_timer_cycle_offset: self.vsync_timer_cycle_offset,
+            sound_timer: self.sound_timer,
+            sound_timer_cycle_offset: self.sound_timer_cycle_offset,
+            delay_timer: self.delay_timer,
+            delay_timer_cycle_offset: self.delay_timer_cycle_offset,
+        }
+    }
+
+    pub fn update_memory_access_flags(&mut self, executed_fragment: &InterpreterHistoryFragment, memory_access_flags: &mut [u8]) {
+        self.interpreter
+            .update_memory_access_flags(executed_fragment, memory_access_flags);
+    }
+}
+
+// tests drive programs through these as well
+#[cfg(any(feature = "debugger", test))]
+impl VM {
+    // input that does not come from the terminal (the debugger, scripts, headless runs) is applied to the program
+    // right away so the next step sees the key and its press or release edge without a flush
+    pub fn press_key(&mut self, key: Key) {
+        self.keyboard.handle_focus();
+        self.keyboard.handle_key_down(key);
+        self.keyboard.flush(&mut self.interpreter.input);
+    }
+
+    pub fn release_key(&mut self, key: Key) {
+        self.keyboard.handle_key_up(key);
+        self.keyboard.flush(&mut self.interpreter.input);
+    }
+}
+
 fn update_timer(cycles: u32, cycles_per_frame: u32, timer: &mut u8, timer_cycle_offset: &mut u32) {
     if *timer == 0 {
         return;
@@ -433,8 +844,32 @@ fn update_timer(cycles: u32, cycles_per_frame: u32, timer: &mut u8, timer_cycle_
     }
 }
 
+// a full copy of the vm state to return to later, where a history fragment only holds enough to undo one step
+#[cfg(feature = "debugger")]
+pub struct VMCheckpoint {
+    interpreter: Interpreter,
+    cycles: u64,
+    cycles_per_frame: u32,
+    keyboard: Keyboard,
+    vsync_timer: u8,
+    vsync_timer_cycle_offset: u32,
+    sound_timer: u8,
+    sound_timer_cycle_offset: u32,
+    delay_timer: u8,
+    delay_timer_cycle_offset: u32,
+}
+
+#[cfg(feature = "debugger")]
+impl VMCheckpoint {
+    pub fn pc(&self) -> u16 {
+        self.interpreter.pc
+    }
+}
+
 #[derive(PartialEq)]
+#[cfg(feature = "debugger")]
 pub struct VMHistoryFragment {
+    pub cycle: u64, // the cycle the instruction completed on, which is later than the last one's after a wait
     pub cycles_per_frame: u32,
     pub keyboard: Keyboard,
     pub interpreter: InterpreterHistoryFragment,
@@ -446,8 +881,10 @@ pub struct VMHistoryFragment {
     pub delay_timer_cycle_offset: u32,
 }
 
+#[cfg(feature = "debugger")]
 impl VMHistoryFragment {
     pub fn restore(&self, vm: &mut VM) {
+        vm.cycles = self.cycle;
         vm.cycles_per_frame = self.cycles_per_frame;
         vm.keyboard = self.keyboard;
         vm.vsync_timer = self.vsync_timer;
@@ -459,6 +896,9 @@ impl VMHistoryFragment {
     }
 
     pub fn log_diff(&self, other: &Self) {
+        if self.cycle != other.cycle {
+            log::debug!("Cycle difference {:?} -> {:?}", self.cycle, other.cycle);
+        }
         if self.cycles_per_frame != other.cycles_per_frame {
             log::debug!(
                 "Cycles per frame difference {:?} -> {:?}",
@@ -497,3 +937,166 @@ impl VMHistoryFragment {
         self.interpreter.log_diff(&other.interpreter);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch8::{audio::silent_audio_controller, rom::RomKind};
+
+    fn vm(program: &[u8]) -> VM {
+        vm_of_kind(RomKind::CHIP8, program)
+    }
+
+    fn vm_of_kind(kind: RomKind, program: &[u8]) -> VM {
+        VM::new(
+            Rom::with_program(kind, program),
+            kind.default_memory_size(),
+            kind.default_cycles_per_frame(),
+            silent_audio_controller(),
+        )
+    }
+
+    #[test]
+    fn executed_instructions_counts_every_instruction_run() {
+        // jp 0x200
+        let mut vm = vm(&[0x12, 0x00]);
+        let cycles_per_frame = vm.cycles_per_frame();
+        assert_eq!(vm.stepn(cycles_per_frame), Ok(true));
+        assert_eq!(vm.executed_instructions(), cycles_per_frame as u64);
+    }
+
+    #[test]
+    fn executed_instructions_skips_cycles_spent_waiting() {
+        // ld v0 k
+        let mut vm = vm(&[0xF0, 0x0A]);
+        let cycles_per_frame = vm.cycles_per_frame();
+        assert_eq!(vm.stepn(cycles_per_frame), Ok(true));
+        assert!(vm.interpreter().waiting);
+        assert_eq!(vm.executed_instructions(), 0);
+    }
+
+    #[test]
+    fn waiting_ends_the_batch_but_not_the_timers() {
+        // ld v0 2, ld dt v0, ld v1 k
+        let mut vm = vm(&[0x60, 0x02, 0xF0, 0x15, 0xF1, 0x0A]);
+        let cycles_per_frame = vm.cycles_per_frame();
+        assert_eq!(vm.stepn(2), Ok(true));
+
+        // the running delay timer splits the batch at each tick, the wait must not start over after them
+        assert_eq!(vm.stepn(3 * cycles_per_frame), Ok(true));
+        assert!(vm.interpreter().waiting);
+        assert_eq!(vm.interpreter().pc, 0x204);
+        assert_eq!(vm.executed_instructions(), 2);
+        assert_eq!(vm.cycles, 2 + 3 * cycles_per_frame as u64);
+        assert_eq!(vm.delay_timer, 0);
+    }
+
+    #[test]
+    fn key_tapped_within_one_batch_is_seen_down_before_its_release() {
+        // skp v0, ld v1 1
+        let mut vm = vm(&[0xE0, 0x9E, 0x61, 0x01]);
+        let code = Key::Q.to_code();
+        vm.interpreter.registers[0] = code;
+
+        vm.queue_events([VMEvent::FocusingKeyDown(Key::Q), VMEvent::KeyUp(Key::Q)].into_iter());
+        assert_eq!(vm.flush_external_input_and_stepn(1), Ok(true));
+        assert_eq!(vm.interpreter().pc, 0x204);
+        assert_eq!(vm.keyboard().state(), (&(1 << code), &None, &None));
+
+        vm.flush_external_input();
+        assert_eq!(vm.keyboard().state(), (&0, &None, &Some(code)));
+    }
+
+    #[test]
+    fn timers_only_advance_with_stepped_cycles() {
+        // ld v0 10, ld dt v0, jp 0x204
+        let mut vm = vm(&[0x60, 0x0A, 0xF0, 0x15, 0x12, 0x04]);
+        let cycles_per_frame = vm.cycles_per_frame();
+        assert_eq!(vm.stepn(2), Ok(true));
+        assert_eq!(vm.delay_timer, 10);
+
+        // a frame's worth of cycles split across steps ticks once, however long passes between them
+        assert_eq!(vm.stepn(cycles_per_frame - 1), Ok(true));
+        assert_eq!(vm.delay_timer, 10);
+        assert_eq!(vm.stepn(1), Ok(true));
+        assert_eq!(vm.delay_timer, 9);
+
+        assert_eq!(vm.stepn(5 * cycles_per_frame), Ok(true));
+        assert_eq!(vm.delay_timer, 4);
+    }
+
+    #[test]
+    fn press_and_release_key_complete_a_key_wait() {
+        // ld v0 k
+        let mut vm = vm(&[0xF0, 0x0A]);
+        let code = Key::Q.to_code();
+        assert_eq!(vm.stepn(1), Ok(true));
+        assert!(vm.interpreter().waiting);
+
+        // the wait only ends once the key goes back up
+        vm.press_key(Key::Q);
+        assert_eq!(vm.interpreter().input.down_keys, 1 << code);
+        assert_eq!(vm.interpreter().input.just_pressed_key, Some(code));
+        assert_eq!(vm.stepn(1), Ok(true));
+        assert!(vm.interpreter().waiting);
+
+        vm.release_key(Key::Q);
+        assert_eq!(vm.interpreter().input.down_keys, 0);
+        assert_eq!(vm.interpreter().input.just_released_key, Some(code));
+        assert_eq!(vm.stepn(1), Ok(true));
+        assert!(!vm.interpreter().waiting);
+        assert_eq!(vm.interpreter().registers[0], code);
+        assert_eq!(vm.interpreter().pc, 0x202);
+    }
+
+    // ld v0 0, add v0 1, jp 0x202
+    const COUNT_UP: [u8; 6] = [0x60, 0x00, 0x70, 0x01, 0x12, 0x02];
+
+    #[test]
+    fn run_until_stops_at_an_address() {
+        let mut vm = vm(&COUNT_UP);
+        assert_eq!(vm.run_until(|interp| interp.pc == 0x204, 100), Ok((2, RunUntilStop::Matched)));
+        assert_eq!(vm.interpreter().registers[0], 1);
+    }
+
+    #[test]
+    fn run_until_stops_when_a_register_matches() {
+        let mut vm = vm(&COUNT_UP);
+        assert_eq!(vm.run_until(|interp| interp.registers[0] == 5, 100), Ok((10, RunUntilStop::Matched)));
+        assert_eq!(vm.interpreter().pc, 0x204);
+        assert_eq!(vm.executed_instructions(), 10);
+    }
+
+    #[test]
+    fn run_until_runs_nothing_when_the_predicate_already_holds() {
+        let mut vm = vm(&COUNT_UP);
+        assert_eq!(vm.run_until(|interp| interp.pc == 0x200, 100), Ok((0, RunUntilStop::Matched)));
+        assert_eq!(vm.executed_instructions(), 0);
+    }
+
+    #[test]
+    fn run_until_gives_up_at_the_step_limit() {
+        let mut vm = vm(&COUNT_UP);
+        assert_eq!(vm.run_until(|interp| interp.registers[0] == 5, 9), Ok((9, RunUntilStop::StepLimit)));
+        assert_eq!(vm.interpreter().registers[0], 4);
+    }
+
+    #[test]
+    fn run_until_reports_a_program_that_exits_first() {
+        // ld v0 1, exit
+        let mut vm = vm_of_kind(RomKind::SCHIP, &[0x60, 0x01, 0x00, 0xFD]);
+        assert_eq!(vm.run_until(|interp| interp.registers[0] == 5, 100), Ok((2, RunUntilStop::Stopped)));
+    }
+
+    #[test]
+    fn run_until_passes_on_the_error_that_stopped_the_program() {
+        // ld v0 1, exit (which CHIP8 can't decode)
+        let mut vm = vm(&[0x60, 0x01, 0x00, 0xFD]);
+        let Err(VMError::Interpreter(InterpreterError::Decode { address, .. })) =
+            vm.run_until(|interp| interp.registers[0] == 5, 100)
+        else {
+            panic!("Undecodable exit should stop with a decode error");
+        };
+        assert_eq!(address, 0x202);
+    }
+}
@@ -1,8 +1,77 @@
-use crate::ch8::rom::RomKind;
+use crate::{
+    ch8::{
+        input::KeypadLayout,
+        interp::{MachineCallMode, UnknownOpcodeMode},
+        mem::{is_valid_memory_size, MemoryFill},
+        rom::{QuirkOverride, RomKind, RomQuirks},
+        trace::TraceFilter,
+    },
+    run::{
+        parse_key_code,
+        preset::{
+            CGA0_COLOR_PRESET, CGA1_COLOR_PRESET, GRAYSCALE_COLOR_PRESET, HOTDOG_COLOR_PRESET, LCD_COLOR_PRESET,
+            OCTO_COLOR_PRESET,
+        },
+        DEFAULT_POLL_TIMEOUT_MS, DEFAULT_TURBO_MULTIPLIER, MAX_POLL_TIMEOUT_MS, MIN_POLL_TIMEOUT_MS,
+    },
+};
 
 use clap::{Parser, Subcommand, ValueEnum};
+use crossterm::event::KeyCode;
 use log::{Level, LevelFilter};
 use std::path::PathBuf;
+use tui::style::Color;
+
+fn parse_memory_size(value: &str) -> Result<usize, String> {
+    let size = match value.strip_suffix(&['K', 'k'][..]) {
+        Some(kilobytes) => kilobytes.parse::<usize>().map(|kilobytes| kilobytes.saturating_mul(1024)),
+        None => value.parse::<usize>(),
+    }
+    .map_err(|e| e.to_string())?;
+
+    if is_valid_memory_size(size) {
+        Ok(size)
+    } else {
+        Err(String::from("memory size must be a power of two from 4K to 64K"))
+    }
+}
+
+// random without a seed picks one so the run can still be repeated with the seed that gets logged
+fn parse_memory_fill(value: &str) -> Result<MemoryFill, String> {
+    match value.split_once(':') {
+        None if value.eq_ignore_ascii_case("zero") => Ok(MemoryFill::Zero),
+        None if value.eq_ignore_ascii_case("ff") => Ok(MemoryFill::Ones),
+        None if value.eq_ignore_ascii_case("random") => Ok(MemoryFill::Random(rand::random())),
+        Some((random, seed)) if random.eq_ignore_ascii_case("random") => seed
+            .parse::<u64>()
+            .map(MemoryFill::Random)
+            .map_err(|e| e.to_string()),
+        _ => Err(String::from("fill must be zero, ff, random, or random:SEED")),
+    }
+}
+
+// NAME turns the quirk on, NAME=on or NAME=off sets it either way
+fn parse_quirk_override(value: &str) -> Result<QuirkOverride, String> {
+    let (name, value) = value.split_once('=').unwrap_or((value, "on"));
+    let value = match value.to_ascii_lowercase().as_str() {
+        "on" | "true" | "1" => true,
+        "off" | "false" | "0" => false,
+        _ => return Err(format!("quirk must be set to on or off, not \"{}\"", value)),
+    };
+
+    match RomQuirks::find_name(name) {
+        Some(name) => Ok(QuirkOverride { name, value }),
+        None => Err(format!(
+            "unknown quirk \"{}\", expected one of: {}",
+            name,
+            RomKind::CHIP8
+                .default_rom_quirks()
+                .named()
+                .map(|(name, _)| name)
+                .join(", ")
+        )),
+    }
+}
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -15,16 +84,20 @@ pub struct Cli {
 
 #[derive(ValueEnum, Clone, Copy)]
 pub enum KindOption {
-    #[clap(aliases = &["c8"])]
+    /// CHIP-8 with modern quirks (aliases: c8, chip-8)
+    #[clap(aliases = &["c8", "chip-8"])]
     CHIP8,
 
-    #[clap(aliases = &["sc"])]
+    /// SUPER-CHIP (aliases: sc, superchip, super-chip, chip48, chip-48)
+    #[clap(aliases = &["sc", "superchip", "super-chip", "chip48", "chip-48"])]
     SCHIP,
 
-    #[clap(aliases = &["og"])]
+    /// CHIP-8 with the quirks of the COSMAC VIP (aliases: og, vip, cosmacvip, cosmac-vip)
+    #[clap(aliases = &["og", "vip", "cosmacvip", "cosmac-vip"])]
     CLASSIC,
 
-    #[clap(aliases = &["xo"])]
+    /// XO-CHIP (aliases: xo, xo-chip)
+    #[clap(aliases = &["xo", "xo-chip"])]
     XOCHIP,
 }
 
@@ -39,6 +112,103 @@ impl KindOption {
     }
 }
 
+#[derive(ValueEnum, Clone, Copy)]
+pub enum UnknownOpcodeOption {
+    /// Stop with an error
+    Strict,
+
+    /// Skip the opcode as a 2 byte no-op
+    Nop,
+
+    /// Stop as if the program exited
+    Halt,
+}
+
+impl UnknownOpcodeOption {
+    pub fn to_mode(self) -> UnknownOpcodeMode {
+        match self {
+            UnknownOpcodeOption::Strict => UnknownOpcodeMode::Strict,
+            UnknownOpcodeOption::Nop => UnknownOpcodeMode::Nop,
+            UnknownOpcodeOption::Halt => UnknownOpcodeMode::Halt,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum MachineCallOption {
+    /// Treat it like any other opcode that cannot be decoded
+    Unknown,
+
+    /// Skip the call as a 2 byte no-op
+    Nop,
+
+    /// Skip the call and log the address it called
+    Log,
+}
+
+impl MachineCallOption {
+    pub fn to_mode(self) -> MachineCallMode {
+        match self {
+            MachineCallOption::Unknown => MachineCallMode::Unknown,
+            MachineCallOption::Nop => MachineCallMode::Nop,
+            MachineCallOption::Log => MachineCallMode::Log,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum KeypadOption {
+    /// 1234/QWER/ASDF/ZXCV laid out like the keypad
+    Qwerty,
+
+    /// Each key typed by its hex digit (0-9, A-F)
+    Vip,
+}
+
+impl KeypadOption {
+    pub fn to_layout(self) -> KeypadLayout {
+        match self {
+            KeypadOption::Qwerty => KeypadLayout::Qwerty,
+            KeypadOption::Vip => KeypadLayout::Vip,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum PaletteOption {
+    /// Black and white with grays for the extra XO-CHIP colors
+    Grayscale,
+
+    /// Colors of the Octo IDE
+    Octo,
+
+    /// Greens of an old LCD
+    Lcd,
+
+    /// Hot dog stand reds and yellows
+    HotDog,
+
+    /// CGA palette 0
+    Cga0,
+
+    /// CGA palette 1
+    Cga1,
+}
+
+impl PaletteOption {
+    pub fn to_colors(self) -> [Color; 16] {
+        let (_, colors) = match self {
+            PaletteOption::Grayscale => GRAYSCALE_COLOR_PRESET,
+            PaletteOption::Octo => OCTO_COLOR_PRESET,
+            PaletteOption::Lcd => LCD_COLOR_PRESET,
+            PaletteOption::HotDog => HOTDOG_COLOR_PRESET,
+            PaletteOption::Cga0 => CGA0_COLOR_PRESET,
+            PaletteOption::Cga1 => CGA1_COLOR_PRESET,
+        };
+        colors
+    }
+}
+
 #[derive(ValueEnum, Clone, Copy)]
 pub enum LogLevelOption {
     Trace,
@@ -82,9 +252,13 @@ pub enum CliCommand {
         #[arg(short, long, value_enum, value_name = "LEVEL")]
         log: Option<LogLevelOption>,
 
-        /// Sets the ROM kind
-        #[arg(long, value_enum)]
+        /// Sets the ROM kind (case insensitive)
+        #[arg(long, value_enum, ignore_case = true)]
         kind: Option<KindOption>,
+
+        /// Discards this many bytes from the start of the file before loading the rest, for dumps with a header
+        #[arg(long, value_name = "BYTES", default_value_t = 0)]
+        skip: usize,
     },
 
     /// Disassembles a CHIP-8 ROM
@@ -97,22 +271,116 @@ pub enum CliCommand {
         #[arg(short, long, value_enum, value_name = "LEVEL")]
         log: Option<LogLevelOption>,
 
-        /// Sets the ROM kind
-        #[arg(long, value_enum)]
+        /// Sets the ROM kind (case insensitive)
+        #[arg(long, value_enum, ignore_case = true)]
         kind: Option<KindOption>,
+
+        /// Discards this many bytes from the start of the file before loading the rest, for dumps with a header
+        #[arg(long, value_name = "BYTES", default_value_t = 0)]
+        skip: usize,
     },
 
-    /// Loads a CHIP-8 ROM and runs it
-    #[clap(group = clap::ArgGroup::new("cycles").multiple(false))]
-    Run {
+    /// Reports how much of a CHIP-8 ROM decodes as instructions and where it does not
+    Coverage {
         /// Path of the ROM to load
         #[arg(value_name = "ROM")]
         path: PathBuf,
 
+        /// Enable logging
+        #[arg(short, long, value_enum, value_name = "LEVEL")]
+        log: Option<LogLevelOption>,
+
+        /// Sets the ROM kind (case insensitive)
+        #[arg(long, value_enum, ignore_case = true)]
+        kind: Option<KindOption>,
+
+        /// Discards this many bytes from the start of the file before loading the rest, for dumps with a header
+        #[arg(long, value_name = "BYTES", default_value_t = 0)]
+        skip: usize,
+    },
+
+    /// Runs every ROM in a directory headless and compares its final display against the expected hashes
+    Test {
+        /// Directory of ROMs (.ch8, .sc8, .xo8) holding the expected hashes file
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Sets how many frames each ROM runs for unless it exits or jumps to itself first
+        #[arg(long, value_name = "FRAMES", default_value_t = 600)]
+        frames: u32,
+
+        /// Records the final display hashes as the expected hashes instead of comparing against them
+        #[arg(long)]
+        bless: bool,
+
+        /// Enable logging
+        #[arg(short, long, value_enum, value_name = "LEVEL")]
+        log: Option<LogLevelOption>,
+    },
+
+    /// Loads a CHIP-8 ROM and runs it
+    #[clap(group = clap::ArgGroup::new("cycles").multiple(false))]
+    Run {
+        /// Path of the ROM to load (optional with --session or --blank)
+        #[arg(value_name = "ROM", required_unless_present_any = ["session", "blank"])]
+        path: Option<PathBuf>,
+
+        /// Starts the debugger with empty program memory instead of a ROM so instructions can be entered with set mem
+        #[arg(long, conflicts_with_all = ["path", "session", "skip"])]
+        blank: bool,
+
         /// Runs the ROM in debug mode
         #[arg(short, long)]
         debug: bool,
 
+        /// Starts the debugger paused before the first instruction (implies --debug)
+        #[arg(long, visible_alias = "break-at-start")]
+        pause: bool,
+
+        /// Restores a debugger session from a file, which save-session writes to (implies --debug)
+        #[arg(long, value_name = "FILE")]
+        session: Option<PathBuf>,
+
+        /// Runs the debugger commands in a file at startup, one per line with # comments (implies --debug)
+        #[arg(long, value_name = "FILE")]
+        commands: Option<PathBuf>,
+
+        /// Runs a debugger command at startup after any --commands file, can be repeated (implies --debug)
+        #[arg(long, value_name = "COMMAND")]
+        ex: Vec<String>,
+
+        /// Sets the key that pauses the program and drops into the debugger (e.g. f2 or `), defaults to esc
+        #[arg(long, value_name = "KEY", value_parser = parse_key_code)]
+        debug_key: Option<KeyCode>,
+
+        /// Logs a warning when the pc enters the font or memory only used as data (requires --debug)
+        #[arg(long)]
+        warn_data_exec: bool,
+
+        /// Sets the most instructions a single undo or redo can travel (requires --debug)
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        undo_limit: Option<u64>,
+
+        /// Skips recording history while debugging so stepping is cheaper, which disables undo and redo (requires --debug)
+        #[arg(long)]
+        no_history: bool,
+
+        /// Logs a warning when adding a constant to a register (7XNN) wraps past 255
+        #[arg(long)]
+        warn_add_overflow: bool,
+
+        /// Logs a warning the first time an instruction reads a register nothing has written since reset
+        #[arg(long)]
+        warn_uninit_reads: bool,
+
+        /// Logs a warning the first time calls nest deeper than DEPTH, defaults to the COSMAC VIP's 12
+        #[arg(long, value_name = "DEPTH", num_args = 0..=1, default_missing_value = "12")]
+        warn_stack_depth: Option<usize>,
+
+        /// Logs every pixel each draw erases as (plane, x, y) at the trace level, for checking sprite rendering
+        #[arg(long)]
+        trace_erased_pixels: bool,
+
         /// Sets the cycles per frame
         #[arg(long, group = "cycles")]
         cpf: Option<u32>,
@@ -125,8 +393,127 @@ pub enum CliCommand {
         #[arg(short, long, value_enum, value_name = "LEVEL")]
         log: Option<LogLevelOption>,
 
-        /// Sets the ROM kind
-        #[arg(long, value_enum)]
+        /// Sets the ROM kind (case insensitive)
+        #[arg(long, value_enum, ignore_case = true)]
         kind: Option<KindOption>,
+
+        /// Discards this many bytes from the start of the file before loading the rest, for dumps with a header
+        #[arg(long, value_name = "BYTES", default_value_t = 0)]
+        skip: usize,
+
+        /// Sets a quirk over the ROM kind default, repeatable (e.g. --quirk add_to_index_overflow_sets_flag_register=on)
+        #[arg(long, value_name = "NAME[=on|off]", value_parser = parse_quirk_override)]
+        quirk: Vec<QuirkOverride>,
+
+        /// Sets the memory size in bytes or kilobytes (e.g. 8K), defaults to 4K or 64K for XO-CHIP
+        #[arg(long, value_name = "SIZE", value_parser = parse_memory_size)]
+        memory: Option<usize>,
+
+        /// Fills memory and registers before loading with zero, ff, random, or random:SEED to mimic real hardware
+        #[arg(long, value_name = "PATTERN", default_value = "zero", value_parser = parse_memory_fill)]
+        fill: MemoryFill,
+
+        /// Sets what happens when an opcode cannot be decoded
+        #[arg(long, value_enum, value_name = "MODE", default_value_t = UnknownOpcodeOption::Strict)]
+        unknown_opcodes: UnknownOpcodeOption,
+
+        /// Sets what happens at a 0NNN machine code call, whose routine is never emulated
+        #[arg(long, value_enum, value_name = "MODE", default_value_t = MachineCallOption::Unknown)]
+        machine_calls: MachineCallOption,
+
+        /// Sets the colors pixels are drawn with, defaults to grayscale
+        #[arg(long, value_enum, value_name = "PALETTE")]
+        palette: Option<PaletteOption>,
+
+        /// Dims every other row of the display for a CRT scanline look
+        #[arg(long)]
+        scanlines: bool,
+
+        /// Fades pixels out over a couple of frames after they turn off to soften sprite flicker
+        #[arg(long)]
+        phosphor: bool,
+
+        /// Shows the pc and the instruction there under the display (toggle with Tab while running)
+        #[arg(long)]
+        show_opcode: bool,
+
+        /// Shows a line under the display with the pc, index, timers, stall state, and held keypad keys
+        #[arg(long)]
+        status_bar: bool,
+
+        /// Publishes display frames over TCP at the given address (e.g. 127.0.0.1:8008)
+        #[arg(long, value_name = "ADDRESS")]
+        stream: Option<String>,
+
+        /// Writes every executed instruction to a file as newline-delimited JSON
+        #[arg(long, value_name = "FILE")]
+        trace_json: Option<PathBuf>,
+
+        /// Compares every executed instruction against a file written by --trace-json and stops at the first difference
+        #[arg(long, value_name = "FILE", conflicts_with = "trace_json")]
+        verify_trace: Option<PathBuf>,
+
+        /// Limits --trace-json or --verify-trace to a comma separated list of mnemonics or instruction names (e.g. drw,call)
+        #[arg(long, value_name = "NAMES", value_parser = TraceFilter::parse)]
+        trace_filter: Option<TraceFilter>,
+
+        /// Replays an input script written by the debugger's dump inputs command before the program runs
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["trace_json", "verify_trace", "blank", "session"])]
+        replay: Option<PathBuf>,
+
+        /// Runs headless as fast as possible for the given number of seconds and reports the instructions per second
+        #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..=3600), conflicts_with_all = ["debug", "pause", "blank", "session", "commands", "ex", "stream"])]
+        bench: Option<u64>,
+
+        /// Sets how long in milliseconds the input loop waits for terminal events (lower is snappier, higher wakes less)
+        #[arg(long, value_name = "MS", default_value_t = DEFAULT_POLL_TIMEOUT_MS, value_parser = clap::value_parser!(u64).range(MIN_POLL_TIMEOUT_MS..=MAX_POLL_TIMEOUT_MS))]
+        poll_ms: u64,
+
+        /// Sets which keys stand in for the CHIP-8 keypad
+        #[arg(long, value_enum, value_name = "LAYOUT", default_value_t = KeypadOption::Qwerty)]
+        keypad: KeypadOption,
+
+        /// Sets the key that runs the program faster while held, defaults to space
+        #[arg(long, value_name = "KEY", value_parser = parse_key_code)]
+        turbo_key: Option<KeyCode>,
+
+        /// Sets how many times faster the program runs while the turbo key is held
+        #[arg(long, value_name = "MULTIPLIER", default_value_t = DEFAULT_TURBO_MULTIPLIER, value_parser = clap::value_parser!(u32).range(1..=100))]
+        turbo: u32,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_kind(kind: &str) -> Result<RomKind, clap::Error> {
+        match Cli::try_parse_from(["c8", "check", "rom.ch8", "--kind", kind])?.command {
+            CliCommand::Check { kind, .. } => Ok(kind.expect("Kind should be set").to_kind()),
+            _ => unreachable!("Parsed a command other than check"),
+        }
+    }
+
+    #[test]
+    fn every_kind_alias_resolves_case_insensitively() {
+        let aliases = [
+            (RomKind::CHIP8, &["chip8", "c8", "chip-8"][..]),
+            (RomKind::SCHIP, &["schip", "sc", "superchip", "super-chip", "chip48", "chip-48"][..]),
+            (RomKind::CLASSIC, &["classic", "og", "vip", "cosmacvip", "cosmac-vip"][..]),
+            (RomKind::XOCHIP, &["xochip", "xo", "xo-chip"][..]),
+        ];
+
+        for (kind, names) in aliases {
+            for name in names {
+                for name in [name.to_string(), name.to_ascii_uppercase()] {
+                    assert!(parse_kind(&name).ok() == Some(kind), "{} should resolve to {}", name, kind);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_kind_is_rejected() {
+        assert!(parse_kind("chip9").is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::ch8::{
+    audio::silent_audio_controller,
+    instruct::Instruction,
+    rom::Rom,
+    vm::VM,
+};
+
+use crossterm::style::Stylize;
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fmt,
+    fs::{read_dir, read_to_string, write},
+    io,
+    path::{Path, PathBuf},
+};
+
+// Every rom in a directory is run headless with no input and the final display is compared against a hash
+// recorded in the expected hashes file in the same directory, one rom per line:
+//
+//   # c8 expected display hashes
+//   ibm_logo.ch8 9f5cd1e2b1a0c3d4
+//
+// Blessing runs the roms the same way but writes the hashes it sees as the new expectations. Roms that read keys
+// or random numbers only pass if their final display does not depend on them
+
+pub const EXPECTED_HASHES_FILE_NAME: &str = "expected.c8hash";
+const EXPECTED_HASHES_HEADER: &str = "# c8 expected display hashes";
+
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+
+pub enum ConformanceOutcome {
+    Pass,
+    Fail(String),
+    Blessed,
+}
+
+pub struct ConformanceReport {
+    pub dir: PathBuf,
+    pub results: Vec<(String, ConformanceOutcome)>,
+}
+
+impl ConformanceReport {
+    pub fn failures(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, ConformanceOutcome::Fail(_)))
+            .count()
+    }
+}
+
+pub fn run_conformance_dir(dir: &Path, frames: u32, bless: bool) -> io::Result<ConformanceReport> {
+    let expected_path = dir.join(EXPECTED_HASHES_FILE_NAME);
+    let expected = if bless {
+        BTreeMap::new()
+    } else {
+        read_expected_hashes(&expected_path)?
+    };
+
+    let mut rom_paths = Vec::new();
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        let is_rom = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map_or(false, |extension| ROM_EXTENSIONS.contains(&extension));
+        if is_rom && path.is_file() {
+            rom_paths.push(path);
+        }
+    }
+    rom_paths.sort();
+
+    let mut hashes = BTreeMap::new();
+    let mut results = Vec::with_capacity(rom_paths.len());
+
+    for path in rom_paths {
+        let name = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let hash = match Rom::read(&path, None, &[]) {
+            Ok(rom) => run_headless(rom, frames),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let outcome = match (hash, expected.get(&name)) {
+            (Err(e), _) => ConformanceOutcome::Fail(e),
+            (Ok(hash), _) if bless => {
+                hashes.insert(name.clone(), hash);
+                ConformanceOutcome::Blessed
+            }
+            (Ok(hash), Some(&expected_hash)) if hash == expected_hash => ConformanceOutcome::Pass,
+            (Ok(hash), Some(&expected_hash)) => ConformanceOutcome::Fail(format!(
+                "display hash {:016x} does not match expected {:016x}",
+                hash, expected_hash
+            )),
+            (Ok(hash), None) => ConformanceOutcome::Fail(format!(
+                "no expected hash (display hash is {:016x}), run with --bless to record it",
+                hash
+            )),
+        };
+
+        results.push((name, outcome));
+    }
+
+    if bless {
+        write_expected_hashes(&expected_path, &hashes)?;
+    }
+
+    Ok(ConformanceReport {
+        dir: dir.to_path_buf(),
+        results,
+    })
+}
+
+// runs until the frames are up, the rom exits, or it settles in a jump to itself which is how test roms end
+fn run_headless(rom: Rom, frames: u32) -> Result<u64, String> {
+    let cycles_per_frame = rom.config.kind.default_cycles_per_frame();
+    let memory_size = rom.config.kind.default_memory_size();
+
+    let mut vm = VM::new(rom, memory_size, cycles_per_frame, silent_audio_controller());
+    vm.pause_audio();
+
+    let (_, stop) = vm.run_until(
+        |interpreter| interpreter.instruction() == Some(Instruction::Jump(interpreter.pc)),
+        frames as u64 * cycles_per_frame as u64,
+    )
+    .map_err(|e| e.to_string())?;
+    log::debug!("{} stopped with {:?}", vm.interpreter().rom.name, stop);
+
+    Ok(vm.interpreter().display.pixel_hash())
+}
+
+fn read_expected_hashes(path: &Path) -> io::Result<BTreeMap<String, u64>> {
+    let contents = match read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut hashes = BTreeMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parsed = line
+            .rsplit_once(' ')
+            .and_then(|(name, hash)| Some((name, u64::from_str_radix(hash, 16).ok()?)));
+        match parsed {
+            Some((name, hash)) => {
+                hashes.insert(name.to_string(), hash);
+            }
+            None => log::warn!(
+                "Skipping unreadable line {} of \"{}\": {}",
+                number + 1,
+                path.display(),
+                line
+            ),
+        }
+    }
+
+    Ok(hashes)
+}
+
+fn write_expected_hashes(path: &Path, hashes: &BTreeMap<String, u64>) -> io::Result<()> {
+    let mut contents = String::from(EXPECTED_HASHES_HEADER);
+    contents.push('\n');
+    for (name, hash) in hashes.iter() {
+        contents.push_str(&format!("{} {:016x}\n", name, hash));
+    }
+    write(path, contents)
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} \"{}\"",
+            format!("Testing").green().bold(),
+            self.dir.display()
+        )?;
+        write!(f, "\n    {}", format!("|").blue().bold())?;
+
+        for (name, outcome) in self.results.iter() {
+            match outcome {
+                ConformanceOutcome::Pass => {
+                    write!(f, "\n    {} {} {}", format!("=").blue().bold(), "PASS".green().bold(), name)?
+                }
+                ConformanceOutcome::Blessed => {
+                    write!(f, "\n    {} {} {}", format!("=").blue().bold(), "BLESS".cyan().bold(), name)?
+                }
+                ConformanceOutcome::Fail(reason) => write!(
+                    f,
+                    "\n    {} {} {}: {}",
+                    format!("=").blue().bold(),
+                    "FAIL".red().bold(),
+                    name,
+                    reason
+                )?,
+            }
+        }
+
+        let failures = self.failures();
+        write!(f, "\n    {}", format!("|").blue().bold())?;
+        write!(
+            f,
+            "\n    {} {} passed, {} failed, {} total",
+            format!("=").blue().bold(),
+            self.results.len() - failures,
+            failures,
+            self.results.len()
+        )
+    }
+}
@@ -1,4 +1,4 @@
-use crate::ch8::input::Key;
+use crate::ch8::{input::Key, instruct::Instruction, trace::TraceFilter};
 
 use clap::{Parser, Subcommand, ValueEnum};
 
@@ -75,10 +75,21 @@ pub enum ClearCommand {
         breakpoint: u16,
     },
 
+    /// Clear an instruction breakpoint
+    #[clap(visible_aliases = &["bo"])]
+    BreakOp {
+        #[arg(value_name = "INSTRUCTION")]
+        pattern: OpcodePattern,
+    },
+
     /// Clear a watchpoint pointer, register, or address
     #[clap(visible_aliases = &["w"])]
     Watch { watchpoint: WatchOption },
 
+    /// Clear the display (can be undone)
+    #[clap(visible_aliases = &["d", "disp", "screen"])]
+    Display,
+
     /// Clear all subcommand
     All {
         #[command(subcommand)]
@@ -93,6 +104,10 @@ pub enum WatchBreakOption {
 
     #[clap(visible_aliases = &["w"])]
     Watch,
+
+    /// Settings in effect: kind, speed, timers, quirks, audio, and diagnostics
+    #[clap(visible_aliases = &["c", "cfg"])]
+    Config,
 }
 
 #[derive(Subcommand, Clone)]
@@ -108,6 +123,105 @@ pub enum ShowHideOption {
         #[clap(long, short)]
         verbose: bool,
     },
+
+    /// Memory at the index register
+    #[clap(visible_aliases = &["i", "idx"])]
+    Index,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum FreezeOption {
+    /// Delay and sound timers
+    #[clap(visible_aliases = &["t"])]
+    Timers,
+
+    /// Instruction execution
+    #[clap(visible_aliases = &["c"])]
+    Cpu,
+}
+
+#[derive(Subcommand, Clone, Copy)]
+pub enum LogCommand {
+    /// Show the log panel
+    On,
+
+    /// Hide the log panel
+    Off,
+
+    /// Set the share of its column the log panel takes up (default = the standard layout)
+    #[clap(visible_aliases = &["h", "size"])]
+    Height {
+        #[arg(value_name = "PERCENT", value_parser = clap::value_parser!(u16).range(10..=90))]
+        percent: Option<u16>,
+    },
+}
+
+#[derive(Subcommand, Clone, Copy)]
+pub enum BreakFileCommand {
+    /// Save breakpoints and watchpoints to a file next to the ROM (<ROM>.c8bp)
+    Save,
+
+    /// Load breakpoints and watchpoints from the file next to the ROM
+    Load,
+}
+
+#[derive(Subcommand, Clone, Copy)]
+pub enum TimingsCommand {
+    /// Start measuring how long each kind of instruction takes to execute
+    On,
+
+    /// Stop measuring and discard the measurements
+    Off,
+
+    /// Discard the measurements so far and keep measuring
+    Clear,
+
+    /// Show how far the frame loop has drifted from 60hz and how long it last slept, which is always measured
+    #[clap(visible_aliases = &["f"])]
+    Frames,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum SetOption {
+    /// Write bytes into memory starting at an address (e.g. set mem 0x200 6001 or set mem 0x200 60 01)
+    #[clap(visible_aliases = &["m", "mem"])]
+    Memory {
+        #[arg(value_name = "ADDRESS", value_parser = parse_addr)]
+        address: u16,
+
+        #[arg(value_name = "HEX BYTES", required = true)]
+        bytes: Vec<HexBytes>,
+    },
+
+    /// Write a sprite drawn as rows of x (or #) and . into memory starting at an address (e.g. set spr 0x300 .xx. x..x .xx.)
+    #[clap(visible_aliases = &["s", "spr"])]
+    Sprite {
+        #[arg(value_name = "ADDRESS", value_parser = parse_addr)]
+        address: u16,
+
+        #[arg(value_name = "ROWS", required = true)]
+        rows: Vec<String>,
+    },
+}
+
+#[derive(Clone)]
+pub struct HexBytes(pub Vec<u8>);
+
+impl FromStr for HexBytes {
+    type Err = &'static str;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let digits = value.trim_start_matches("0x");
+        if digits.is_empty() || digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("Bytes must be an even number of hex digits (e.g. 6001)");
+        }
+
+        Ok(HexBytes(
+            (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+                .collect(),
+        ))
+    }
 }
 
 #[derive(Subcommand, Clone)]
@@ -118,6 +232,13 @@ pub enum DumpOption {
         #[arg(value_name = "FILE PATH")]
         path: PathBuf,
     },
+
+    /// Write the keypad input of the recorded history as a text script
+    #[clap(visible_aliases = &["in"])]
+    Inputs {
+        #[arg(value_name = "FILE PATH")]
+        path: PathBuf,
+    },
 }
 
 #[derive(Clone)]
@@ -176,6 +297,63 @@ impl FromStr for WatchOption {
     }
 }
 
+// Matches instructions by mnemonic or instruction name (e.g. "drw" or "draw")
+// or by an opcode pattern (e.g. "8XY4") where x, y, n, and ? match any nibble
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum OpcodePattern {
+    Name(String),
+    Opcode { mask: u16, value: u16, pattern: String },
+}
+
+impl OpcodePattern {
+    pub fn matches(&self, instruction: &Instruction, opcode: u16, mnemonic: &str) -> bool {
+        match self {
+            Self::Name(name) => {
+                mnemonic.eq_ignore_ascii_case(name)
+                    || format!("{:?}", instruction)
+                        .split('(')
+                        .next()
+                        .map_or(false, |variant| variant.eq_ignore_ascii_case(name))
+            }
+            Self::Opcode { mask, value, .. } => opcode & mask == *value,
+        }
+    }
+}
+
+impl FromStr for OpcodePattern {
+    type Err = &'static str;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let is_wildcard = |c: char| matches!(c.to_ascii_lowercase(), 'x' | 'y' | 'n' | '?');
+
+        if value.len() == 4 && value.chars().all(|c| c.is_ascii_hexdigit() || is_wildcard(c)) {
+            let (mask, opcode) = value.chars().fold((0, 0), |(mask, opcode), c| {
+                match c.to_digit(16).filter(|_| !is_wildcard(c)) {
+                    Some(nibble) => (mask << 4 | 0xF, opcode << 4 | nibble as u16),
+                    None => (mask << 4, opcode << 4),
+                }
+            });
+            Ok(Self::Opcode {
+                mask,
+                value: opcode,
+                pattern: value.to_ascii_uppercase(),
+            })
+        } else if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic()) {
+            Ok(Self::Name(value.to_ascii_lowercase()))
+        } else {
+            Err("Instruction must be a mnemonic (e.g. drw) or an opcode pattern (e.g. 8XY4)")
+        }
+    }
+}
+
+impl std::fmt::Display for OpcodePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{}", name),
+            Self::Opcode { pattern, .. } => write!(f, "{}", pattern),
+        }
+    }
+}
+
 #[derive(ValueEnum, Clone, Copy)]
 pub enum SemanticLocation {
     Start,
@@ -258,6 +436,13 @@ pub enum DebugCliCommand {
         amount: usize,
     },
 
+    /// Run until the display next changes or a frame's worth of instructions run, N (default = 1) times
+    #[clap(visible_aliases = &["fs"])]
+    Frame {
+        #[arg(value_name = "AMOUNT", default_value_t = 1)]
+        amount: usize,
+    },
+
     /// Set the instructions executed per second of the program
     #[clap(visible_aliases = &["hz", "ips", "rate", "freq", "frequency"])]
     Hertz {
@@ -298,6 +483,17 @@ pub enum DebugCliCommand {
         location: GotoOption,
     },
 
+    /// List subroutines called by the program (found statically, so dynamic targets are missed)
+    #[clap(visible_aliases = &["fn", "funcs"])]
+    Functions,
+
+    /// Go to the Nth subroutine listed by functions
+    #[clap(visible_aliases = &["gf"])]
+    GotoFunc {
+        #[arg(value_name = "N")]
+        number: usize,
+    },
+
     /// Follow a pointer in memory
     #[clap(visible_aliases = &["f"])]
     Follow { pointer: Pointer },
@@ -306,13 +502,28 @@ pub enum DebugCliCommand {
     #[clap(visible_aliases = &["uf"])]
     Unfollow,
 
-    /// Set a breakpoint at an address
+    /// Set a breakpoint at an address or save/load breakpoints kept alongside the ROM
     #[clap(visible_aliases = &["b"])]
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Break {
-        #[arg(value_name = "ADDRESS", value_parser = parse_addr)]
-        address: u16,
+        #[arg(value_name = "ADDRESS", value_parser = parse_addr, required = true)]
+        address: Option<u16>,
+
+        #[command(subcommand)]
+        file: Option<BreakFileCommand>,
+    },
+
+    /// Set a breakpoint on every instruction matching a mnemonic (e.g. drw) or opcode pattern (e.g. 8XY4)
+    #[clap(visible_aliases = &["bo"])]
+    BreakOp {
+        #[arg(value_name = "INSTRUCTION")]
+        pattern: OpcodePattern,
     },
 
+    /// Toggle breaking after any sprite draw that collides (sets vf)
+    #[clap(visible_aliases = &["bc"])]
+    BreakOnCollision,
+
     /// Watch a register, pointer, or address for change
     #[clap(visible_aliases = &["w"])]
     Watch { watchpoint: WatchOption },
@@ -350,10 +561,95 @@ pub enum DebugCliCommand {
         command: ClearCommand,
     },
 
+    /// Toggle read only history review where stepping stops at the present instead of executing
+    #[clap(visible_aliases = &["rv"])]
+    Review,
+
+    /// Toggle freezing the timers or the cpu independently of each other
+    #[clap(visible_aliases = &["fr"])]
+    Freeze {
+        #[command(subcommand)]
+        what: FreezeOption,
+    },
+
+    /// Execute log subcommand
+    #[clap(visible_aliases = &["lg"])]
+    Log {
+        #[command(subcommand)]
+        command: LogCommand,
+    },
+
+    /// Save breakpoints, watchpoints, and command history to a session file (default = the --session file)
+    #[clap(visible_aliases = &["save"])]
+    SaveSession {
+        #[arg(value_name = "FILE PATH")]
+        path: Option<PathBuf>,
+    },
+
+    /// Show the execution speed and how many sprite draws and screen clears have executed
+    Stats,
+
+    /// Limit the instruction trace to mnemonics or instruction names (e.g. drw,call) or show the current limit
+    #[clap(visible_aliases = &["tf"])]
+    TraceFilter {
+        #[arg(value_name = "NAMES", value_parser = TraceFilter::parse, conflicts_with = "clear")]
+        filter: Option<TraceFilter>,
+
+        /// Trace every instruction again
+        #[arg(short, long)]
+        clear: bool,
+    },
+
+    /// Show the min/avg/max execution time per instruction kind or toggle measuring it
+    #[clap(visible_aliases = &["tm"])]
+    Timings {
+        #[command(subcommand)]
+        command: Option<TimingsCommand>,
+    },
+
+    /// Replace the instruction at the pc with an opcode (e.g. 6005) or assembly (e.g. ld v0 5), undoable
+    #[clap(visible_aliases = &["e"])]
+    Edit {
+        #[arg(value_name = "INSTRUCTION", required = true)]
+        instruction: Vec<String>,
+    },
+
+    /// Move the pc to an address without executing anything, undoable
+    #[clap(visible_aliases = &["jmp"])]
+    Jump {
+        #[arg(value_name = "ADDRESS", value_parser = parse_addr)]
+        address: u16,
+    },
+
+    /// Explain what an opcode (e.g. 8124) does for the current ROM kind
+    #[clap(visible_aliases = &["op"])]
+    Opcode {
+        #[arg(value_name = "HEX OPCODE")]
+        opcode: HexBytes,
+    },
+
+    /// Execute set subcommand
+    Set {
+        #[command(subcommand)]
+        what: SetOption,
+    },
+
     /// Execute dump subcommand
     #[clap(visible_aliases = &["d"])]
     Dump {
         #[command(subcommand)]
         what: DumpOption,
     },
+
+    /// Save the program state under a name to return to with restore, kept in memory until c8 exits
+    #[clap(visible_aliases = &["cp"])]
+    Checkpoint { name: String },
+
+    /// Return the program to a named checkpoint, which clears the history
+    #[clap(visible_aliases = &["rst"])]
+    Restore { name: String },
+
+    /// List the named checkpoints
+    #[clap(visible_aliases = &["cps"])]
+    Checkpoints,
 }
@@ -1,8 +1,12 @@
+use super::asm_token_color;
+
 use crate::{
-    asm::{write_inst_dasm, ADDRESS_COMMENT_TOKEN, INSTRUCTION_MAX_LENGTH},
+    asm::{write_inst_tokens, ADDRESS_COMMENT_TOKEN, INSTRUCTION_MAX_LENGTH},
     ch8::{
+        instruct::Instruction,
         rom::RomConfig,
-        vm::{VMHistoryFragment, VM},
+        script::InputScript,
+        vm::{VMError, VMHistoryFragment, VM},
     },
 };
 
@@ -15,27 +19,87 @@ use tui::{
     widgets::{Block, Borders, Paragraph, Widget},
 };
 
-use std::{collections::VecDeque, fmt::Write};
+use std::{collections::VecDeque, fmt::Write, fs, io, path::Path};
+
+pub(super) const HISTORY_CAPACITY: usize = 1_000_000;
 
-const HISTORY_CAPACITY: usize = 1_000_000;
+// Exported inputs replay the recorded instructions from the start of the program (see InputScript). Instructions
+// entered by the debugger are not part of it since the program never ran them
 
 pub(super) struct History {
     pub fragments: VecDeque<VMHistoryFragment>,
     pub present_fragment: Option<VMHistoryFragment>,
     rom_config: RomConfig,
+    from_program_start: bool, // nothing was recorded before the first fragment or dropped since
     cursor: usize,
+    reviewing: bool,
+    recording: bool,
 }
 
 impl History {
-    pub(super) fn new(rom_config: RomConfig) -> Self {
+    pub(super) fn new(vm: &VM) -> Self {
         Self {
-            rom_config,
+            rom_config: vm.interpreter().rom.config,
+            from_program_start: vm.cycles() == 0,
             fragments: VecDeque::with_capacity(HISTORY_CAPACITY),
             present_fragment: None,
             cursor: 0,
+            reviewing: false,
+            recording: true,
         }
     }
 
+    pub(super) fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    // without recording nothing can be undone, but stepping skips capturing the state before every instruction
+    pub(super) fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+        if !recording {
+            self.from_program_start = false;
+            self.fragments = VecDeque::new();
+            self.present_fragment = None;
+            self.cursor = 0;
+        }
+    }
+
+    // while reviewing, history is read only so stepping replays recorded frames and stops at the present
+    pub(super) fn is_reviewing(&self) -> bool {
+        self.reviewing
+    }
+
+    pub(super) fn set_reviewing(&mut self, reviewing: bool) {
+        self.reviewing = reviewing;
+    }
+
+    // the inputs of the instructions that led to the current state, returning how many instructions they span
+    pub(super) fn write_input_script<P: AsRef<Path>>(&self, path: P, vm: &VM) -> io::Result<u64> {
+        if !self.from_program_start {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the recorded history does not reach back to the start of the program",
+            ));
+        }
+
+        let interpreter = vm.interpreter();
+        let mut script = InputScript::new(self.rom_config.kind, interpreter.rom.name.clone(), interpreter.rng_seed());
+
+        let mut cycle = 0;
+        for fragment in self.fragments.range(..self.cursor).filter(|fragment| !fragment.interpreter.injected) {
+            let (&down_keys, &pressed, &released) = fragment.keyboard.state();
+            script.push_cycles_per_frame(fragment.cycles_per_frame);
+            script.push_cycles(fragment.cycle.saturating_sub(cycle), fragment.interpreter.waiting);
+            script.push_instruction(down_keys, pressed, released);
+            cycle = fragment.cycle + 1;
+        }
+        script.push_cycles_per_frame(vm.cycles_per_frame());
+        script.push_cycles(vm.cycles().saturating_sub(cycle), interpreter.waiting);
+
+        fs::write(path, script.to_string())?;
+        Ok(script.instructions())
+    }
+
     pub(super) fn redo_amount(&self) -> usize {
         self.fragments.len().abs_diff(self.cursor)
     }
@@ -61,7 +125,116 @@ impl History {
         amt_rewinded
     }
 
-    pub(super) fn step(&mut self, vm: &mut VM, memory_access_flags: &mut [u8]) -> Result<bool, String> {
+    // execute an instruction on behalf of the debugger and record it so it can be undone like any other
+    pub(super) fn inject(&mut self, vm: &mut VM, instruction: Instruction, memory_access_flags: &[u8]) {
+        self.prepare_injection();
+
+        let state = vm.to_injected_history_fragment(instruction, memory_access_flags);
+        vm.inject(instruction);
+        self.push_injected(state);
+    }
+
+    // write memory on behalf of the debugger and record it so it can be undone like an injected instruction
+    pub(super) fn edit_memory(&mut self, vm: &mut VM, address: u16, bytes: &[u8], memory_access_flags: &[u8]) {
+        self.prepare_injection();
+
+        let state = vm.to_memory_edit_history_fragment(address, bytes, memory_access_flags);
+        vm.write_memory(address, bytes);
+        self.push_injected(state);
+    }
+
+    // move the pc on behalf of the debugger and record it so it can be undone like an injected instruction
+    pub(super) fn edit_pc(&mut self, vm: &mut VM, pc: u16, memory_access_flags: &[u8]) {
+        self.prepare_injection();
+
+        let state = vm.to_pc_edit_history_fragment(pc, memory_access_flags);
+        vm.set_pc(pc);
+        self.push_injected(state);
+    }
+
+    // anything the debugger changes happens at the cursor, so whatever was ahead of it can no longer be redone
+    fn prepare_injection(&mut self) {
+        let redo_amount = self.redo_amount();
+        if redo_amount > 0 {
+            log::info!(
+                "Clearing {} history checkpoints at or ahead of cursor",
+                redo_amount
+            );
+            self.clear_redo_history();
+        }
+
+        self.present_fragment = None;
+    }
+
+    fn push_injected(&mut self, state: VMHistoryFragment) {
+        if !self.recording {
+            return;
+        }
+
+        if self.fragments.len() == HISTORY_CAPACITY {
+            self.fragments.pop_front();
+            self.from_program_start = false;
+        }
+        self.fragments.push_back(state);
+        self.cursor = self.fragments.len();
+    }
+
+    // memory edits that undoing or redoing the given amount would revert or reapply as (address, length)
+    pub(super) fn memory_edits_within(&self, amount: usize, forwards: bool) -> Vec<(u16, u16)> {
+        let range = if forwards {
+            self.cursor..(self.cursor + amount).min(self.fragments.len())
+        } else {
+            self.cursor.saturating_sub(amount)..self.cursor
+        };
+
+        self.fragments
+            .range(range)
+            .filter_map(|fragment| fragment.interpreter.memory_edit())
+            .map(|(address, _, bytes)| (address, bytes.len() as u16))
+            .collect()
+    }
+
+    pub(super) fn step(&mut self, vm: &mut VM, memory_access_flags: &mut [u8]) -> Result<bool, VMError> {
+        // redoing an injected instruction executes it again without involving the program
+        if let Some(fragment) = self.fragments.get(self.cursor) {
+            if fragment.interpreter.injected {
+                if let Some(instruction) = fragment.interpreter.instruction {
+                    vm.inject(instruction);
+                    self.cursor += 1;
+                    return Ok(true);
+                }
+
+                if let Some((address, _, bytes)) = fragment.interpreter.memory_edit() {
+                    vm.write_memory(address, bytes);
+                    self.cursor += 1;
+                    return Ok(true);
+                }
+
+                if let Some(pc) = fragment.interpreter.pc_edit() {
+                    vm.set_pc(pc);
+                    self.cursor += 1;
+                    return Ok(true);
+                }
+            }
+        }
+
+        // a frozen cpu only advances the timers so there is no instruction to record
+        if vm.is_cpu_frozen() {
+            return vm.stepn(1);
+        }
+
+        // the memory access flags still need the executed instruction, but none of what it takes to undo it
+        if !self.recording {
+            let executed = vm.interpreter().to_executed_fragment();
+            let vm_result = vm.stepn(1);
+            if let Ok(true) = vm_result {
+                if !vm.interpreter().waiting {
+                    vm.update_memory_access_flags(&executed, memory_access_flags);
+                }
+            }
+            return vm_result;
+        }
+
         // time step is not state that is completely deterministic so must set it if possible
         if self.cursor < self.fragments.len() {
             vm.set_cycles_per_frame(self.fragments[self.cursor].cycles_per_frame);
@@ -96,12 +269,15 @@ impl History {
             }
         }
 
+        // a waiting instruction (e.g. WaitForKey) is only recorded on the step it completes, so its fragment holds
+        // the keyboard with the key that was delivered and whether it was already waiting. undoing onto it restores
+        // both, so the rewound state is waiting again and redoing consumes the same recorded key
         if redo_amount == 0 && !vm.interpreter().waiting && vm_result.is_ok() {
             if self.fragments.len() == HISTORY_CAPACITY {
                 self.fragments.pop_front();
+                self.from_program_start = false;
             }
             self.fragments.push_back(state);
-            
         }
 
         self.cursor = (self.cursor + 1).min(self.fragments.len());
@@ -167,7 +343,9 @@ impl<'a> Widget for HistoryWidget<'_> {
         let cursor = self.history.cursor;
 
         let history_block = Block::default()
-            .title(if cursor < history.len() {
+            .title(if !self.history.recording {
+                String::from(" History (off) ")
+            } else if cursor < history.len() {
                 format!(" History ({}/{}) ", cursor + 1, history.len())
             } else {
                 format!(" History ({}) ", history.len())
@@ -186,6 +364,15 @@ impl<'a> Widget for HistoryWidget<'_> {
 
         let mut lines = Vec::with_capacity(rbound - lbound);
 
+        let cursor_style = Style::default()
+            .bg(if self.active {
+                Color::White
+            } else {
+                Color::LightGreen
+            })
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD);
+
         if history_inner_area.area() > 0 {
             let mut asm = String::new();
             let mut asm_desc = String::new();
@@ -196,27 +383,61 @@ impl<'a> Widget for HistoryWidget<'_> {
             {
                 asm.clear();
                 asm_desc.clear();
-                write!(&mut asm, "  {:#05X}: ", interp_state.pc).ok();
+                if interp_state.injected {
+                    write!(&mut asm, "  {:>5}: ", "DEBUG").ok();
+                } else {
+                    write!(&mut asm, "  {:#05X}: ", interp_state.pc).ok();
+                }
+                let asm_start = asm.len();
                 asm_desc.push_str(ADDRESS_COMMENT_TOKEN);
                 asm_desc.push(' ');
+
+                // the cursor line is styled here rather than below so its operands keep their colors
+                let highlighted = lbound + lines.len() == cursor;
+                let style = if highlighted { cursor_style } else { Style::default() };
+
+                let mut spans = vec![Span::styled(asm.clone(), style)];
                 if let Some(inst) = interp_state.instruction.as_ref() {
-                    write_inst_dasm(inst, self.history.rom_config, &mut asm, &mut asm_desc).ok();
+                    write_inst_tokens(
+                        inst,
+                        self.history.rom_config,
+                        &mut |token, text| {
+                            let token_style = match asm_token_color(token, highlighted) {
+                                Some(color) => style.fg(color),
+                                None => style,
+                            };
+                            asm.push_str(text);
+                            spans.push(Span::styled(text.to_owned(), token_style));
+                            Ok(())
+                        },
+                        &mut asm_desc,
+                    )
+                    .ok();
+                } else if let Some((address, prior_memory, memory)) = interp_state.memory_edit() {
+                    write!(&mut asm, "edit {:#05X}", address).ok();
+                    write!(&mut asm_desc, "{} -> {}", hex_string(prior_memory), hex_string(memory)).ok();
+                } else if let Some(pc) = interp_state.pc_edit() {
+                    write!(&mut asm, "jump {:#05X}", pc).ok();
+                    write!(&mut asm_desc, "pc {:#05X} -> {:#05X}", interp_state.pc, pc).ok();
                 } else {
                     asm.push_str("BAD INSTRUCTION");
                 }
+                if interp_state.instruction.is_none() {
+                    spans.push(Span::styled(asm[asm_start..].to_owned(), style));
+                }
 
                 if asm_desc.len() > ADDRESS_COMMENT_TOKEN.len() + 1 {
-                    for _ in 0..(10 + INSTRUCTION_MAX_LENGTH).saturating_sub(asm.len()) {
-                        asm.push(' ');
-                    }
-
-                    lines.push(Spans::from(vec![
-                        Span::raw(asm.clone()),
-                        Span::styled(asm_desc.clone(), Style::default().fg(Color::Yellow)),
-                    ]));
-                } else {
-                    lines.push(Spans::from(asm.clone()));
+                    spans.push(Span::styled(
+                        " ".repeat((10 + INSTRUCTION_MAX_LENGTH).saturating_sub(asm.len())),
+                        style,
+                    ));
+                    spans.push(Span::styled(
+                        asm_desc.clone(),
+                        if highlighted { style } else { style.fg(Color::Yellow) },
+                    ));
                 }
+
+                lines.push(Spans::from(spans));
             }
 
             if rbound == history.len() + 1 {
@@ -235,15 +456,8 @@ impl<'a> Widget for HistoryWidget<'_> {
                 for _ in 0..span_len {
                     content.push(' ');
                 }
-                for span in line.0.iter_mut() {
-                    span.style = Style::default()
-                        .bg(if self.active {
-                            Color::White
-                        } else {
-                            Color::LightGreen
-                        })
-                        .fg(Color::Black)
-                        .add_modifier(Modifier::BOLD);
+                for span in line.0.iter_mut().filter(|span| span.style.bg.is_none()) {
+                    span.style = cursor_style;
                 }
             }
         }
@@ -251,3 +465,7 @@ impl<'a> Widget for HistoryWidget<'_> {
         Paragraph::new(lines).block(history_block).render(area, buf);
     }
 }
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
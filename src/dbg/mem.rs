@@ -1,4 +1,4 @@
-use super::Watchpoint;
+use super::{asm_token_color, Watchpoint};
 
 use crate::{
     asm::{Disassembler, InstructionTag, ADDRESS_COMMENT_TOKEN, INSTRUCTION_COLUMNS},
@@ -16,7 +16,7 @@ use tui::{
 
 use std::{collections::HashSet, fs::File, io::Write, path::Path};
 
-#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub(super) enum MemoryPointer {
     ProgramCounter,
     Index,
@@ -251,7 +251,13 @@ impl<'a> MemoryWidget<'_> {
             Color::Reset
         };
 
-        let mut spans: Vec<Span> = Vec::with_capacity(if show_comments { 2 } else { 1 });
+        let asm_tokens: &[_] = if show_addr_asm {
+            &address_formatter.asm_tokens
+        } else {
+            &[]
+        };
+
+        let mut spans: Vec<Span> = Vec::with_capacity(asm_tokens.len() + if show_comments { 3 } else { 2 });
 
         let mut content = String::with_capacity(content_len_padded);
         content.push(breakpoint_char);
@@ -265,17 +271,23 @@ impl<'a> MemoryWidget<'_> {
         content.push_str(&address_formatter.tag);
         if show_addr_asm {
             content.push(' ');
-            content.push_str(&address_formatter.asm);
-        }
-        if show_comments {
-            for _ in 0..content_len_padded.saturating_sub(content.len()) {
-                content.push(' ');
-            }
         }
+        let written_len = content.len() + asm_tokens.iter().map(|(_, range)| range.len()).sum::<usize>();
 
         spans.push(Span::styled(content, Style::default().fg(highlight)));
 
+        // each asm token gets a span so operands can be colored once the line is highlighted
+        let asm_spans_start = spans.len();
+        for (_, range) in asm_tokens.iter() {
+            spans.push(Span::styled(
+                address_formatter.asm[range.clone()].to_owned(),
+                Style::default().fg(highlight),
+            ));
+        }
+
         if show_comments {
+            spans.push(Span::raw(" ".repeat(content_len_padded.saturating_sub(written_len))));
+
             let mut comment = String::with_capacity(comment_len);
             comment.push_str(ADDRESS_COMMENT_TOKEN);
             comment.push(' ');
@@ -309,6 +321,17 @@ impl<'a> MemoryWidget<'_> {
             MemoryWidget::highlight_line(&mut spans, Color::White, highlight, addr_line_width);
         }
 
+        // breakpoint and watchpoint lines keep a single color so they stand out
+        if !is_breakpoint && !is_watchpoint {
+            let highlighted =
+                addr_is_selected || addr == self.interpreter.pc || addr == self.interpreter.index;
+            for (span, &(token, _)) in spans[asm_spans_start..].iter_mut().zip(asm_tokens.iter()) {
+                if let Some(color) = asm_token_color(token, highlighted) {
+                    span.style = span.style.fg(color);
+                }
+            }
+        }
+
         self.disassembler.address_formatter.set(address_formatter);
 
         Spans::from(spans)
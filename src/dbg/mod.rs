@@ -1,26 +1,30 @@
 pub mod cli;
 pub mod hist;
 pub mod mem;
+pub mod session;
 pub mod shell;
 
 use {
     cli::*,
-    hist::{History, HistoryWidget},
+    hist::{History, HistoryWidget, HISTORY_CAPACITY},
     mem::*,
+    session::{breakpoints_path, DebugSession},
     shell::*,
 };
 
 use crate::{
-    asm::Disassembler,
+    asm::{assemble_inst, assemble_sprite, write_inst_dasm, AsmToken, Disassembler},
     ch8::{
         disp::DisplayMode,
-        input::KEY_ORDERING,
-        instruct::Instruction,
-        interp::Interpreter,
+        input::{Key, KEY_ORDERING},
+        instruct::{Instruction, InstructionParameters},
+        interp::{Interpreter, VFLAG},
+        mem::MemoryRef,
         rom::RomKind,
         run::Runner,
-        vm::{VM, VM_FRAME_RATE},
+        vm::{VMCheckpoint, VM, VM_FRAME_RATE},
     },
+    run::{key_code_matches, key_code_name, DEFAULT_DEBUG_KEY, FRAME_STEP_KEY},
 };
 
 use ansi_to_tui::IntoText;
@@ -36,10 +40,11 @@ use tui::{
 
 use std::{
     cell::Cell,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
 };
 
-#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 enum Watchpoint {
     Pointer(MemoryPointer),
     Register(u8),
@@ -77,6 +82,34 @@ impl From<&Interpreter> for WatchState {
     }
 }
 
+// unique call targets in the loaded program in address order
+// only instructions aligned to the load address are decoded so misaligned calls and computed jumps are missed
+fn scan_functions(interp: &Interpreter) -> Vec<u16> {
+    let mut functions: Vec<u16> = interp
+        .decoded_instructions()
+        .filter_map(|(_, instruction)| match instruction {
+            Ok(Instruction::CallSubroutine(address)) => Some(address),
+            _ => None,
+        })
+        .collect();
+    functions.sort_unstable();
+    functions.dedup();
+    functions
+}
+
+// darker variants keep operands readable on the light background of highlighted lines
+fn asm_token_color(token: AsmToken, highlighted: bool) -> Option<Color> {
+    match (token, highlighted) {
+        (AsmToken::Register, false) => Some(Color::LightCyan),
+        (AsmToken::Register, true) => Some(Color::Blue),
+        (AsmToken::Immediate, false) => Some(Color::LightMagenta),
+        (AsmToken::Immediate, true) => Some(Color::Magenta),
+        (AsmToken::Address, false) => Some(Color::LightBlue),
+        (AsmToken::Address, true) => Some(Color::DarkGray),
+        _ => None,
+    }
+}
+
 impl WatchState {
     fn update(
         &mut self,
@@ -145,15 +178,27 @@ impl WatchState {
 enum DebugEvent {
     WatchpointTrigger(Watchpoint, u16, u16),
     BreakpointReached(u16),
+    OpBreakpointReached(OpcodePattern, u16),
+    CollisionReached { pc: u16, x: u8, y: u8, index: u16 },
+}
+
+// a frame step in progress, which runs one frame each time the runner steps so it can be interrupted like continue
+struct FrameStep {
+    frames: usize,
+    frames_stepped: usize,
+    amt_stepped: usize,
 }
 
 pub struct Debugger {
     active: bool,
+    frame_step: Option<FrameStep>,
 
     history: History,
     history_active: bool,
 
     breakpoints: HashSet<u16>,
+    op_breakpoints: Vec<OpcodePattern>,
+    break_on_collision: bool,
     watchpoints: HashSet<Watchpoint>,
     watch_state: WatchState,
     event_queue: Vec<DebugEvent>,
@@ -166,7 +211,10 @@ pub struct Debugger {
     memory_visible: bool,
     memory_widget_state: Cell<MemoryWidgetState>,
 
+    index_memory_visible: bool,
+
     keyboard_shows_qwerty: bool,
+    held_keys_applied: u16,
 
     runner_target_execution_frequency: u32,
 
@@ -177,22 +225,47 @@ pub struct Debugger {
     vm_visible: bool,
     vm_exception: Option<String>,
     vm_executing: bool,
+
+    warn_data_execution: bool,
+    executing_data: bool,
+
+    checkpoints: BTreeMap<String, VMCheckpoint>,
+
+    undo_limit: usize, // most instructions a single undo or redo travels so a typo cannot rewind everything
+
+    logging: bool,
+    logger_visible: bool,
+    logger_height_percent: Option<u16>,
+
+    rom_path: Option<PathBuf>,
+    session_path: Option<PathBuf>,
+
+    activation_key: KeyCode,
+
+    // set by anything that changes what the debugger shows so the renderer repaints without a display change
+    dirty: bool,
 }
 
 impl Debugger {
     pub fn new(vm: &VM, initial_target_execution_frequency: u32) -> Self {
         let mut dbg = Debugger {
             active: false,
+            frame_step: None,
 
-            history: History::new(vm.interpreter().rom.config),
+            history: History::new(vm),
             history_active: false,
 
             breakpoints: Default::default(),
+            op_breakpoints: Default::default(),
+            break_on_collision: false,
             watchpoints: Default::default(),
             watch_state: WatchState::from(vm.interpreter()),
             event_queue: Default::default(),
 
-            disassembler: Disassembler::from(vm.interpreter().rom.clone()),
+            disassembler: Disassembler::with_memory_size(
+                vm.interpreter().rom.clone(),
+                vm.interpreter().memory.len(),
+            ),
             disassembler_needs_update: false,
 
             memory: Memory::from(vm.interpreter().memory.as_slice()), //Default::default(),
@@ -200,7 +273,10 @@ impl Debugger {
             memory_visible: true,
             memory_widget_state: Default::default(),
 
+            index_memory_visible: false,
+
             keyboard_shows_qwerty: true,
+            held_keys_applied: 0,
 
             runner_target_execution_frequency: initial_target_execution_frequency,
 
@@ -211,6 +287,24 @@ impl Debugger {
             vm_visible: true,
             vm_exception: None,
             vm_executing: true,
+
+            warn_data_execution: false,
+            executing_data: false,
+
+            checkpoints: Default::default(),
+
+            undo_limit: HISTORY_CAPACITY,
+
+            logging: false,
+            logger_visible: true,
+            logger_height_percent: None,
+
+            rom_path: None,
+            session_path: None,
+
+            activation_key: DEFAULT_DEBUG_KEY,
+
+            dirty: true,
         };
 
         dbg.disassembler.run();
@@ -220,33 +314,228 @@ impl Debugger {
 
     pub fn reset(&mut self, vm: &mut VM, preserve_rpl_flags: bool) {
         vm.reset(preserve_rpl_flags);
-
-        self.history = History::new(vm.interpreter().rom.config);
+        self.reset_history(vm);
 
         self.watch_state = WatchState::from(vm.interpreter());
         self.event_queue = Default::default();
         
-        self.disassembler = Disassembler::from(vm.interpreter().rom.clone());
+        self.disassembler = Disassembler::with_memory_size(
+            vm.interpreter().rom.clone(),
+            vm.interpreter().memory.len(),
+        );
         self.memory = Memory::from(vm.interpreter().memory.as_slice());
         self.memory_widget_state = Default::default();
         self.vm_exception = None;
         self.vm_executing = true;
+        self.executing_data = false;
 
         self.disassembler.run();
     }
 
+    fn reset_history(&mut self, vm: &VM) {
+        let reviewing = self.history.is_reviewing();
+        let recording = self.history.is_recording();
+        self.history = History::new(vm);
+        self.history.set_reviewing(reviewing);
+        self.history.set_recording(recording);
+    }
+
+    // execution access is only tracked while debugging so this diagnostic lives here rather than the vm
+    pub fn set_warn_data_execution(&mut self, warn: bool) {
+        self.warn_data_execution = warn;
+    }
+
+    pub fn set_undo_limit(&mut self, limit: usize) {
+        self.undo_limit = limit;
+    }
+
+    // queued commands run before anything else once the debugger starts, as if they were typed into the shell
+    pub fn queue_commands(&mut self, commands: Vec<String>) {
+        self.shell.queue(commands);
+    }
+
+    pub fn set_history_recording(&mut self, recording: bool) {
+        self.history.set_recording(recording);
+    }
+
+    // reports why a command that needs history cannot run
+    fn is_history_off(&mut self) -> bool {
+        let off = !self.history.is_recording();
+        if off {
+            self.shell.error("History is off (started with --no-history)");
+        }
+        off
+    }
+
+    fn clamp_to_undo_limit(&mut self, amount: usize) -> usize {
+        if amount > self.undo_limit {
+            self.shell.print(format!(
+                "Limited to {} instructions (set with --undo-limit)",
+                self.undo_limit
+            ));
+        }
+        amount.min(self.undo_limit)
+    }
+
+    // the logger can only be toggled when one was initialized at startup
+    pub fn set_logging(&mut self, logging: bool) {
+        self.logging = logging;
+    }
+
+    pub fn is_logger_visible(&self) -> bool {
+        self.logging && self.logger_visible
+    }
+
+    pub fn set_activation_key(&mut self, key: KeyCode) {
+        self.activation_key = key;
+    }
+
+    pub fn activation_key_name(&self) -> String {
+        key_code_name(self.activation_key)
+    }
+
+    pub fn set_rom_path(&mut self, path: PathBuf) {
+        self.rom_path = Some(path);
+    }
+
+    pub fn restore_session(&mut self, vm: &VM, path: PathBuf, mut session: DebugSession) {
+        self.shell.load_history(std::mem::take(&mut session.shell_history));
+        self.shell
+            .print(format!("Restored session from \"{}\"", path.display()));
+        self.merge_breakpoints(vm, session);
+        self.session_path = Some(path);
+    }
+
+    // breakpoints kept next to the rom are picked up on launch, and having none is the common case so it is silent
+    pub fn load_rom_breakpoints(&mut self, vm: &VM) {
+        let Some(path) = self.rom_path.as_deref().map(breakpoints_path) else {
+            return;
+        };
+
+        if path.exists() {
+            self.load_breakpoints(vm, &path);
+        }
+    }
+
+    fn load_breakpoints(&mut self, vm: &VM, path: &Path) {
+        match DebugSession::read(path) {
+            Ok(session) => {
+                self.shell
+                    .print(format!("Loaded breakpoints from \"{}\"", path.display()));
+                self.merge_breakpoints(vm, session);
+            }
+            Err(e) => self.shell.print(format!(
+                "Failed to load breakpoints from \"{}\": {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    // a file saved with more memory can hold addresses this vm doesn't have, those are dropped and reported
+    fn merge_breakpoints(&mut self, vm: &VM, session: DebugSession) {
+        let memory_len = vm.interpreter().memory.len();
+        let mut dropped_breakpoints = 0;
+        let mut dropped_watchpoints = 0;
+
+        for address in session.breakpoints {
+            if address as usize >= memory_len {
+                dropped_breakpoints += 1;
+                continue;
+            }
+            self.breakpoints.insert(address);
+        }
+
+        for pattern in session.op_breakpoints {
+            if !self.op_breakpoints.contains(&pattern) {
+                self.op_breakpoints.push(pattern);
+            }
+        }
+
+        for watchpoint in session.watchpoints {
+            if let Watchpoint::Address(address) = watchpoint {
+                if address as usize >= memory_len {
+                    dropped_watchpoints += 1;
+                    continue;
+                }
+                self.watch_state
+                    .addresses
+                    .insert(address, vm.interpreter().memory[address as usize]);
+            }
+            self.watchpoints.insert(watchpoint);
+        }
+
+        let dropped: Vec<String> = [(dropped_breakpoints, "breakpoint"), (dropped_watchpoints, "watchpoint")]
+            .into_iter()
+            .filter(|&(count, _)| count > 0)
+            .map(|(count, name)| format!("{} {}{}", count, name, if count == 1 { "" } else { "s" }))
+            .collect();
+        if !dropped.is_empty() {
+            self.shell.print(format!(
+                "Dropped {} past the end of memory ({}B)",
+                dropped.join(" and "),
+                memory_len
+            ));
+        }
+    }
+
+    fn to_session(&self) -> DebugSession {
+        // sets have no order of their own so they are sorted to keep saved files the same between saves
+        let mut breakpoints: Vec<u16> = self.breakpoints.iter().copied().collect();
+        breakpoints.sort_unstable();
+        let mut watchpoints: Vec<Watchpoint> = self.watchpoints.iter().copied().collect();
+        watchpoints.sort_unstable();
+
+        DebugSession {
+            rom_path: self.rom_path.clone(),
+            breakpoints,
+            op_breakpoints: self.op_breakpoints.clone(),
+            watchpoints,
+            shell_history: self.shell.history().to_vec(),
+        }
+    }
+
+    fn save_session(&mut self, path: &Path) {
+        match self.to_session().write(path) {
+            Ok(()) => self
+                .shell
+                .print(format!("Saved session to \"{}\"", path.display())),
+            Err(e) => self.shell.print(format!(
+                "Failed to save session to \"{}\": {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
     pub fn is_active(&self) -> bool {
         self.active
     }
 
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
     fn activate(&mut self, vm: &VM) {
         if self.active {
             return;
         }
 
-        self.shell.print("Paused.");
+        match self.frame_step.take() {
+            Some(FrameStep { frames_stepped: 1, amt_stepped, .. }) => {
+                self.shell.print(format!("Stepped a frame in {} instructions", amt_stepped));
+            }
+            Some(FrameStep { frames_stepped, amt_stepped, .. }) if frames_stepped > 1 => {
+                self.shell.print(format!(
+                    "Stepped {} frames in {} instructions",
+                    frames_stepped, amt_stepped
+                ));
+            }
+            _ => self.shell.print("Paused."),
+        }
         self.shell.output_pc(vm.interpreter());
         self.active = true;
+        self.dirty = true;
     }
 
     fn deactivate(&mut self) {
@@ -256,6 +545,7 @@ impl Debugger {
 
         self.shell.print("Continuing.");
         self.active = false;
+        self.dirty = true;
     }
 
     fn stepn(&mut self, vm: &mut VM, amt: usize, cycles_per_frame: u32) -> usize {
@@ -274,6 +564,52 @@ impl Debugger {
         amt_stepped
     }
 
+    // a frame ends at the first instruction that changes the display or after the runner's frame of instructions
+    // so it is bounded, and the debugger activates once the last frame of the frame step is done
+    fn step_frame(&mut self, vm: &mut VM, amt: usize) -> bool {
+        let display_updates = vm.display_updates();
+        for _ in 0..amt {
+            if !self.step_instructions(vm, 1) {
+                return false;
+            }
+            if let Some(frame_step) = self.frame_step.as_mut() {
+                frame_step.amt_stepped += 1;
+            }
+            if vm.display_updates() != display_updates {
+                break;
+            }
+        }
+
+        let Some(frame_step) = self.frame_step.as_mut() else {
+            return false
+        };
+        frame_step.frames_stepped += 1;
+        if frame_step.frames_stepped < frame_step.frames {
+            return true;
+        }
+
+        self.activate(vm);
+        false
+    }
+
+    // presses the held keys and releases the ones this pressed before that are no longer held
+    // so keys set with the key command are left alone
+    fn apply_held_keys(&mut self, vm: &mut VM, held_keys: &HashSet<Key>) {
+        let held = held_keys.iter().fold(0_u16, |held, key| held | 1 << key.to_code());
+        let (&down_keys, _, _) = vm.keyboard().state();
+
+        for &key in KEY_ORDERING.iter() {
+            let bit = 1 << key.to_code();
+            if held & bit != 0 && down_keys & bit == 0 {
+                vm.press_key(key);
+            } else if held & bit == 0 && self.held_keys_applied & bit != 0 {
+                vm.release_key(key);
+            }
+        }
+
+        self.held_keys_applied = held;
+    }
+
     fn redon(&mut self, vm: &mut VM, mut amt: usize) -> usize {
         amt = amt.min(self.history.redo_amount());
         vm.clear_event_queue();
@@ -287,6 +623,14 @@ impl Debugger {
         amt
     }
 
+    // seeking through history only keeps the disassembler current for instructions that store to memory
+    // so memory edits made by the debugger are checked separately
+    fn update_disassembler_for_edits(&mut self, vm: &VM, edits: Vec<(u16, u16)>) {
+        for (address, len) in edits {
+            self.disassembler_needs_update |= self.disassembler.needs_rerun(vm.interpreter(), address, len);
+        }
+    }
+
     fn step_once(&mut self, vm: &mut VM) -> bool {
         let mut should_continue = match self.history.step(vm, &mut self.memory.access_flags) {
             Ok(cont) => {
@@ -298,9 +642,10 @@ impl Debugger {
                 cont
             }
             Err(e) => {
-                self.shell.error(&e);
+                let message = e.to_string();
+                self.shell.error(&message);
                 self.vm_executing = false;
-                self.vm_exception = Some(e);
+                self.vm_exception = Some(message);
                 self.activate(vm);
                 false
             }
@@ -330,6 +675,37 @@ impl Debugger {
             _ => (),
         }
 
+        // warn once on entering a region that is usually not code, typically the result of a runaway jump
+        if self.warn_data_execution {
+            let region = vm
+                .interpreter()
+                .data_execution_region(&self.memory.access_flags);
+            if let Some(region) = region {
+                if !self.executing_data {
+                    log::warn!(
+                        "Program counter entered {} at {:#05X}",
+                        region,
+                        vm.interpreter().pc
+                    );
+                }
+            }
+            self.executing_data = region.is_some();
+        }
+
+        // a draw waiting for the vertical blank has not drawn yet so vf is only its collision once it stops waiting
+        if self.break_on_collision && !vm.interpreter().waiting {
+            if let Some(Instruction::Draw(vx, vy, _)) = self.watch_state.instruction {
+                if vm.interpreter().registers[VFLAG] != 0 {
+                    self.event_queue.push(DebugEvent::CollisionReached {
+                        pc: self.watch_state.pc,
+                        x: self.watch_state.registers[vx as usize],
+                        y: self.watch_state.registers[vy as usize],
+                        index: self.watch_state.index,
+                    });
+                }
+            }
+        }
+
         // update watch state
         self.watch_state
             .update(vm.interpreter(), &self.watchpoints, &mut self.event_queue);
@@ -340,6 +716,27 @@ impl Debugger {
                 .push(DebugEvent::BreakpointReached(vm.interpreter().pc));
         }
 
+        // update instruction breakpoints
+        if !self.op_breakpoints.is_empty() {
+            let interp = vm.interpreter();
+            if let Some(instruction) = interp.instruction() {
+                let mut opcode = [0; 2];
+                interp.memory.export(interp.pc, &mut opcode);
+
+                let mut asm = String::new();
+                let mut asm_desc = String::new();
+                write_inst_dasm(&instruction, interp.rom.config, &mut asm, &mut asm_desc).ok();
+                let mnemonic = asm.split_whitespace().next().unwrap_or_default();
+
+                if let Some(pattern) = self.op_breakpoints.iter().find(|pattern| {
+                    pattern.matches(&instruction, u16::from_be_bytes(opcode), mnemonic)
+                }) {
+                    self.event_queue
+                        .push(DebugEvent::OpBreakpointReached(pattern.clone(), interp.pc));
+                }
+            }
+        }
+
         if !self.event_queue.is_empty() {
             should_continue = false;
             self.activate(vm);
@@ -349,6 +746,14 @@ impl Debugger {
     }
 
     pub fn step(&mut self, vm: &mut VM, amt: usize) -> bool {
+        if self.frame_step.is_some() {
+            self.step_frame(vm, amt)
+        } else {
+            self.step_instructions(vm, amt)
+        }
+    }
+
+    fn step_instructions(&mut self, vm: &mut VM, amt: usize) -> bool {
         if let Some(e) = self.vm_exception.as_ref() {
             self.shell.error(e);
             self.activate(vm);
@@ -378,6 +783,7 @@ impl Debugger {
         }
 
         self.memory_widget_state.get_mut().poke();
+        self.dirty = true;
 
         // handle debug events emitted
         for debug_event in self.event_queue.drain(..) {
@@ -386,6 +792,16 @@ impl Debugger {
                     self.shell
                         .print(format!("Breakpoint {:#05X} reached", addr));
                 }
+                DebugEvent::OpBreakpointReached(pattern, addr) => {
+                    self.shell
+                        .print(format!("Instruction breakpoint {} reached at {:#05X}", pattern, addr));
+                }
+                DebugEvent::CollisionReached { pc, x, y, index } => {
+                    self.shell.print(format!(
+                        "Collision at {:#05X} drawing the sprite at {:#05X} to ({}, {})",
+                        pc, index, x, y
+                    ));
+                }
                 DebugEvent::WatchpointTrigger(watchpoint, old, new) => match watchpoint {
                     Watchpoint::Pointer(pointer) => {
                         let identifier = match pointer {
@@ -419,10 +835,24 @@ impl Debugger {
         should_continue
     }
 
-    pub fn handle_input_event(&mut self, event: Event, runner: &mut Runner, vm: &mut VM) -> bool {
+    // held keys are the keypad keys physically held down, which reach the program through the frame step key
+    pub fn handle_input_event(
+        &mut self,
+        event: Event,
+        runner: &mut Runner,
+        vm: &mut VM,
+        held_keys: &HashSet<Key>,
+    ) -> bool {
         let mut sink_event = false;
 
         'handler: {
+            if let Event::Paste(text) = &event {
+                if self.active && self.shell_input_active {
+                    sink_event = self.shell.handle_paste(text);
+                }
+                break 'handler;
+            }
+
             let Event::Key(key_event) = event else {
                 break 'handler;
             };
@@ -432,7 +862,11 @@ impl Debugger {
             };
 
             if self.active {
-                if self.shell_input_active {
+                if key_event.code == FRAME_STEP_KEY {
+                    sink_event = true;
+                    self.apply_held_keys(vm, held_keys);
+                    self.handle_command(DebugCliCommand::Frame { amount: 1 }, runner, vm);
+                } else if self.shell_input_active {
                     sink_event = self.shell.handle_input_key_event(key_event);
                 } else if self.shell_output_active {
                     sink_event = self
@@ -462,15 +896,17 @@ impl Debugger {
                     }
                     let (seek_amt, seek_forwards) = payload;
                     if seek_amt > 0 {
+                        let edits = self.history.memory_edits_within(seek_amt, seek_forwards);
                         if seek_forwards {
                             self.redon(vm, seek_amt);
                         } else {
                             self.history.undo(vm, seek_amt, &mut self.memory.access_flags);
                             self.memory_widget_state.get_mut().poke();
                         }
+                        self.update_disassembler_for_edits(vm, edits);
                     }
                 }
-            } else if key_event.code == KeyCode::Esc {
+            } else if key_code_matches(key_event.code, self.activation_key) {
                 log::info!("c8vm interrupt!");
                 sink_event = true;
                 if let Err(e) = runner.pause() {
@@ -481,14 +917,26 @@ impl Debugger {
             }
         }
 
-        for input in self.shell.try_recv().collect::<Vec<_>>() {
+        self.run_queued_commands(runner, vm);
+
+        self.dirty |= sink_event;
+        sink_event
+    }
+
+    // runs commands typed into the shell or queued at startup, stopping once one resumes the program
+    pub fn run_queued_commands(&mut self, runner: &mut Runner, vm: &mut VM) {
+        let inputs: Vec<String> = self.shell.try_recv().collect();
+        for (i, input) in inputs.iter().enumerate() {
             if !self.active {
+                if i > 0 {
+                    log::warn!("Skipped {} queued commands after the program resumed", inputs.len() - i);
+                }
                 break;
             }
 
-            self.shell.echo(&input);
+            self.shell.echo(input);
 
-            let Ok(mut args) = shell_words::split(&input) else {
+            let Ok(mut args) = shell_words::split(input) else {
                 self.shell.print("Failed to parse command: mismatched quotes");
                 continue
             };
@@ -543,11 +991,42 @@ impl Debugger {
                 }
             }
         }
+    }
+
+    // resumes the runner for the frames instead of stepping them all here, which would hold the lock until done
+    fn handle_frame_step(&mut self, runner: &mut Runner, vm: &mut VM, amount: usize) {
+        if let Some(e) = self.vm_exception.as_ref() {
+            self.shell.error(e);
+            return;
+        }
 
-        sink_event
+        if !self.vm_executing {
+            self.shell.print("Program has finished executing.");
+            return;
+        }
+
+        if amount == 0 {
+            return;
+        }
+
+        if let Err(e) = runner.resume() {
+            log::warn!("Failed to resume runner: {}", e);
+            return;
+        }
+
+        self.frame_step = Some(FrameStep {
+            frames: amount,
+            frames_stepped: 0,
+            amt_stepped: 0,
+        });
+        self.active = false;
+        self.dirty = true;
+        self.history.clear_redo_history();
+        vm.clear_event_queue();
     }
 
     fn handle_command(&mut self, command: DebugCliCommand, runner: &mut Runner, vm: &mut VM) {
+        self.dirty = true;
         match command {
             DebugCliCommand::Reload => {
                 self.reset(vm, true);
@@ -571,6 +1050,11 @@ impl Debugger {
                     return;
                 }
 
+                if self.history.is_reviewing() {
+                    self.shell.print("Review mode is on, use step or redo to replay history or review to turn it off");
+                    return;
+                }
+
                 if let Err(e) = runner.resume() {
                     log::warn!("Failed to resume runner: {}", e);
                     return;
@@ -582,6 +1066,20 @@ impl Debugger {
                 vm.keyboard_mut().clear();
             }
 
+            DebugCliCommand::Step { amount } if self.history.is_reviewing() => {
+                if self.history.redo_amount() == 0 {
+                    self.shell.print("Reached the present (review mode is on)");
+                    return;
+                }
+
+                let amt_stepped = self.redon(vm, amount);
+                if amt_stepped > 1 {
+                    self.shell.print(format!("Stepped {} times", amt_stepped));
+                } else if amt_stepped == 1 {
+                    self.shell.output_pc(vm.interpreter());
+                }
+            }
+
             DebugCliCommand::Step { amount } => {
                 let amt_stepped = self.stepn(
                     vm,
@@ -596,6 +1094,12 @@ impl Debugger {
                 }
             }
 
+            DebugCliCommand::Frame { .. } if self.history.is_reviewing() => {
+                self.shell.print("Review mode is on, use step or redo to replay history or review to turn it off");
+            }
+
+            DebugCliCommand::Frame { amount } => self.handle_frame_step(runner, vm, amount),
+
             DebugCliCommand::Hertz { mut hertz } => {
                 if let Err(e) = runner.set_execution_frequency(hertz) {
                     self.shell.error(e);
@@ -611,12 +1115,19 @@ impl Debugger {
             }
 
             DebugCliCommand::Redo { amount } => {
+                if self.is_history_off() {
+                    return;
+                }
+
                 if self.history.redo_amount() == 0 {
                     self.shell.print("Nothing to redo");
                     return;
                 }
 
+                let amount = self.clamp_to_undo_limit(amount);
+                let edits = self.history.memory_edits_within(amount, true);
                 let amt_stepped = self.redon(vm, amount);
+                self.update_disassembler_for_edits(vm, edits);
                 if amt_stepped > 1 {
                     self.shell
                         .print(format!("Redid {} instructions", amt_stepped));
@@ -626,7 +1137,14 @@ impl Debugger {
             }
 
             DebugCliCommand::Undo { amount } => {
+                if self.is_history_off() {
+                    return;
+                }
+
+                let amount = self.clamp_to_undo_limit(amount);
+                let edits = self.history.memory_edits_within(amount, false);
                 let amt_rewinded = self.history.undo(vm, amount, &mut self.memory.access_flags);
+                self.update_disassembler_for_edits(vm, edits);
                 if amt_rewinded > 0 {
                     self.vm_exception = None;
                     self.vm_executing = true;
@@ -676,6 +1194,32 @@ impl Debugger {
                 self.memory_widget_state.get_mut().set_focus(address);
             }
 
+            DebugCliCommand::Functions => {
+                let functions = scan_functions(vm.interpreter());
+                if functions.is_empty() {
+                    self.shell.print("No subroutine calls found");
+                    return;
+                }
+
+                for (number, address) in functions.into_iter().enumerate() {
+                    self.shell.print(format!("{:>3}: {:#05X}", number, address));
+                }
+            }
+
+            DebugCliCommand::GotoFunc { number } => {
+                let Some(&address) = scan_functions(vm.interpreter()).get(number) else {
+                    self.shell.print(format!("No subroutine {} (use functions to list them)", number));
+                    return;
+                };
+
+                if (address as usize) >= vm.interpreter().memory.len() {
+                    self.shell.print("Address is out of bounds");
+                    return;
+                }
+
+                self.memory_widget_state.get_mut().set_focus(address);
+            }
+
             DebugCliCommand::Follow { pointer } => {
                 self.memory.follow = Some(match pointer {
                     Pointer::Pc => MemoryPointer::ProgramCounter,
@@ -694,7 +1238,39 @@ impl Debugger {
                 }
             }
 
-            DebugCliCommand::Break { address } => {
+            DebugCliCommand::Break { file: Some(file), .. } => {
+                let Some(path) = self.rom_path.as_deref().map(breakpoints_path) else {
+                    self.shell.print("No ROM file to keep breakpoints alongside");
+                    return;
+                };
+
+                match file {
+                    BreakFileCommand::Save => match self.to_session().write_breakpoints(&path) {
+                        Ok(()) => self
+                            .shell
+                            .print(format!("Saved breakpoints to \"{}\"", path.display())),
+                        Err(e) => self.shell.print(format!(
+                            "Failed to save breakpoints to \"{}\": {}",
+                            path.display(),
+                            e
+                        )),
+                    },
+                    BreakFileCommand::Load => {
+                        if path.exists() {
+                            self.load_breakpoints(vm, &path);
+                        } else {
+                            self.shell
+                                .print(format!("No breakpoints saved at \"{}\"", path.display()));
+                        }
+                    }
+                }
+            }
+
+            DebugCliCommand::Break { address: None, .. } => {
+                self.shell.print("Expected a breakpoint address");
+            }
+
+            DebugCliCommand::Break { address: Some(address), .. } => {
                 if (address as usize) >= vm.interpreter().memory.len() {
                     self.shell.print("Address is out of bounds");
                     return;
@@ -709,6 +1285,26 @@ impl Debugger {
                 }
             }
 
+            DebugCliCommand::BreakOp { pattern } => {
+                if self.op_breakpoints.contains(&pattern) {
+                    self.shell
+                        .print(format!("Instruction breakpoint {} already exists", pattern));
+                } else {
+                    self.shell
+                        .print(format!("Instruction breakpoint set on {}", pattern));
+                    self.op_breakpoints.push(pattern);
+                }
+            }
+
+            DebugCliCommand::BreakOnCollision => {
+                self.break_on_collision = !self.break_on_collision;
+                if self.break_on_collision {
+                    self.shell.print("Breaking on sprite collisions");
+                } else {
+                    self.shell.print("No longer breaking on sprite collisions");
+                }
+            }
+
             DebugCliCommand::Watch { watchpoint } => {
                 let watchpoint = match watchpoint {
                     WatchOption::Pointer(Pointer::Pc) => {
@@ -748,6 +1344,9 @@ impl Debugger {
                     self.memory_visible = true;
                     self.memory.verbose = verbose;
                 }
+                ShowHideOption::Index => {
+                    self.index_memory_visible = true;
+                }
             },
 
             DebugCliCommand::Hide { view } => match view {
@@ -761,17 +1360,23 @@ impl Debugger {
                         self.memory_visible = false;
                     }
                 }
+                ShowHideOption::Index => {
+                    self.index_memory_visible = false;
+                }
             },
 
             DebugCliCommand::Info { what } => match what {
                 WatchBreakOption::Break => {
-                    if self.breakpoints.is_empty() {
+                    if self.breakpoints.is_empty() && self.op_breakpoints.is_empty() {
                         self.shell.print("No breakpoints set");
                     } else {
                         self.shell.print("Breakpoints:");
                         for breakpoint in self.breakpoints.iter() {
                             self.shell.print(format!("    - {:#05X}", breakpoint));
                         }
+                        for pattern in self.op_breakpoints.iter() {
+                            self.shell.print(format!("    - {}", pattern));
+                        }
                     }
                 }
                 WatchBreakOption::Watch => {
@@ -792,12 +1397,61 @@ impl Debugger {
                         }
                     }
                 }
+                WatchBreakOption::Config => {
+                    let cycles_per_frame = vm.cycles_per_frame();
+                    let interp = vm.interpreter();
+                    let config = interp.rom.config;
+
+                    let mut settings = vec![
+                        ("kind", config.kind.to_string()),
+                        ("memory", format!("{} bytes", interp.memory.len())),
+                        (
+                            "speed",
+                            format!("{}Cpf ({}Hz)", cycles_per_frame, cycles_per_frame * VM_FRAME_RATE),
+                        ),
+                        ("timers", format!("{}Hz", VM_FRAME_RATE)),
+                        ("cpu frozen", vm.is_cpu_frozen().to_string()),
+                        ("timers frozen", vm.are_timers_frozen().to_string()),
+                        ("volume", format!("{:.0}%", vm.audio().volume() * 100.0)),
+                        ("audio pitch", interp.audio.pitch.to_string()),
+                        (
+                            "unknown opcodes",
+                            format!("{:?}", interp.settings.unknown_opcode_mode).to_lowercase(),
+                        ),
+                        (
+                            "machine calls",
+                            format!("{:?}", interp.settings.machine_call_mode).to_lowercase(),
+                        ),
+                        ("warn add overflow", interp.settings.warn_add_overflow.to_string()),
+                        ("warn uninit reads", interp.settings.warn_uninit_reads.to_string()),
+                        (
+                            "warn stack depth",
+                            interp
+                                .settings
+                                .warn_stack_depth
+                                .map_or(String::from("off"), |depth| depth.to_string()),
+                        ),
+                        ("trace erased pixels", interp.settings.trace_erased_pixels.to_string()),
+                        ("undo limit", self.undo_limit.to_string()),
+                    ];
+                    settings.extend(
+                        config
+                            .quirks
+                            .named()
+                            .into_iter()
+                            .map(|(name, value)| (name, value.to_string())),
+                    );
+
+                    let width = settings.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+                    for (name, value) in settings {
+                        self.shell.print(format!("{:width$} = {}", name, value, width = width));
+                    }
+                }
             },
 
             DebugCliCommand::Key { command } => match command {
                 KeyCommand::Down { key } => {
-                    vm.keyboard_mut().handle_focus();
-                    vm.keyboard_mut().handle_key_down(key);
+                    vm.press_key(key);
                     self.shell.print(format!(
                         "Key \"{:X}\" ({}) down",
                         key.to_code(),
@@ -805,15 +1459,14 @@ impl Debugger {
                     ));
                 }
                 KeyCommand::Up { key } => {
-                    vm.keyboard_mut().handle_key_up(key);
+                    vm.release_key(key);
                     self.shell
                         .print(format!("Key \"{:X}\" ({}) up", key.to_code(), key.to_str()));
                 }
                 KeyCommand::Press { key } => {
-                    vm.keyboard_mut().handle_key_up(key);
-                    vm.keyboard_mut().handle_focus();
-                    vm.keyboard_mut().handle_key_down(key);
-                    vm.keyboard_mut().handle_key_up(key);
+                    vm.release_key(key);
+                    vm.press_key(key);
+                    vm.release_key(key);
                     self.shell.print(format!(
                         "Key \"{:X}\" ({}) pressed",
                         key.to_code(),
@@ -838,6 +1491,11 @@ impl Debugger {
                     vm.keyboard_mut().clear();
                     self.shell.print("Cleared keyboard");
                 }
+                ClearCommand::Display => {
+                    self.history
+                        .inject(vm, Instruction::ClearScreen, &self.memory.access_flags);
+                    self.shell.print("Cleared display");
+                }
                 ClearCommand::Watch { watchpoint } => {
                     let watchpoint = match watchpoint {
                         WatchOption::Pointer(Pointer::Pc) => {
@@ -869,6 +1527,16 @@ impl Debugger {
                         self.shell.print(format!("No watchpoint {}", watchpoint));
                     }
                 }
+                ClearCommand::BreakOp { pattern } => {
+                    if let Some(i) = self.op_breakpoints.iter().position(|p| *p == pattern) {
+                        self.op_breakpoints.remove(i);
+                        self.shell
+                            .print(format!("Cleared instruction breakpoint {}", pattern));
+                    } else {
+                        self.shell
+                            .print(format!("No instruction breakpoint {}", pattern));
+                    }
+                }
                 ClearCommand::Break {
                     breakpoint: address,
                 } => {
@@ -883,6 +1551,7 @@ impl Debugger {
                 ClearCommand::All { what } => match what {
                     WatchBreakOption::Break => {
                         self.breakpoints.clear();
+                        self.op_breakpoints.clear();
                         self.shell.print("Cleared all breakpoints");
                     }
                     WatchBreakOption::Watch => {
@@ -890,17 +1559,348 @@ impl Debugger {
                         self.watch_state.addresses.clear();
                         self.shell.print("Cleared all watchpoints");
                     }
+                    WatchBreakOption::Config => self.shell.error("Only breakpoints and watchpoints can be cleared"),
                 },
             },
 
-            DebugCliCommand::Dump { what } => match what {
-                DumpOption::Memory { path } => {
-                    let path_string = path.as_path().display().to_string();
-                    match (MemoryWidget {
-                        active: self.memory_active,
-                        memory: &self.memory,
-                        watchpoints: &self.watchpoints,
-                        breakpoints: &self.breakpoints,
+            DebugCliCommand::Review => {
+                if self.is_history_off() {
+                    return;
+                }
+
+                let reviewing = !self.history.is_reviewing();
+                self.history.set_reviewing(reviewing);
+                self.shell.print(if reviewing {
+                    "Review mode on: stepping replays history and stops at the present"
+                } else {
+                    "Review mode off: stepping at the present executes new instructions"
+                });
+            }
+
+            DebugCliCommand::Freeze { what } => match what {
+                FreezeOption::Timers => {
+                    let frozen = !vm.are_timers_frozen();
+                    vm.set_timers_frozen(frozen);
+                    self.shell.print(if frozen {
+                        "Timers frozen"
+                    } else {
+                        "Timers unfrozen"
+                    });
+                }
+                FreezeOption::Cpu => {
+                    let frozen = !vm.is_cpu_frozen();
+                    vm.set_cpu_frozen(frozen);
+                    self.shell.print(if frozen {
+                        "CPU frozen"
+                    } else {
+                        "CPU unfrozen"
+                    });
+                }
+            },
+
+            DebugCliCommand::Log { command } => {
+                if !self.logging {
+                    self.shell.print("Logging is not enabled (run with --log)");
+                    return;
+                }
+
+                match command {
+                    LogCommand::On => {
+                        self.logger_visible = true;
+                        self.shell.print("Log panel shown");
+                    }
+                    LogCommand::Off => {
+                        self.logger_visible = false;
+                        self.shell.print("Log panel hidden");
+                    }
+                    LogCommand::Height { percent } => {
+                        self.logger_height_percent = percent;
+                        match percent {
+                            Some(percent) => self
+                                .shell
+                                .print(format!("Log panel height set to {}%", percent)),
+                            None => self.shell.print("Log panel height reset"),
+                        }
+                    }
+                }
+            }
+
+            DebugCliCommand::SaveSession { path } => {
+                let Some(path) = path.or_else(|| self.session_path.clone()) else {
+                    self.shell.print("No session file to save to (pass a file path)");
+                    return;
+                };
+
+                self.save_session(&path);
+                self.session_path = Some(path);
+            }
+
+            DebugCliCommand::Stats => {
+                let cycles_per_frame = vm.cycles_per_frame();
+                let draw_stats = vm.interpreter().draw_stats;
+                self.shell.print(format!(
+                    "Speed: {}Cpf ({}Hz)",
+                    cycles_per_frame,
+                    cycles_per_frame * VM_FRAME_RATE
+                ));
+                self.shell
+                    .print(format!("Sprite draws: {}", draw_stats.draws));
+                self.shell
+                    .print(format!("Screen clears: {}", draw_stats.clears));
+            }
+
+            DebugCliCommand::TraceFilter { filter, clear } => {
+                let Some(tracer) = vm.tracer_mut() else {
+                    self.shell.error("Not tracing instructions (run with --trace-json or --verify-trace)");
+                    return;
+                };
+
+                if clear {
+                    tracer.set_filter(None);
+                    self.shell.print("Tracing every instruction");
+                } else if let Some(filter) = filter {
+                    self.shell.print(format!("Tracing only {}", filter));
+                    tracer.set_filter(Some(filter));
+                } else {
+                    match tracer.filter() {
+                        Some(filter) => self.shell.print(format!("Tracing only {}", filter)),
+                        None => self.shell.print("Tracing every instruction"),
+                    }
+                }
+            }
+
+            DebugCliCommand::Timings { command } => match command {
+                Some(TimingsCommand::On) => {
+                    vm.set_timing(true);
+                    self.shell.print("Measuring instruction timings");
+                }
+                Some(TimingsCommand::Off) => {
+                    vm.set_timing(false);
+                    self.shell.print("Stopped measuring instruction timings");
+                }
+                Some(TimingsCommand::Clear) => {
+                    if vm.timings().is_some() {
+                        vm.set_timing(false);
+                        vm.set_timing(true);
+                        self.shell.print("Cleared instruction timings");
+                    } else {
+                        self.shell.print("Instruction timings are off (use timings on)");
+                    }
+                }
+                Some(TimingsCommand::Frames) => {
+                    let timing = vm.frame_timing();
+                    if timing.frames() == 0 {
+                        self.shell.print("No frames run yet");
+                        return;
+                    }
+
+                    self.shell.print(format!("{:<16} {}", "frames", timing.frames()));
+                    self.shell.print(format!("{:<16} {:?}", "drift", timing.drift()));
+                    self.shell.print(format!("{:<16} {:?}", "avg drift", timing.avg_drift()));
+                    self.shell.print(format!("{:<16} {:?}", "last sleep", timing.last_sleep()));
+                    self.shell.print(format!("{:<16} {:?}", "last oversleep", timing.last_oversleep()));
+                    self.shell.print(format!("{:<16} {:?}", "max oversleep", timing.max_oversleep()));
+                    self.shell.print(format!("{:<16} {}", "overruns", timing.overruns()));
+                }
+                None => {
+                    let Some(timings) = vm.timings() else {
+                        self.shell.print("Instruction timings are off (use timings on)");
+                        return;
+                    };
+
+                    let report = timings.report();
+                    if report.is_empty() {
+                        self.shell.print("No instructions timed yet");
+                        return;
+                    }
+
+                    self.shell.print(format!(
+                        "{:<24} {:>10} {:>10} {:>10} {:>10}",
+                        "Instruction", "Count", "Min", "Avg", "Max"
+                    ));
+                    for (name, timing) in report {
+                        self.shell.print(format!(
+                            "{:<24} {:>10} {:>10} {:>10} {:>10}",
+                            name,
+                            timing.count,
+                            format!("{:?}", timing.min),
+                            format!("{:?}", timing.avg()),
+                            format!("{:?}", timing.max)
+                        ));
+                    }
+                }
+            },
+
+            DebugCliCommand::Opcode { opcode: HexBytes(bytes) } => {
+                if bytes.len() != 2 && bytes.len() != 4 {
+                    self.shell.error("Opcode must be 4 hex digits, or 8 for a long instruction (e.g. F0000200)");
+                    return;
+                }
+
+                let mut padded = [0; 4];
+                padded[..bytes.len()].copy_from_slice(&bytes);
+                let parameters = InstructionParameters::from(padded);
+                let rom_config = vm.interpreter().rom.config;
+
+                let instruction = match parameters.try_decode(rom_config.kind) {
+                    Ok(instruction) => instruction,
+                    Err(e) => {
+                        self.shell.error(e.to_string());
+                        return;
+                    }
+                };
+
+                let mut asm = String::new();
+                let mut asm_desc = String::new();
+                write_inst_dasm(&instruction, rom_config, &mut asm, &mut asm_desc).ok();
+
+                self.shell.print(format!(
+                    "{} {}",
+                    asm.split_whitespace().collect::<Vec<_>>().join(" "),
+                    asm_desc.trim()
+                ));
+                self.shell.print(format!("  {}", parameters));
+
+                let quirks = rom_config.quirks.affecting(&instruction);
+                if quirks.is_empty() {
+                    self.shell.print(format!("  Behaves the same under every quirk of {}", rom_config.kind));
+                } else {
+                    self.shell.print(format!("  Depends on quirks, as set for {}:", rom_config.kind));
+                    for (name, value) in quirks {
+                        self.shell.print(format!("    {} = {}", name, value));
+                    }
+                }
+            }
+
+            DebugCliCommand::Edit { instruction } => {
+                let interpreter = vm.interpreter();
+                let pc = interpreter.pc;
+                let rom_config = interpreter.rom.config;
+
+                let (opcode, new_instruction) = match assemble_inst(&instruction.join(" "), rom_config) {
+                    Ok(assembled) => assembled,
+                    Err(e) => {
+                        self.shell.error(e);
+                        return;
+                    }
+                };
+
+                let mut prior_bytes = [0; 2];
+                interpreter.memory.export(pc, &mut prior_bytes);
+
+                self.history
+                    .edit_memory(vm, pc, &opcode.to_be_bytes(), &self.memory.access_flags);
+                self.disassembler_needs_update |= self.disassembler.needs_rerun(vm.interpreter(), pc, 2);
+
+                if vm.interpreter().instruction().is_some() {
+                    self.vm_exception = None;
+                    self.vm_executing = true;
+                }
+
+                let mut asm = String::new();
+                let mut asm_desc = String::new();
+                write_inst_dasm(&new_instruction, rom_config, &mut asm, &mut asm_desc).ok();
+
+                self.memory_widget_state.get_mut().poke();
+                self.shell.print(format!(
+                    "Edited {:#05X}: {:04X} -> {:04X} ({})",
+                    pc,
+                    u16::from_be_bytes(prior_bytes),
+                    opcode,
+                    asm.split_whitespace().collect::<Vec<_>>().join(" ")
+                ));
+            }
+
+            DebugCliCommand::Jump { address } => {
+                let memory_len = vm.interpreter().memory.len();
+                if address as usize >= memory_len {
+                    self.shell.error(format!(
+                        "Address {:#05X} is outside of memory (last address is {:#05X})",
+                        address,
+                        memory_len - 1
+                    ));
+                    return;
+                }
+
+                // instructions are 2 bytes so an odd pc is usually a mistake but some roms do it on purpose
+                if address % 2 != 0 {
+                    self.shell.print(format!("Warning: {:#05X} is not 2 byte aligned", address));
+                }
+
+                let prior_pc = vm.interpreter().pc;
+                self.history.edit_pc(vm, address, &self.memory.access_flags);
+
+                if vm.interpreter().instruction().is_some() {
+                    self.vm_exception = None;
+                    self.vm_executing = true;
+                }
+
+                self.memory_widget_state.get_mut().poke();
+                self.shell.print(format!("Moved pc {:#05X} -> {:#05X}", prior_pc, address));
+            }
+
+            DebugCliCommand::Set { what } => {
+                let (address, bytes): (u16, Vec<u8>) = match what {
+                    SetOption::Memory { address, bytes } => {
+                        (address, bytes.into_iter().flat_map(|HexBytes(bytes)| bytes).collect())
+                    }
+                    SetOption::Sprite { address, rows } => {
+                        match assemble_sprite(&rows, vm.interpreter().rom.config.kind) {
+                            Ok(bytes) => (address, bytes),
+                            Err(e) => {
+                                self.shell.error(e);
+                                return;
+                            }
+                        }
+                    }
+                };
+
+                let memory_len = vm.interpreter().memory.len();
+                if address as usize >= memory_len {
+                    self.shell.error(format!(
+                        "Address {:#05X} is outside of memory (last address is {:#05X})",
+                        address,
+                        memory_len - 1
+                    ));
+                    return;
+                }
+
+                self.history
+                    .edit_memory(vm, address, &bytes, &self.memory.access_flags);
+
+                // the disassembler compares at most 16 bytes at a time
+                let memory_last_address = vm.interpreter().memory_last_address;
+                for (i, chunk) in bytes.chunks(16).enumerate() {
+                    let chunk_address = address.wrapping_add(16 * i as u16) & memory_last_address;
+                    self.disassembler_needs_update |= self.disassembler.needs_rerun(
+                        vm.interpreter(),
+                        chunk_address,
+                        chunk.len() as u16,
+                    );
+                }
+
+                if vm.interpreter().instruction().is_some() {
+                    self.vm_exception = None;
+                    self.vm_executing = true;
+                }
+
+                self.memory_widget_state.get_mut().poke();
+                self.shell.print(format!(
+                    "Wrote {} byte{} at {:#05X}",
+                    bytes.len(),
+                    if bytes.len() == 1 { "" } else { "s" },
+                    address
+                ));
+            }
+
+            DebugCliCommand::Dump { what } => match what {
+                DumpOption::Memory { path } => {
+                    let path_string = path.as_path().display().to_string();
+                    match (MemoryWidget {
+                        active: self.memory_active,
+                        memory: &self.memory,
+                        watchpoints: &self.watchpoints,
+                        breakpoints: &self.breakpoints,
                         interpreter: vm.interpreter(),
                         disassembler: &self.disassembler,
                     }
@@ -915,7 +1915,83 @@ impl Debugger {
                         )),
                     };
                 }
+                DumpOption::Inputs { path } => {
+                    if self.is_history_off() {
+                        return;
+                    }
+
+                    let path_string = path.as_path().display().to_string();
+                    match self.history.write_input_script(path, vm) {
+                        Ok(instructions) => self.shell.print(format!(
+                            "Dumped inputs of {} recorded instructions to \"{}\"",
+                            instructions, path_string
+                        )),
+                        Err(e) => self.shell.print(format!(
+                            "Failed to dump inputs to \"{}\": {}",
+                            path_string, e
+                        )),
+                    };
+                }
             },
+
+            DebugCliCommand::Checkpoint { name } => {
+                let replaced = self.checkpoints.insert(name.clone(), vm.to_checkpoint()).is_some();
+                self.shell.print(format!(
+                    "{} checkpoint \"{}\" at {:#05X}",
+                    if replaced { "Replaced" } else { "Saved" },
+                    name,
+                    vm.interpreter().pc
+                ));
+            }
+
+            DebugCliCommand::Restore { name } => {
+                let Some(checkpoint) = self.checkpoints.get(&name) else {
+                    self.shell.error(format!("No checkpoint named \"{}\"", name));
+                    return;
+                };
+
+                vm.restore_checkpoint(checkpoint);
+
+                // the history led up to a different state so none of it can be undone or redone from here
+                self.reset_history(vm);
+
+                self.watch_state = WatchState::from(vm.interpreter());
+                for watchpoint in self.watchpoints.iter() {
+                    if let &Watchpoint::Address(address) = watchpoint {
+                        self.watch_state
+                            .addresses
+                            .insert(address, vm.interpreter().memory[address as usize]);
+                    }
+                }
+
+                // any byte may differ and the disassembler compares at most 16 bytes at a time
+                let memory_len = vm.interpreter().memory.len();
+                for address in (0..memory_len).step_by(16) {
+                    let len = (memory_len - address).min(16) as u16;
+                    self.disassembler_needs_update |=
+                        self.disassembler
+                            .needs_rerun(vm.interpreter(), address as u16, len);
+                }
+
+                self.vm_exception = None;
+                self.vm_executing = true;
+                self.executing_data = false;
+                self.memory_widget_state.get_mut().poke();
+
+                self.shell.print(format!("Restored checkpoint \"{}\"", name));
+                self.shell.output_pc(vm.interpreter());
+            }
+
+            DebugCliCommand::Checkpoints => {
+                if self.checkpoints.is_empty() {
+                    self.shell.print("No checkpoints saved");
+                } else {
+                    self.shell.print("Checkpoints:");
+                    for (name, checkpoint) in self.checkpoints.iter() {
+                        self.shell.print(format!("    - {} at {:#05X}", name, checkpoint.pc()));
+                    }
+                }
+            }
         }
     }
 
@@ -957,6 +2033,7 @@ pub struct DebuggerWidgetAreas {
     pub pointers: Rect,
     pub registers: Rect,
     pub timers: Rect,
+    pub index_memory: Rect,
     pub stack: Rect,
     pub memory: Rect,
     pub audio: Rect,
@@ -974,6 +2051,7 @@ pub struct DebuggerWidgetBorders {
     pub pointers: Borders,
     pub registers: Borders,
     pub timers: Borders,
+    pub index_memory: Borders,
     pub stack: Borders,
     pub memory: Borders,
     pub audio: Borders,
@@ -993,6 +2071,7 @@ impl Default for DebuggerWidgetBorders {
             pointers: Borders::NONE,
             registers: Borders::NONE,
             timers: Borders::NONE,
+            index_memory: Borders::NONE,
             stack: Borders::NONE,
             memory: Borders::NONE,
             audio: Borders::NONE,
@@ -1011,11 +2090,63 @@ impl<'a> DebuggerWidget<'a> {
     const POINTERS_STATE_HEIGHT: u16 = 3;
     const REGISTERS_STATE_HEIGHT: u16 = 17;
     const TIMERS_STATE_HEIGHT: u16 = 5;
+    const INDEX_MEMORY_STATE_HEIGHT: u16 = 5;
     const AUDIO_STATE_HEIGHT: u16 = 8;
     const SCHIP_FLAG_STATE_HEIGHT: u16 = 9;
     const XOCHIP_FLAG_STATE_HEIGHT: u16 = 17;
     const PLANES_STATE_HEIGHT: u16 = 4;
 
+    // the smallest terminal the panels of the current layout fit in without being cut off
+    pub fn min_size(&self) -> (u16, u16) {
+        let command_line_height = 1;
+        if self.dbg.shell_output_active || self.dbg.memory_active {
+            return (DisplayMode::LowResolution.window_dimensions().0, 1 + command_line_height);
+        }
+
+        let (display_window_width, display_window_height) =
+            self.vm.interpreter().display.mode.window_dimensions();
+        let display_window_height = if self.dbg.vm_visible { display_window_height } else { 0 };
+
+        let rom_kind = self.vm.interpreter().rom.config.kind;
+        let (second_general_area_width, second_general_area_height) = if rom_kind >= RomKind::XOCHIP {
+            (
+                Self::GENERAL_STATE_COLUMN_WIDTH,
+                Self::PLANES_STATE_HEIGHT + Self::AUDIO_STATE_HEIGHT + Self::XOCHIP_FLAG_STATE_HEIGHT,
+            )
+        } else if rom_kind >= RomKind::SCHIP {
+            (Self::GENERAL_STATE_COLUMN_WIDTH, Self::SCHIP_FLAG_STATE_HEIGHT)
+        } else {
+            (0, 0)
+        };
+
+        let general_area_height = Self::KEYBOARD_STATE_HEIGHT
+            + Self::POINTERS_STATE_HEIGHT
+            + Self::REGISTERS_STATE_HEIGHT
+            + Self::TIMERS_STATE_HEIGHT
+            + self.index_memory_state_height()
+            + self.stack_state_height();
+
+        (
+            display_window_width + Self::GENERAL_STATE_COLUMN_WIDTH + second_general_area_width,
+            general_area_height
+                .max(second_general_area_height)
+                .max(display_window_height)
+                + command_line_height,
+        )
+    }
+
+    fn index_memory_state_height(&self) -> u16 {
+        if self.dbg.index_memory_visible {
+            Self::INDEX_MEMORY_STATE_HEIGHT
+        } else {
+            0
+        }
+    }
+
+    fn stack_state_height(&self) -> u16 {
+        1 + self.vm.interpreter().stack.len().max(1) as u16
+    }
+
     pub fn cursor_position(
         &self,
         area: Rect,
@@ -1158,14 +2289,19 @@ impl<'a> DebuggerWidget<'a> {
                 ])
                 .split(right_of_column_with_display)[..] else { unreachable!() };
 
+        // by default the logger lines up with the display unless given an explicit share of the column
+        let top_right_most_column_height = match self.dbg.logger_height_percent {
+            Some(percent) => (right_most_column.height as u32 * percent as u32 / 100) as u16,
+            None => display_window_height,
+        };
         let [top_right_most_column, bottom_right_most_column] = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(display_window_height),
+                Constraint::Length(top_right_most_column_height),
                 Constraint::Length(
                     right_of_column_with_display
                         .height
-                        .saturating_sub(display_window_height),
+                        .saturating_sub(top_right_most_column_height),
                 ),
             ])
             .split(right_most_column)[..] else { unreachable!() };
@@ -1196,18 +2332,19 @@ impl<'a> DebuggerWidget<'a> {
             ])
             .split(left_of_column_with_display)[..] else { unreachable!() };
         
+        let logger_height_percent = self.dbg.logger_height_percent.unwrap_or(50);
         let [top_left_most_area, bottom_left_most_area] = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Percentage(50),
-                Constraint::Percentage(50)
+                Constraint::Percentage(logger_height_percent),
+                Constraint::Percentage(100 - logger_height_percent)
             ])
             .split(left_most_area)[..] else { unreachable!() };
         
         let output_area = if self.logging && display_mode == DisplayMode::HighResolution { bottom_left_most_area } else { left_most_area };
         let output_area_borders = Borders::TOP;
 
-        let [keyboard_area, pointers_area, registers_area, timers_area, stack_area] =
+        let [keyboard_area, pointers_area, registers_area, timers_area, index_memory_area, stack_area] =
             Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
@@ -1215,13 +2352,15 @@ impl<'a> DebuggerWidget<'a> {
                     Constraint::Length(Self::POINTERS_STATE_HEIGHT),
                     Constraint::Length(Self::REGISTERS_STATE_HEIGHT),
                     Constraint::Length(Self::TIMERS_STATE_HEIGHT),
-                    Constraint::Length(1 + self.vm.interpreter().stack.len().max(1) as u16),
+                    Constraint::Length(self.index_memory_state_height()),
+                    Constraint::Length(self.stack_state_height()),
                 ])
                 .split(chip8_general_area)[..] else { unreachable!() };
         let keyboard_area_borders = Borders::TOP.union(Borders::LEFT);
         let pointers_area_borders = Borders::TOP.union(Borders::LEFT);
         let registers_area_borders = Borders::TOP.union(Borders::LEFT);
         let timers_area_borders = Borders::TOP.union(Borders::LEFT);
+        let index_memory_area_borders = Borders::TOP.union(Borders::LEFT);
         let stack_area_borders = Borders::ALL.difference(Borders::RIGHT);
 
         let second_general_area = if second_general_left_area_width > 0 {
@@ -1284,6 +2423,7 @@ impl<'a> DebuggerWidget<'a> {
                 pointers: pointers_area,
                 registers: registers_area,
                 timers: timers_area,
+                index_memory: index_memory_area,
                 stack: stack_area,
                 memory: memory_area,
                 planes: planes_area,
@@ -1300,6 +2440,7 @@ impl<'a> DebuggerWidget<'a> {
                 pointers: pointers_area_borders,
                 registers: registers_area_borders,
                 timers: timers_area_borders,
+                index_memory: index_memory_area_borders,
                 stack: stack_area_borders,
                 memory: memory_area_borders,
                 planes: planes_area_borders,
@@ -1332,8 +2473,19 @@ impl<'a> StatefulWidget for DebuggerWidget<'_> {
         let display_widget = self.vm.to_display_widget();
 
         // Display
+        // issuing commands while rewound is easy to do by accident when the history panel is hidden
+        let mut display_title = display_widget.build_title();
+        let rewound = self.dbg.history.redo_amount();
+        if rewound > 0 {
+            display_title.0.push(Span::styled(
+                format!("[rewound -{}]", rewound),
+                Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            ));
+            display_title.0.push(Span::raw(" "));
+        }
+
         let display_block = Block::default()
-            .title(display_widget.build_title())
+            .title(display_title)
             .borders(layout_borders.display);
         display_widget.render(display_block.inner(layout_areas.display), buf);
         display_block.render(layout_areas.display, buf);
@@ -1523,6 +2675,40 @@ impl<'a> StatefulWidget for DebuggerWidget<'_> {
         )
         .render(layout_areas.timers, buf);
 
+        // Index Memory
+        // undo restores memory so the bytes at i are the historical ones while rewinding
+        let mut index_memory = [0; 16];
+        interp.memory.export(interp.index, &mut index_memory);
+        Paragraph::new(
+            index_memory
+                .chunks_exact(4)
+                .enumerate()
+                .map(|(row, bytes)| {
+                    Spans::from(bytes.iter().enumerate().fold(
+                        vec![Span::raw(" ")],
+                        |mut vec, (col, byte)| {
+                            vec.push(Span::styled(
+                                format!("{:02x}", byte),
+                                if row == 0 && col == 0 {
+                                    Style::default().fg(Color::Black).bg(Color::LightYellow)
+                                } else {
+                                    Style::default()
+                                },
+                            ));
+                            vec.push(Span::raw(if col == 1 { "  " } else { " " }));
+                            vec
+                        },
+                    ))
+                })
+                .collect::<Vec<_>>(),
+        )
+        .block(
+            Block::default()
+                .title(" Index ")
+                .borders(layout_borders.index_memory),
+        )
+        .render(layout_areas.index_memory, buf);
+
         // Stack
         Paragraph::new(
             interp
@@ -1642,3 +2828,367 @@ impl<'a> StatefulWidget for DebuggerWidget<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ch8::{audio::silent_audio_controller, rom::Rom, script::InputScript},
+        testlog,
+    };
+
+    use crossterm::event::{KeyEvent, KeyModifiers};
+
+    use std::{ops::DerefMut, time::Duration};
+
+    fn vm(program: &[u8]) -> VM {
+        let kind = RomKind::CHIP8;
+        VM::new(
+            Rom::with_program(kind, program),
+            kind.default_memory_size(),
+            kind.default_cycles_per_frame(),
+            silent_audio_controller(),
+        )
+    }
+
+    // a paused debugger with the runner it needs for commands, which only runs while a frame step resumes it
+    fn paused(program: &[u8]) -> Runner {
+        let vm = vm(program);
+        let mut dbg = Debugger::new(&vm, 600);
+        dbg.activate(&vm);
+        Runner::new(vm, Some(dbg))
+    }
+
+    fn with_c8<T>(runner: &Runner, f: impl FnOnce(&mut VM, &mut Debugger) -> T) -> T {
+        let c8 = runner.c8();
+        let mut guard = c8.lock().expect("Failed to lock C8 for test");
+        let (vm, dbg) = guard.deref_mut();
+        f(vm, dbg.as_mut().expect("Runner has no debugger"))
+    }
+
+    // runs the input as if typed into the shell and returns what it printed
+    fn command(runner: &mut Runner, input: &str) -> Vec<String> {
+        let c8 = runner.c8();
+        let mut guard = c8.lock().expect("Failed to lock C8 for test");
+        let (vm, dbg) = guard.deref_mut();
+        let dbg = dbg.as_mut().expect("Runner has no debugger");
+
+        let printed = dbg.shell.output_text().len();
+        dbg.queue_commands(vec![input.to_string()]);
+        dbg.run_queued_commands(runner, vm);
+        dbg.shell.output_text().split_off(printed + 1)
+    }
+
+    fn press(runner: &mut Runner, code: KeyCode, held_keys: &[Key]) {
+        let c8 = runner.c8();
+        let mut guard = c8.lock().expect("Failed to lock C8 for test");
+        let (vm, dbg) = guard.deref_mut();
+        let dbg = dbg.as_mut().expect("Runner has no debugger");
+
+        let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        dbg.handle_input_event(event, runner, vm, &held_keys.iter().copied().collect());
+    }
+
+    // the runner steps a frame step so this waits for the debugger to take back control
+    fn wait_until_paused(runner: &Runner) {
+        while !with_c8(runner, |_, dbg| dbg.is_active()) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // presses the frame step key with the given keypad keys held down
+    fn frame_step_holding(runner: &mut Runner, held_keys: &[Key]) {
+        press(runner, FRAME_STEP_KEY, held_keys);
+        wait_until_paused(runner);
+    }
+
+    #[test]
+    fn jumping_into_the_font_warns() {
+        // jp 0x050
+        let mut vm = vm(&[0x10, 0x50]);
+        let mut dbg = Debugger::new(&vm, 600);
+        dbg.set_warn_data_execution(true);
+
+        testlog::capture();
+        dbg.step(&mut vm, 1);
+        assert_eq!(vm.interpreter().pc, 0x050);
+        assert_eq!(testlog::warnings(), vec!["Program counter entered font at 0x050"]);
+    }
+
+    #[test]
+    fn jumping_into_stored_data_warns() {
+        // ld i 0x300, ld v0 0x12, ld [i] v0, jp 0x300
+        let mut vm = vm(&[0xA3, 0x00, 0x60, 0x12, 0xF0, 0x55, 0x13, 0x00]);
+        let mut dbg = Debugger::new(&vm, 600);
+        dbg.set_warn_data_execution(true);
+
+        testlog::capture();
+        dbg.step(&mut vm, 3);
+        assert!(testlog::warnings().is_empty());
+
+        dbg.step(&mut vm, 1);
+        assert_eq!(vm.interpreter().pc, 0x300);
+        assert_eq!(testlog::warnings(), vec!["Program counter entered data at 0x300"]);
+    }
+
+    #[test]
+    fn jumping_into_the_font_is_silent_without_the_warning() {
+        let mut vm = vm(&[0x10, 0x50]);
+        let mut dbg = Debugger::new(&vm, 600);
+
+        testlog::capture();
+        dbg.step(&mut vm, 1);
+        assert_eq!(vm.interpreter().pc, 0x050);
+        assert!(testlog::warnings().is_empty());
+    }
+
+    #[test]
+    fn review_mode_stops_at_the_present_without_recording() {
+        // ld v0 1, ld v1 2, ld v2 3
+        let mut runner = paused(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03]);
+        command(&mut runner, "step 2");
+        command(&mut runner, "undo");
+        command(&mut runner, "review");
+
+        command(&mut runner, "step 2");
+        assert_eq!(command(&mut runner, "step"), vec!["Reached the present (review mode is on)"]);
+        with_c8(&runner, |vm, dbg| {
+            assert_eq!(vm.interpreter().pc, 0x204);
+            assert_eq!(vm.interpreter().registers[2], 0);
+            assert_eq!(dbg.history.fragments.len(), 2);
+        });
+
+        command(&mut runner, "review");
+        command(&mut runner, "step");
+        with_c8(&runner, |vm, dbg| {
+            assert_eq!(vm.interpreter().registers[2], 3);
+            assert_eq!(dbg.history.fragments.len(), 3);
+        });
+
+        runner.exit().expect("Runner failed");
+    }
+
+    #[test]
+    fn rewinding_a_key_wait_waits_again_and_redoing_consumes_the_same_key() {
+        // ld v0 k
+        let mut vm = vm(&[0xF0, 0x0A]);
+        let mut dbg = Debugger::new(&vm, 600);
+
+        dbg.step(&mut vm, 1);
+        assert!(vm.interpreter().waiting);
+        assert!(dbg.history.fragments.is_empty());
+
+        vm.press_key(Key::Q);
+        vm.release_key(Key::Q);
+        dbg.step(&mut vm, 1);
+        assert!(!vm.interpreter().waiting);
+        assert_eq!(vm.interpreter().registers[0], Key::Q.to_code());
+        assert_eq!(dbg.history.fragments.len(), 1);
+
+        assert_eq!(dbg.history.undo(&mut vm, 1, &mut dbg.memory.access_flags), 1);
+        assert!(vm.interpreter().waiting);
+        assert_eq!(vm.interpreter().pc, 0x200);
+        assert_eq!(vm.interpreter().registers[0], 0);
+
+        assert_eq!(dbg.redon(&mut vm, 1), 1);
+        assert!(!vm.interpreter().waiting);
+        assert_eq!(vm.interpreter().registers[0], Key::Q.to_code());
+        assert_eq!(dbg.history.fragments.len(), 1);
+        assert_eq!(dbg.history.redo_amount(), 0);
+    }
+
+    #[test]
+    fn replaying_dumped_inputs_reaches_the_same_state() {
+        // rnd v0 0xFF, ld v1 k, ld v2 0x20, ld dt v2, ld v3 dt, sknp v1, add v4 1, jp 0x208
+        let program = [0xC0, 0xFF, 0xF1, 0x0A, 0x62, 0x20, 0xF2, 0x15, 0xF3, 0x07, 0xE1, 0xA1, 0x74, 0x01, 0x12, 0x08];
+        let path = std::env::temp_dir().join(format!("c8-test-{}-inputs", std::process::id()));
+        let mut runner = paused(&program);
+
+        frame_step_holding(&mut runner, &[]);
+        frame_step_holding(&mut runner, &[Key::Q]);
+        frame_step_holding(&mut runner, &[]);
+        frame_step_holding(&mut runner, &[Key::Q]);
+        frame_step_holding(&mut runner, &[Key::Q]);
+        frame_step_holding(&mut runner, &[]);
+        let output = command(&mut runner, &format!("dump inputs {}", path.display()));
+        assert!(output[0].starts_with("Dumped inputs of "), "{:?}", output);
+
+        let text = std::fs::read_to_string(&path).expect("Failed to read dumped inputs");
+        std::fs::remove_file(&path).ok();
+        let script = InputScript::parse(&text).expect("Failed to parse dumped inputs");
+
+        let mut replayed = vm(&program);
+        assert_eq!(replayed.replay(&script), Ok(true));
+
+        with_c8(&runner, |vm, _| {
+            assert_eq!(replayed.cycles(), vm.cycles());
+            assert_eq!(replayed.delay_timer(), vm.delay_timer());
+            assert_eq!(replayed.sound_timer(), vm.sound_timer());
+
+            let (replayed, recorded) = (replayed.interpreter(), vm.interpreter());
+            assert_eq!(replayed.rng_seed(), recorded.rng_seed());
+            assert_eq!(replayed.registers, recorded.registers);
+            assert_eq!(replayed.pc, recorded.pc);
+            assert_eq!(replayed.index, recorded.index);
+            assert_eq!(replayed.waiting, recorded.waiting);
+            assert_eq!(replayed.display, recorded.display);
+            assert_ne!(recorded.registers[4], 0);
+        });
+
+        runner.exit().expect("Runner failed");
+    }
+
+    #[test]
+    fn frame_step_presses_held_keys_until_they_are_let_go() {
+        // ld v0 Q, skp v0, jp 0x204, jp 0x206
+        let mut runner = paused(&[0x60, Key::Q.to_code(), 0xE0, 0x9E, 0x12, 0x04, 0x12, 0x06]);
+        command(&mut runner, "step");
+
+        frame_step_holding(&mut runner, &[Key::Q]);
+        with_c8(&runner, |vm, _| {
+            assert_eq!(vm.interpreter().pc, 0x206);
+            assert_eq!(vm.keyboard().state().0 >> Key::Q.to_code() & 1, 1);
+        });
+
+        frame_step_holding(&mut runner, &[]);
+        with_c8(&runner, |vm, _| assert_eq!(vm.keyboard().state().0 >> Key::Q.to_code() & 1, 0));
+
+        runner.exit().expect("Runner failed");
+    }
+
+    #[test]
+    fn frame_step_runs_on_the_runner_and_reports_once_done() {
+        // jp 0x200, which never changes the display so every frame is a frame's worth of instructions
+        let mut runner = paused(&[0x12, 0x00]);
+        command(&mut runner, "frame 3");
+        wait_until_paused(&runner);
+
+        with_c8(&runner, |vm, dbg| {
+            assert_eq!(vm.executed_instructions(), 30);
+            assert!(dbg.frame_step.is_none());
+            assert!(dbg.shell.output_text().contains(&String::from("Stepped 3 frames in 30 instructions")));
+        });
+
+        runner.exit().expect("Runner failed");
+    }
+
+    #[test]
+    fn debug_key_interrupts_a_frame_step() {
+        let mut runner = paused(&[0x12, 0x00]);
+        command(&mut runner, "frame 1000000");
+        press(&mut runner, DEFAULT_DEBUG_KEY, &[]);
+
+        let executed = with_c8(&runner, |vm, dbg| {
+            assert!(dbg.is_active());
+            assert!(dbg.frame_step.is_none());
+            vm.executed_instructions()
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        with_c8(&runner, |vm, _| assert_eq!(vm.executed_instructions(), executed));
+
+        runner.exit().expect("Runner failed");
+    }
+
+    #[test]
+    fn jump_moves_the_pc_the_next_step_fetches_from() {
+        // ld v0 1, ld v1 2, ld v2 3
+        let mut runner = paused(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03]);
+        assert_eq!(command(&mut runner, "jump 0x204"), vec!["Moved pc 0x200 -> 0x204"]);
+
+        command(&mut runner, "step");
+        with_c8(&runner, |vm, _| {
+            assert_eq!(vm.interpreter().pc, 0x206);
+            assert_eq!(vm.interpreter().registers[..3], [0, 0, 3]);
+        });
+
+        command(&mut runner, "undo 2");
+        with_c8(&runner, |vm, _| assert_eq!(vm.interpreter().pc, 0x200));
+
+        runner.exit().expect("Runner failed");
+    }
+
+    #[test]
+    fn set_memory_on_a_blank_rom_runs_the_written_bytes_and_can_be_undone() {
+        let kind = RomKind::CHIP8;
+        let vm = VM::new(
+            Rom::blank(kind),
+            kind.default_memory_size(),
+            kind.default_cycles_per_frame(),
+            silent_audio_controller(),
+        );
+        let mut dbg = Debugger::new(&vm, 600);
+        dbg.activate(&vm);
+        let mut runner = Runner::new(vm, Some(dbg));
+
+        assert_eq!(command(&mut runner, "set mem 0x200 6001"), vec!["Wrote 2 bytes at 0x200"]);
+        command(&mut runner, "step");
+        with_c8(&runner, |vm, _| {
+            assert_eq!(vm.interpreter().registers[0], 1);
+            assert_eq!(vm.interpreter().pc, 0x202);
+        });
+
+        command(&mut runner, "undo 2");
+        with_c8(&runner, |vm, _| {
+            assert_eq!(vm.interpreter().registers[0], 0);
+            assert_eq!(vm.interpreter().pc, 0x200);
+            assert_eq!(vm.interpreter().memory[0x200..0x202], [0, 0]);
+        });
+
+        runner.exit().expect("Runner failed");
+    }
+
+    #[test]
+    fn jump_rejects_addresses_outside_of_memory_and_warns_on_odd_ones() {
+        let mut runner = paused(&[0x60, 0x01]);
+        assert_eq!(
+            command(&mut runner, "jump 0x1000"),
+            vec!["ERROR: Address 0x1000 is outside of memory (last address is 0xFFF)"]
+        );
+        assert_eq!(
+            command(&mut runner, "jump 0x203"),
+            vec!["Warning: 0x203 is not 2 byte aligned", "Moved pc 0x200 -> 0x203"]
+        );
+
+        runner.exit().expect("Runner failed");
+    }
+
+    #[test]
+    fn saved_breakpoints_load_back_and_those_outside_memory_are_reported() {
+        let rom_path = std::env::temp_dir().join(format!("c8-test-{}-breakpoints.ch8", std::process::id()));
+        let path = breakpoints_path(&rom_path);
+
+        let mut saved = paused(&[0x12, 0x00]);
+        with_c8(&saved, |_, dbg| dbg.set_rom_path(rom_path.clone()));
+        command(&mut saved, "break 0x202");
+        command(&mut saved, "watch v3");
+        assert_eq!(
+            command(&mut saved, "break save"),
+            vec![format!("Saved breakpoints to \"{}\"", path.display())]
+        );
+        saved.exit().expect("Runner failed");
+
+        let mut loaded = paused(&[0x12, 0x00]);
+        with_c8(&loaded, |_, dbg| dbg.set_rom_path(rom_path.clone()));
+        let output = command(&mut loaded, "break load");
+        with_c8(&loaded, |_, dbg| {
+            assert_eq!(dbg.breakpoints, HashSet::from([0x202]));
+            assert_eq!(dbg.watchpoints, HashSet::from([Watchpoint::Register(3)]));
+        });
+        assert_eq!(output, vec![format!("Loaded breakpoints from \"{}\"", path.display())]);
+
+        // as if saved while running with more memory than this vm has
+        std::fs::write(&path, "break 0x204\nbreak 0x1200\nwatch 0x1300\n").expect("Failed to write breakpoints");
+        let output = command(&mut loaded, "break load");
+        std::fs::remove_file(&path).ok();
+        assert_eq!(
+            output,
+            vec![
+                format!("Loaded breakpoints from \"{}\"", path.display()),
+                String::from("Dropped 1 breakpoint and 1 watchpoint past the end of memory (4096B)"),
+            ]
+        );
+        with_c8(&loaded, |_, dbg| assert_eq!(dbg.breakpoints, HashSet::from([0x202, 0x204])));
+
+        loaded.exit().expect("Runner failed");
+    }
+}
@@ -0,0 +1,231 @@
+use super::{
+    cli::{parse_addr, OpcodePattern, Pointer, WatchOption},
+    mem::MemoryPointer,
+    Watchpoint,
+};
+
+use std::{
+    fs::{read_to_string, write},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Sessions are stored as plain text, one entry per line:
+//
+//   rom roms/pong.ch8
+//   break 0x200
+//   break-op 8XY4
+//   watch v3
+//   history step 50
+//
+// Lines that fail to parse are skipped with a warning so an old session never blocks the debugger from starting
+
+// Breakpoint files hold only the break, break-op, and watch lines of a session and sit next to their rom so
+// they can be loaded automatically whenever that rom is debugged
+
+const SESSION_HEADER: &str = "# c8 debugger session";
+const BREAKPOINTS_HEADER: &str = "# c8 debugger breakpoints";
+const BREAKPOINTS_EXTENSION: &str = "c8bp";
+
+pub fn breakpoints_path(rom_path: &Path) -> PathBuf {
+    let mut path = rom_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(BREAKPOINTS_EXTENSION);
+    PathBuf::from(path)
+}
+
+#[derive(Default)]
+pub struct DebugSession {
+    pub rom_path: Option<PathBuf>,
+    pub(super) breakpoints: Vec<u16>,
+    pub(super) op_breakpoints: Vec<OpcodePattern>,
+    pub(super) watchpoints: Vec<Watchpoint>,
+    pub(super) shell_history: Vec<String>,
+}
+
+impl DebugSession {
+    // a session file that does not exist yet is an empty session so --session can name the file to create
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = match read_to_string(path.as_ref()) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+
+        let mut session = Self::default();
+
+        for (number, line) in contents.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let parsed = match key {
+                "rom" => {
+                    session.rom_path = Some(PathBuf::from(value));
+                    true
+                }
+                "break" => parse_addr(value)
+                    .map(|address| session.breakpoints.push(address))
+                    .is_ok(),
+                "break-op" => OpcodePattern::from_str(value)
+                    .map(|pattern| session.op_breakpoints.push(pattern))
+                    .is_ok(),
+                "watch" => WatchOption::from_str(value)
+                    .map(|option| session.watchpoints.push(Watchpoint::from(option)))
+                    .is_ok(),
+                "history" => {
+                    session.shell_history.push(value.to_string());
+                    true
+                }
+                _ => false,
+            };
+
+            if !parsed {
+                log::warn!(
+                    "Skipping unreadable line {} of session \"{}\": {}",
+                    number + 1,
+                    path.as_ref().display(),
+                    line
+                );
+            }
+        }
+
+        Ok(session)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_with_header(path, SESSION_HEADER)
+    }
+
+    // the rom path and shell history are left out since a breakpoints file is found through its rom
+    pub fn write_breakpoints<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        DebugSession {
+            rom_path: None,
+            breakpoints: self.breakpoints.clone(),
+            op_breakpoints: self.op_breakpoints.clone(),
+            watchpoints: self.watchpoints.clone(),
+            shell_history: Vec::new(),
+        }
+        .write_with_header(path, BREAKPOINTS_HEADER)
+    }
+
+    fn write_with_header<P: AsRef<Path>>(&self, path: P, header: &str) -> io::Result<()> {
+        let mut contents = String::from(header);
+        contents.push('\n');
+
+        // a lossy path would be read back as a different file so it is not written at all
+        if let Some(rom_path) = self.rom_path.as_ref() {
+            let Some(rom_path) = rom_path.to_str() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("ROM path \"{}\" is not valid UTF-8", rom_path.display()),
+                ));
+            };
+            contents.push_str(&format!("rom {}\n", rom_path));
+        }
+
+        for address in self.breakpoints.iter() {
+            contents.push_str(&format!("break {:#05X}\n", address));
+        }
+
+        for pattern in self.op_breakpoints.iter() {
+            contents.push_str(&format!("break-op {}\n", pattern));
+        }
+
+        for watchpoint in self.watchpoints.iter() {
+            contents.push_str(&format!("watch {}\n", watchpoint));
+        }
+
+        for cmd in self.shell_history.iter() {
+            contents.push_str(&format!("history {}\n", cmd));
+        }
+
+        write(path, contents)
+    }
+}
+
+impl From<WatchOption> for Watchpoint {
+    fn from(option: WatchOption) -> Self {
+        match option {
+            WatchOption::Pointer(Pointer::Pc) => Watchpoint::Pointer(MemoryPointer::ProgramCounter),
+            WatchOption::Pointer(Pointer::I) => Watchpoint::Pointer(MemoryPointer::Index),
+            WatchOption::Register(register) => Watchpoint::Register(register.to_index()),
+            WatchOption::Address(address) => Watchpoint::Address(address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testlog;
+
+    use std::{env, fs};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("c8-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn written_sessions_read_back_the_same() {
+        let session = DebugSession {
+            rom_path: Some(PathBuf::from("roms/pong.ch8")),
+            breakpoints: vec![0x200, 0x20A],
+            op_breakpoints: vec![
+                OpcodePattern::from_str("8XY4").expect("Failed to parse opcode pattern"),
+                OpcodePattern::from_str("drw").expect("Failed to parse opcode pattern"),
+            ],
+            watchpoints: vec![
+                Watchpoint::Pointer(MemoryPointer::Index),
+                Watchpoint::Register(0xA),
+                Watchpoint::Address(0x300),
+            ],
+            shell_history: vec![String::from("break 0x200"), String::from("step 5")],
+        };
+
+        let path = temp_path("session");
+        session.write(&path).expect("Failed to write session");
+        let read = DebugSession::read(&path).expect("Failed to read session");
+        fs::remove_file(&path).ok();
+
+        assert_eq!(read.rom_path, session.rom_path);
+        assert_eq!(read.breakpoints, session.breakpoints);
+        assert_eq!(read.op_breakpoints, session.op_breakpoints);
+        assert_eq!(read.watchpoints, session.watchpoints);
+        assert_eq!(read.shell_history, session.shell_history);
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped_with_a_warning() {
+        let path = temp_path("unreadable-session");
+        fs::write(&path, "# c8 debugger session\nbreak 0x200\nbreak nowhere\nwatch v3\n").expect("Failed to write session");
+
+        testlog::capture();
+        let read = DebugSession::read(&path).expect("Failed to read session");
+        fs::remove_file(&path).ok();
+
+        assert_eq!(read.breakpoints, vec![0x200]);
+        assert_eq!(read.watchpoints, vec![Watchpoint::Register(3)]);
+        let warnings = testlog::warnings();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].starts_with("Skipping unreadable line 3 of session"), "{}", warnings[0]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rom_paths_that_are_not_utf8_are_not_written() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let session = DebugSession {
+            rom_path: Some(PathBuf::from(OsStr::from_bytes(b"roms/\xFF.ch8"))),
+            ..Default::default()
+        };
+
+        let path = temp_path("lossy-session");
+        let error = session.write(&path).expect_err("A lossy ROM path should not be written");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(!path.exists());
+    }
+}
@@ -43,6 +43,15 @@ impl Shell {
         }
     }
 
+    pub(super) fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub(super) fn load_history(&mut self, history: Vec<String>) {
+        self.history = history;
+        self.history_index = self.history.len();
+    }
+
     pub(super) fn handle_input_key_event(&mut self, event: KeyEvent) -> bool {
         if !self.input_enabled {
             return false;
@@ -107,6 +116,8 @@ impl Shell {
                 if char.is_ascii() {
                     self.input.insert(self.cursor_position, char);
                     self.cursor_position += 1;
+                } else {
+                    self.error(format!("Cannot type '{}', the shell only accepts ASCII", char));
                 }
             }
             _ => {
@@ -117,6 +128,34 @@ impl Shell {
         sink_input
     }
 
+    // pasted line breaks become spaces since a paste should never run a command by itself
+    pub(super) fn handle_paste(&mut self, text: &str) -> bool {
+        if !self.input_enabled {
+            return false;
+        }
+
+        let text = text.trim_end_matches(&['\r', '\n'][..]);
+        let rejected = text.chars().filter(|c| !c.is_ascii()).count();
+        let pasted: String = text
+            .chars()
+            .filter(char::is_ascii)
+            .map(|c| if c.is_ascii_control() { ' ' } else { c })
+            .collect();
+
+        self.input.insert_str(self.cursor_position, &pasted);
+        self.cursor_position += pasted.len();
+
+        if rejected > 0 {
+            self.error(format!(
+                "Dropped {} pasted character{} the shell cannot accept (ASCII only)",
+                rejected,
+                if rejected == 1 { "" } else { "s" }
+            ));
+        }
+
+        true
+    }
+
     pub(super) fn handle_output_key_event(&mut self, event: KeyEvent, active: &mut bool) -> bool {
         match event.code {
             KeyCode::Esc => {
@@ -165,6 +204,10 @@ impl Shell {
         }
     }
 
+    pub(super) fn queue(&mut self, cmds: Vec<String>) {
+        self.cmd_queue.extend(cmds);
+    }
+
     pub(super) fn try_recv(&mut self) -> impl Iterator<Item = String> + '_ {
         self.cmd_queue.drain(..)
     }
@@ -197,6 +240,14 @@ impl Shell {
         ]));
     }
 
+    #[cfg(test)]
+    pub(super) fn output_text(&self) -> Vec<String> {
+        self.output
+            .iter()
+            .map(|spans| spans.0.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
     pub(super) fn as_output_widget(&self) -> OutputWidget {
         OutputWidget {
             output: self
@@ -413,3 +464,23 @@ impl<'a> StatefulWidget for InputWidget<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paste_inserts_at_the_cursor_and_drops_what_the_shell_cannot_accept() {
+        let mut shell = Shell::new();
+        shell.input = String::from("set mem  6001");
+        shell.cursor_position = 8;
+
+        assert!(shell.handle_paste("0x2\u{e9}00\n60\n"));
+        assert_eq!(shell.input, "set mem 0x200 60 6001");
+        assert_eq!(shell.cursor_position, 16);
+        assert_eq!(
+            shell.output_text(),
+            vec!["ERROR: Dropped 1 pasted character the shell cannot accept (ASCII only)"]
+        );
+    }
+}
@@ -3,68 +3,153 @@ extern crate log;
 mod asm;
 mod ch8;
 mod cli;
+mod conform;
+#[cfg(feature = "debugger")]
+mod dbg;
+#[cfg(not(feature = "debugger"))]
+#[path = "nodbg.rs"]
 mod dbg;
 mod render;
 mod run;
+mod stream;
+#[cfg(test)]
+mod testlog;
 
 use {
-    asm::Disassembler,
-    ch8::rom::Rom,
+    asm::{DecodeCoverage, Disassembler},
+    ch8::{
+        interp::{Interpreter, InterpreterSettings, PROGRAM_STARTING_ADDRESS},
+        mem::MemoryFill,
+        rom::{Rom, RomKind},
+        script::InputScript,
+        trace::InstructionTracer,
+    },
     cli::{Cli, CliCommand},
     render::panic_cleanup_terminal,
-    run::spawn_run_thread,
+    run::{device_key, key_code_matches, key_code_name, spawn_run_thread, BOUND_KEYS, DEFAULT_DEBUG_KEY, DEFAULT_TURBO_KEY},
+    stream::spawn_display_stream,
 };
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
 use crossterm::style::Stylize;
 
-use std::io::stdout;
+use std::{fs, io::stdout, path::Path, time::Duration};
 
 use crate::{ch8::{
-    audio::spawn_audio_stream,
-    vm::{VM_FRAME_RATE, VM}, run::Runner,
-}, dbg::Debugger, render::spawn_render_thread};
+    audio::{silent_audio_controller, spawn_audio_stream},
+    vm::{VM_FRAME_RATE, VM}, run::{run_bench, Runner},
+}, render::{spawn_render_thread, RenderOptions}};
+
+#[cfg(feature = "debugger")]
+use crate::dbg::{session::DebugSession, Debugger};
+#[cfg(feature = "debugger")]
+use anyhow::Context;
 
 fn main() -> Result<()> {
     match Cli::parse().command {
-        CliCommand::Check { path, log, kind } => {
+        CliCommand::Check { path, log, kind, skip } => {
             if let Some(level) = log {
                 simple_logger::init_with_level(level.to_level())?;
             }
 
-            let mut disasm = Disassembler::from(Rom::read(
+            let mut disasm = Disassembler::from(Rom::read_skipping(
                 path,
+                skip,
                 kind.map(cli::KindOption::to_kind),
-                None
+                &[]
             )?);
             disasm.run();
             disasm.write_issue_traces(&mut stdout())?;
         }
-        CliCommand::Dasm { path, log, kind } => {
+        CliCommand::Dasm { path, log, kind, skip } => {
             if let Some(level) = log {
                 simple_logger::init_with_level(level.to_level())?;
             }
 
-            let mut disasm = Disassembler::from(Rom::read(
+            let mut disasm = Disassembler::from(Rom::read_skipping(
                 path,
+                skip,
                 kind.map(cli::KindOption::to_kind),
-                None
+                &[]
             )?);
             disasm.run();
             print!("{}", disasm);
         }
+        CliCommand::Coverage { path, log, kind, skip } => {
+            if let Some(level) = log {
+                simple_logger::init_with_level(level.to_level())?;
+            }
+
+            let interp = Interpreter::new(Rom::read_skipping(
+                path,
+                skip,
+                kind.map(cli::KindOption::to_kind),
+                &[]
+            )?);
+            print!("{}", DecodeCoverage::from(&interp));
+        }
+        CliCommand::Test { dir, frames, bless, log } => {
+            if let Some(level) = log {
+                simple_logger::init_with_level(level.to_level())?;
+            }
+
+            let report = conform::run_conformance_dir(&dir, frames, bless)?;
+            println!("{}", report);
+
+            let failures = report.failures();
+            if failures > 0 {
+                bail!("{} of {} ROMs failed", failures, report.results.len());
+            }
+        }
         CliCommand::Run {
             path,
             debug,
+            pause,
+            blank,
+            session,
+            commands,
+            ex,
+            warn_data_exec,
+            undo_limit,
+            no_history,
+            warn_add_overflow,
+            warn_uninit_reads,
+            warn_stack_depth,
+            trace_erased_pixels,
+            debug_key,
             hz,
             cpf,
             log,
             kind,
+            quirk,
+            skip,
+            memory,
+            fill,
+            unknown_opcodes,
+            machine_calls,
+            palette,
+            scanlines,
+            phosphor,
+            show_opcode,
+            status_bar,
+            stream,
+            trace_json,
+            verify_trace,
+            trace_filter,
+            replay,
+            poll_ms,
+            keypad,
+            turbo_key,
+            turbo,
+            bench,
         } => {
-            let rom = Rom::read(path, kind.map(cli::KindOption::to_kind), None)?;
-            let kind = rom.config.kind;
-            let cpf = cpf.or(hz.map(|hz| hz / VM_FRAME_RATE)).unwrap_or(kind.default_cycles_per_frame());
+            #[cfg(not(feature = "debugger"))]
+            if debug || pause || blank || session.is_some() || commands.is_some() || !ex.is_empty() || warn_data_exec || undo_limit.is_some() || no_history || debug_key.is_some() {
+                bail!("This build of c8 does not include the debugger (enable the \"debugger\" feature)");
+            }
+
+            let debug = debug || pause || blank || session.is_some() || commands.is_some() || !ex.is_empty();
             let logging = log.is_some();
             
             if let Some(level) = log {
@@ -72,6 +157,133 @@ fn main() -> Result<()> {
                 tui_logger::set_default_level(level.to_level_filter());
             }
 
+            // a session remembers its rom but an explicit path wins, which also recovers sessions whose rom has moved
+            #[cfg(feature = "debugger")]
+            let session = session
+                .map(|session_path| DebugSession::read(&session_path).map(|session| (session_path, session)))
+                .transpose()?;
+            // a missing commands file is an error rather than an empty script since the setup it holds would be lost
+            #[cfg(feature = "debugger")]
+            let startup_commands = {
+                let mut startup_commands = Vec::new();
+                if let Some(commands_path) = commands {
+                    let contents = std::fs::read_to_string(&commands_path)
+                        .with_context(|| format!("Failed to read commands from \"{}\"", commands_path.display()))?;
+                    startup_commands.extend(
+                        contents
+                            .lines()
+                            .map(str::trim)
+                            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                            .map(String::from),
+                    );
+                }
+                startup_commands.extend(ex);
+                startup_commands
+            };
+
+            #[cfg(feature = "debugger")]
+            let session_rom_path = session.as_ref().and_then(|(_, session)| session.rom_path.clone());
+            #[cfg(not(feature = "debugger"))]
+            let session_rom_path: Option<std::path::PathBuf> = None;
+
+            let path = match (path, session_rom_path) {
+                _ if blank => None,
+                (Some(path), _) => Some(path),
+                (None, Some(rom_path)) if rom_path.exists() => Some(rom_path),
+                (None, Some(rom_path)) => bail!(
+                    "Session ROM \"{}\" no longer exists, pass the ROM path to load it from elsewhere",
+                    rom_path.display()
+                ),
+                (None, None) => bail!("Session does not reference a ROM, pass the ROM path to load"),
+            };
+
+            let mut rom = match path.as_ref() {
+                Some(path) => Rom::read_skipping(path, skip, kind.map(cli::KindOption::to_kind), &quirk)?,
+                None => {
+                    let mut rom = Rom::blank(kind.map_or(RomKind::CHIP8, cli::KindOption::to_kind));
+                    rom.config.quirks = rom.config.quirks.with_overrides(&quirk);
+                    rom.config.quirks.warn_overrides(rom.config.kind);
+                    rom
+                }
+            };
+            rom.fill = fill;
+            rom.log_load();
+            if let MemoryFill::Random(seed) = fill {
+                log::info!("Filled memory and registers with random:{}", seed);
+            }
+            let kind = rom.config.kind;
+            let cpf = cpf.or(hz.map(|hz| hz / VM_FRAME_RATE)).unwrap_or(kind.default_cycles_per_frame());
+            let memory_size = memory.unwrap_or(kind.default_memory_size());
+            if rom.data.len() > memory_size - PROGRAM_STARTING_ADDRESS as usize {
+                bail!(
+                    "ROM size ({}B) does not fit in {}B of memory",
+                    rom.data.len(),
+                    memory_size
+                );
+            }
+
+            // bind the display stream before the terminal is taken over so errors are readable
+            let display_stream = stream.map(spawn_display_stream).transpose()?;
+            let verifying = verify_trace.is_some();
+            let mut tracer = match (trace_json, verify_trace) {
+                (Some(path), _) => Some(InstructionTracer::create(path)?),
+                (None, Some(path)) => Some(InstructionTracer::verify(path)?),
+                (None, None) => None,
+            };
+            if let Some(tracer) = tracer.as_mut() {
+                tracer.set_filter(trace_filter);
+            }
+
+            let replay = replay.map(|path| read_input_script(&path, kind, &rom.name)).transpose()?;
+
+            let settings = InterpreterSettings {
+                unknown_opcode_mode: unknown_opcodes.to_mode(),
+                machine_call_mode: machine_calls.to_mode(),
+                warn_add_overflow,
+                warn_uninit_reads,
+                warn_stack_depth,
+                trace_erased_pixels,
+            };
+
+            // the bench has no terminal to play sound along with so it never opens the audio device
+            if let Some(seconds) = bench {
+                let mut vm = VM::new(rom, memory_size, cpf, silent_audio_controller());
+                configure_vm(&mut vm, settings, tracer, replay.as_ref())?;
+
+                match run_bench(vm, Duration::from_secs(seconds)) {
+                    Ok(stats) => println!("{}", stats),
+                    Err(err) => bail!("{}", err),
+                }
+
+                return Ok(());
+            }
+
+            let turbo_key_code = turbo_key.unwrap_or(DEFAULT_TURBO_KEY);
+            let Some(turbo_key) = device_key(turbo_key_code) else {
+                bail!("Turbo key {} cannot be detected while held, pick another", key_code_name(turbo_key_code));
+            };
+
+            // a debug key that is bound to something else is taken by the debugger before it does that
+            let debug_key = debug_key.unwrap_or(DEFAULT_DEBUG_KEY);
+            let shadowed_binding = if keypad.to_layout().map_crossterm_key(debug_key).is_some() {
+                Some("CHIP-8 keypad")
+            } else if key_code_matches(debug_key, turbo_key_code) {
+                Some("turbo")
+            } else {
+                BOUND_KEYS
+                    .iter()
+                    .find(|(key, _)| key_code_matches(debug_key, *key))
+                    .map(|(_, name)| *name)
+            };
+            if let Some(binding) = shadowed_binding.filter(|_| debug) {
+                println!(
+                    "\n  {} debug key {} is also the {} key and will not work as both",
+                    format!("Warning").yellow().bold(),
+                    key_code_name(debug_key),
+                    binding
+                );
+            }
+
             // preempt wait thread message
             println!(
                 "\n  {} for {} thread",
@@ -94,21 +306,59 @@ fn main() -> Result<()> {
             let (_audio_stream, audio_controller) = spawn_audio_stream();
 
             // vm and optional debugger
-            let vm = VM::new(rom, cpf, audio_controller);
+            let mut vm = VM::new(rom, memory_size, cpf, audio_controller);
+            if let Some(palette) = palette {
+                vm.set_palette(palette.to_colors());
+            }
+            configure_vm(&mut vm, settings, tracer, replay.as_ref())?;
+            #[cfg(feature = "debugger")]
             let dbg = if debug {
-                Some(Debugger::new(&vm, cpf * VM_FRAME_RATE))
+                let mut dbg = Debugger::new(&vm, cpf * VM_FRAME_RATE);
+                dbg.set_warn_data_execution(warn_data_exec);
+                if let Some(limit) = undo_limit {
+                    dbg.set_undo_limit(limit as usize);
+                }
+                dbg.set_history_recording(!no_history);
+                dbg.set_logging(logging);
+                dbg.set_activation_key(debug_key);
+                if let Some(path) = path {
+                    dbg.set_rom_path(path.canonicalize().unwrap_or(path));
+                }
+                if let Some((session_path, session)) = session {
+                    dbg.restore_session(&vm, session_path, session);
+                }
+                dbg.load_rom_breakpoints(&vm);
+                dbg.queue_commands(startup_commands);
+                Some(dbg)
             } else {
                 None
             };
+            #[cfg(not(feature = "debugger"))]
+            let dbg = None;
 
             // vm runner
             let runner = Runner::new(vm, dbg);
 
             // spawn render thread
-            let (render_controller, render_thread) = spawn_render_thread(runner.c8(), logging);
+            let render_options = RenderOptions {
+                scanlines,
+                phosphor,
+                opcode_overlay: show_opcode,
+                status_bar,
+                display_stream,
+            };
+            let (render_controller, render_thread) = spawn_render_thread(runner.c8(), logging, render_options);
 
             // spawn run thread
-            let run_thread = spawn_run_thread(runner, render_controller, debug, logging);
+            let run_thread = spawn_run_thread(
+                runner,
+                render_controller,
+                debug,
+                logging,
+                Duration::from_millis(poll_ms),
+                keypad.to_layout(),
+                (turbo_key, turbo),
+            );
 
             // wait for threads
             render_thread
@@ -116,10 +366,68 @@ fn main() -> Result<()> {
                 .expect("Failed to join render thread");
             match run_thread.join().expect("Failed to join run thread") {
                 Ok(analytics) => println!("{}", analytics),
-                Err(err) => println!("\n    {} {}", format!("Error").red().bold(), err),
+                Err(err) => {
+                    println!("\n    {} {}", format!("Error").red().bold(), err);
+
+                    // scripts verifying a trace need to see the failure
+                    if verifying {
+                        std::process::exit(1);
+                    }
+                }
             }
         }
     }
 
     Ok(())
 }
+
+// read before the terminal is taken over so a script that cannot replay is reported readably
+fn read_input_script(path: &Path, kind: RomKind, rom_name: &str) -> Result<InputScript> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => bail!("Failed to read input script \"{}\": {}", path.display(), e),
+    };
+
+    let script = match InputScript::parse(&text) {
+        Ok(script) => script,
+        Err(e) => bail!(e),
+    };
+
+    if script.kind != kind {
+        bail!("Input script was recorded for {} but the ROM runs as {}", script.kind, kind);
+    }
+    if script.rom_name != rom_name {
+        log::warn!("Input script was recorded for {} rather than {}", script.rom_name, rom_name);
+    }
+
+    Ok(script)
+}
+
+// the same for the bench and a normal run so a setting only has to be passed on here
+fn configure_vm(
+    vm: &mut VM,
+    settings: InterpreterSettings,
+    tracer: Option<InstructionTracer>,
+    replay: Option<&InputScript>,
+) -> Result<()> {
+    vm.set_settings(settings);
+    vm.set_tracer(tracer);
+    if let Some(script) = replay {
+        replay_input_script(vm, script)?;
+    }
+    Ok(())
+}
+
+// the runner and debugger pick up from wherever the script leaves the program
+fn replay_input_script(vm: &mut VM, script: &InputScript) -> Result<()> {
+    match vm.replay(script) {
+        Ok(running) => {
+            log::info!("Replayed {} instructions of {}", script.instructions(), script.rom_name);
+            if !running {
+                log::info!("Program exited during the replay");
+            }
+            Ok(())
+        }
+        Err(e) => bail!("Replay failed: {}", e),
+    }
+}
@@ -0,0 +1,39 @@
+use crate::ch8::{input::Key, run::Runner, vm::VM};
+
+use crossterm::event::Event;
+
+use std::collections::HashSet;
+
+// Stands in for the debugger when c8 is built without the "debugger" feature.
+// A Debugger can never be constructed so every Option<Debugger> is None and the debugging paths compile away.
+pub enum Debugger {}
+
+impl Debugger {
+    pub fn is_active(&self) -> bool {
+        match *self {}
+    }
+
+    pub fn is_logger_visible(&self) -> bool {
+        match *self {}
+    }
+
+    pub fn take_dirty(&mut self) -> bool {
+        match *self {}
+    }
+
+    pub fn activation_key_name(&self) -> String {
+        match *self {}
+    }
+
+    pub fn step(&mut self, _: &mut VM, _: usize) -> bool {
+        match *self {}
+    }
+
+    pub fn run_queued_commands(&mut self, _: &mut Runner, _: &mut VM) {
+        match *self {}
+    }
+
+    pub fn handle_input_event(&mut self, _: Event, _: &mut Runner, _: &mut VM, _: &HashSet<Key>) -> bool {
+        match *self {}
+    }
+}
@@ -1,15 +1,24 @@
 use crate::{
+    asm::write_inst_dasm,
     ch8::{
-        disp::DisplayWidget,
+        disp::{Display, DisplayMode, DisplayWidget, PhosphorFade},
+        interp::{Interpreter, StallReason},
         run::C8Lock,
         vm::{VM, VM_FRAME_DURATION},
     },
-    dbg::{Debugger, DebuggerWidget, DebuggerWidgetState},
+    dbg::Debugger,
+    stream::DisplayStream,
 };
 
+#[cfg(feature = "debugger")]
+use crate::dbg::{DebuggerWidget, DebuggerWidgetState};
+#[cfg(feature = "debugger")]
+use std::cell::Cell;
+
 use anyhow::{anyhow, Context, Result};
 use crossterm::{
     execute,
+    event::{DisableBracketedPaste, EnableBracketedPaste},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use tui::{
@@ -17,26 +26,28 @@ use tui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::Span,
-    widgets::{Block, Borders, Gauge, Paragraph},
+    widgets::{Block, Borders, Gauge, Paragraph, Wrap},
     Frame,
 };
 use tui_logger::{TuiLoggerLevelOutput, TuiLoggerWidget};
 
 use std::{
-    cell::Cell,
     io::{self, stdout},
     ops::DerefMut,
     sync::mpsc::{channel, Sender, TryRecvError},
     thread::{self, JoinHandle},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 type Terminal = tui::Terminal<CrosstermBackend<io::Stdout>>;
 
+// how long the display title shows the zoom after it changes
+const ZOOM_NOTICE_DURATION: Duration = Duration::from_secs(2);
+
 fn cleanup_terminal(terminal: &mut Terminal) -> Result<()> {
     // clean up the terminal so its usable after program exit
     disable_raw_mode().context("Failed to disable terminal raw mode")?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)
+    execute!(terminal.backend_mut(), DisableBracketedPaste, LeaveAlternateScreen)
         .context("Failed to leave alternate terminal screen")?;
     terminal
         .show_cursor()
@@ -44,6 +55,33 @@ fn cleanup_terminal(terminal: &mut Terminal) -> Result<()> {
     Ok(())
 }
 
+fn enter_terminal(out: &mut impl io::Write) -> Result<()> {
+    // change terminal to an alternate screen so user doesnt lose terminal history on exit
+    // and enable raw mode so we have full authority over event handling and output
+    // bracketed paste delivers a paste as one event rather than a key event per character
+    enable_raw_mode().context("Failed to enable terminal raw mode")?;
+    execute!(out, EnterAlternateScreen, EnableBracketedPaste).context("Failed to enter alternate terminal screen")?;
+    Ok(())
+}
+
+// raw mode turns Ctrl-Z into a key event so the process is stopped here the way the shell would have stopped it,
+// with the terminal handed back first and taken over again once the process is continued
+#[cfg(unix)]
+fn suspend_terminal(terminal: &mut Terminal) -> Result<()> {
+    cleanup_terminal(terminal)?;
+
+    // returns once the process receives SIGCONT
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+
+    enter_terminal(terminal.backend_mut())?;
+
+    // the screen was lost while suspended so everything is drawn again rather than only what changed
+    terminal.clear().context("Failed to clear terminal")?;
+    Ok(())
+}
+
 pub fn panic_cleanup_terminal() -> Result<()> {
     cleanup_terminal(
         &mut tui::Terminal::new(CrosstermBackend::new(stdout()))
@@ -51,32 +89,46 @@ pub fn panic_cleanup_terminal() -> Result<()> {
     )
 }
 
-pub fn spawn_render_thread(c8: C8Lock, logging: bool) -> (RenderController, JoinHandle<()>) {
-    let (render_sender, render_receiver) = channel::<()>();
-    let render_thread_handle = thread::spawn(move || {
-        // change terminal to an alternate screen so user doesnt lose terminal history on exit
-        // and enable raw mode so we have full authority over event handling and output
-        enable_raw_mode().expect("Failed to enable terminal raw mode");
+// how the display is drawn, chosen on the command line
+#[derive(Default)]
+pub struct RenderOptions {
+    pub scanlines: bool,
+    pub phosphor: bool,
+    pub opcode_overlay: bool,
+    pub status_bar: bool,
+    pub display_stream: Option<DisplayStream>,
+}
 
+pub fn spawn_render_thread(c8: C8Lock, logging: bool, options: RenderOptions) -> (RenderController, JoinHandle<()>) {
+    let (render_sender, render_receiver) = channel::<RenderEvent>();
+    let render_thread_handle = thread::spawn(move || {
         let mut stdout = stdout();
-        execute!(stdout, EnterAlternateScreen).expect("Failed to enter alternate terminal screen");
+        enter_terminal(&mut stdout).expect("Failed to set up terminal");
 
         let mut terminal = tui::Terminal::new(CrosstermBackend::new(stdout))
             .expect("Failed to create interface to terminal backend");
 
-        let mut renderer = Renderer {
-            dbg_widget_state: Default::default(),
-            dbg_visible: false,
-            logging,
-        };
+        let mut renderer = Renderer::new(logging, options);
 
         let mut should_redraw = false;
 
         let mut frame_start = Instant::now();
 
         loop {
-            if render_receiver.try_iter().last().is_some() {
+            for event in render_receiver.try_iter() {
                 should_redraw = true;
+                match event {
+                    RenderEvent::ToggleOpcodeOverlay => renderer.opcode_overlay = !renderer.opcode_overlay,
+                    RenderEvent::Zoom(zoom_in) => renderer.zoom(zoom_in),
+                    #[cfg(unix)]
+                    RenderEvent::Suspend => {
+                        suspend_terminal(&mut terminal).expect("Failed to suspend terminal");
+
+                        // frames missed while stopped are not worth catching up on
+                        frame_start = Instant::now();
+                    }
+                    RenderEvent::Redraw => (),
+                }
             }
 
             if let Err(TryRecvError::Disconnected) = render_receiver.try_recv() {
@@ -101,22 +153,115 @@ pub fn spawn_render_thread(c8: C8Lock, logging: bool) -> (RenderController, Join
     (RenderController(render_sender), render_thread_handle)
 }
 
-pub struct RenderController(Sender<()>);
+enum RenderEvent {
+    Redraw,
+    ToggleOpcodeOverlay,
+    Zoom(bool),
+    #[cfg(unix)]
+    Suspend,
+}
+
+pub struct RenderController(Sender<RenderEvent>);
 
 impl RenderController {
     pub fn trigger(&self) {
-        self.0.send(()).expect("Unable to send render event")
+        self.0.send(RenderEvent::Redraw).expect("Unable to send render event")
+    }
+
+    pub fn toggle_opcode_overlay(&self) {
+        self.0
+            .send(RenderEvent::ToggleOpcodeOverlay)
+            .expect("Unable to send render event")
+    }
+
+    pub fn zoom(&self, zoom_in: bool) {
+        self.0
+            .send(RenderEvent::Zoom(zoom_in))
+            .expect("Unable to send render event")
+    }
+
+    // there is no suspending a process from a terminal on windows so this does nothing there
+    pub fn suspend(&self) {
+        #[cfg(unix)]
+        self.0.send(RenderEvent::Suspend).expect("Unable to send render event")
     }
 }
 
 struct Renderer {
     logging: bool,
+    logger_visible: bool,
+    stall_reason: Option<StallReason>,
+    scanlines: bool,
+    opcode_overlay: bool,
+    opcode_overlay_text: Option<String>,
+    status_bar: bool,
+    status_bar_text: Option<String>,
+    phosphor_fade: Option<PhosphorFade>,
+    zoom: u16,
+    zoom_limit: u16, // the largest zoom that fit the terminal when the display was last drawn
+    zoom_notice_until: Option<Instant>,
     dbg_visible: bool,
+    #[cfg(feature = "debugger")]
     dbg_widget_state: Cell<DebuggerWidgetState>,
+    display_stream: Option<DisplayStream>,
+}
+
+// what is drawn around the display besides the display itself, gathered fresh each frame
+struct VmFrame<'a> {
+    volume: f32,
+    dbg_key: Option<String>,
+    logging: bool,
+    opcode_overlay: Option<&'a str>,
+    status_bar: Option<&'a str>,
+    zoom_notice: Option<u16>,
 }
 
 impl Renderer {
+    fn new(logging: bool, options: RenderOptions) -> Self {
+        Self {
+            #[cfg(feature = "debugger")]
+            dbg_widget_state: Default::default(),
+            dbg_visible: false,
+            logging,
+            logger_visible: logging,
+            stall_reason: None,
+            scanlines: options.scanlines,
+            opcode_overlay: options.opcode_overlay,
+            opcode_overlay_text: None,
+            status_bar: options.status_bar,
+            status_bar_text: None,
+            phosphor_fade: options.phosphor.then(PhosphorFade::default),
+            zoom: 1,
+            zoom_limit: 1,
+            zoom_notice_until: None,
+            display_stream: options.display_stream,
+        }
+    }
+
+    // zooming past what fits does nothing rather than building up zoom that cannot be seen
+    fn zoom(&mut self, zoom_in: bool) {
+        let zoom = self.zoom.min(self.zoom_limit);
+        self.zoom = if zoom_in {
+            (zoom + 1).min(self.zoom_limit)
+        } else {
+            zoom.saturating_sub(1).max(1)
+        };
+        self.zoom_notice_until = Some(Instant::now() + ZOOM_NOTICE_DURATION);
+    }
+
     fn step(&mut self, terminal: &mut Terminal, should_redraw: bool, c8: &C8Lock) -> Result<()> {
+        let maybe_display = self.draw(terminal, should_redraw, c8)?;
+
+        // the frame is encoded once the vm is unlocked and free to keep running, the stream thread writes it out
+        if let (Some(stream), Some(display)) = (&self.display_stream, &maybe_display) {
+            stream.publish(display);
+        }
+
+        Ok(())
+    }
+
+    // draws if anything changed and returns the display if it changed since the last step
+    fn draw(&mut self, terminal: &mut Terminal, should_redraw: bool, c8: &C8Lock) -> Result<Option<Display>> {
         let mut _guard = c8
             .lock()
             .map_err(|_| anyhow!("Failed to lock C8 for render step"))?;
@@ -126,43 +271,116 @@ impl Renderer {
         let maybe_display = vm.extract_new_display();
 
         let is_dbg_visible = maybe_dbg.as_ref().map_or(false, Debugger::is_active);
-        let should_draw =
-            should_redraw || maybe_display.is_some() || is_dbg_visible != self.dbg_visible;
+
+        // scrubbing history or typing changes the debugger without changing the display
+        let is_dbg_dirty = maybe_dbg.as_mut().map_or(false, Debugger::take_dirty);
+
+        // fading pixels change every frame while the display itself does not
+        let fading = match self.phosphor_fade.as_mut() {
+            Some(fade) => fade.step(maybe_display.as_ref()),
+            None => false,
+        };
+
+        // the debugger can hide the logger at runtime
+        let logging = self.logging && maybe_dbg.as_ref().map_or(true, Debugger::is_logger_visible);
+
+        // vertical blank waits come and go every frame and are not shown so they should not force a redraw
+        let stall_reason = vm
+            .stall_reason()
+            .filter(|&reason| reason != StallReason::WaitingForVerticalBlank);
+
+        // the pc moves without the display changing so the overlay is compared to decide on a redraw too
+        let opcode_overlay_text = (self.opcode_overlay && !is_dbg_visible)
+            .then(|| opcode_overlay_text(vm.interpreter()));
+        let status_bar_text = (self.status_bar && !is_dbg_visible).then(|| status_bar_text(vm));
+
+        let zoom_notice_expired = self
+            .zoom_notice_until
+            .map_or(false, |until| Instant::now() >= until);
+        if zoom_notice_expired {
+            self.zoom_notice_until = None;
+        }
+
+        let should_draw = should_redraw
+            || zoom_notice_expired
+            || maybe_display.is_some()
+            || is_dbg_visible != self.dbg_visible
+            || is_dbg_dirty && is_dbg_visible
+            || logging != self.logger_visible
+            || stall_reason != self.stall_reason
+            || opcode_overlay_text != self.opcode_overlay_text
+            || status_bar_text != self.status_bar_text
+            || fading && !is_dbg_visible;
 
         if should_draw {
             self.dbg_visible = is_dbg_visible;
+            self.logger_visible = logging;
+            self.stall_reason = stall_reason;
+            self.opcode_overlay_text = opcode_overlay_text;
+            self.status_bar_text = status_bar_text;
             if is_dbg_visible {
-                let Some(dbg) = maybe_dbg else {
-                    unreachable!("debugger must exist for debugger draw call to be made")
-                };
-
-                terminal.draw(|f| {
-                    dbg.prepare_render();
-                    self.render_debugger(f, dbg, vm);
-                })?;
+                #[cfg(feature = "debugger")]
+                {
+                    let Some(dbg) = maybe_dbg else {
+                        unreachable!("debugger must exist for debugger draw call to be made")
+                    };
+
+                    terminal.draw(|f| {
+                        dbg.prepare_render();
+                        self.render_debugger(f, dbg, vm, logging);
+                    })?;
+                }
             } else {
                 let volume = vm.audio().volume();
-                let is_dbg_enabled = maybe_dbg.is_some();
-                let display_widget = vm.to_display_widget();
+                let dbg_key = maybe_dbg.as_ref().map(Debugger::activation_key_name);
+                let mut display_widget = vm.to_display_widget();
+                display_widget.scanlines = self.scanlines;
+                display_widget.phosphor_fade = self.phosphor_fade.clone();
                 drop(_guard);
 
+                // the display shares the terminal with the bottom bar, volume bar, and the opcode overlay and status bar if shown
+                let size = terminal.size().context("Failed to get terminal size")?;
+                let reserved_height = 2
+                    + self.opcode_overlay_text.as_ref().map_or(0, |_| 1)
+                    + self.status_bar_text.as_ref().map_or(0, |_| 1);
+                self.zoom_limit = display_widget
+                    .display
+                    .mode
+                    .max_scale(size.width, size.height.saturating_sub(reserved_height));
+                display_widget.scale = self.zoom.min(self.zoom_limit);
+                let zoom_notice = self.zoom_notice_until.map(|_| display_widget.scale);
+
                 terminal.draw(|f| {
-                    self.render_virtual_machine(f, volume, is_dbg_enabled, display_widget);
+                    let frame = VmFrame {
+                        volume,
+                        dbg_key,
+                        logging,
+                        opcode_overlay: self.opcode_overlay_text.as_deref(),
+                        status_bar: self.status_bar_text.as_deref(),
+                        zoom_notice,
+                    };
+                    self.render_virtual_machine(f, display_widget, frame);
                 })?;
             }
         }
 
-        Ok(())
+        Ok(maybe_display)
     }
 
-    fn render_debugger<B: Backend>(&self, f: &mut Frame<B>, dbg: &Debugger, vm: &VM) {
-        let dbg_area = f.size();
+    #[cfg(feature = "debugger")]
+    fn render_debugger<B: Backend>(&self, f: &mut Frame<B>, dbg: &Debugger, vm: &VM, logging: bool) {
         let dbg_widget = DebuggerWidget {
             dbg,
             vm,
-            logging: self.logging,
+            logging,
         };
 
+        if render_terminal_too_small(f, dbg_widget.min_size()) {
+            return;
+        }
+
+        let dbg_area = f.size();
+
         let mut dbg_widget_state = self.dbg_widget_state.take();
 
         if let Some((x, y)) = dbg_widget.cursor_position(dbg_area, &mut dbg_widget_state) {
@@ -178,13 +396,22 @@ impl Renderer {
         self.dbg_widget_state.set(dbg_widget_state);
     }
 
-    fn render_virtual_machine<B: Backend>(
-        &self,
-        f: &mut Frame<B>,
-        volume: f32,
-        is_dbg_enabled: bool,
-        display_widget: DisplayWidget,
-    ) {
+    fn render_virtual_machine<B: Backend>(&self, f: &mut Frame<B>, display_widget: DisplayWidget, frame: VmFrame) {
+        let VmFrame {
+            volume,
+            dbg_key,
+            logging,
+            opcode_overlay,
+            status_bar,
+            zoom_notice,
+        } = frame;
+
+        // the low resolution display and the bottom bar, a high resolution display is cut off rather than hidden
+        let (min_width, min_height) = DisplayMode::LowResolution.window_dimensions();
+        if render_terminal_too_small(f, (min_width, min_height + 1)) {
+            return;
+        }
+
         let area = f.size();
 
         let [area, bottom_area] = Layout::default()
@@ -195,7 +422,10 @@ impl Renderer {
             ])
             .split(area)[..] else { unreachable!() };
 
-        let (display_width, display_height) = display_widget.display.mode.window_dimensions();
+        let (display_width, display_height) = display_widget
+            .display
+            .mode
+            .scaled_window_dimensions(display_widget.scale);
         let [display_column, logger_column, ..] = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
@@ -204,16 +434,20 @@ impl Renderer {
             ])
             .split(area)[..] else { unreachable!() };
 
-        let [display_row, volume_row, logger_row] = Layout::default()
+        let overlay_height = opcode_overlay.map_or(0, |_| 1);
+        let status_height = status_bar.map_or(0, |_| 1);
+        let [display_row, overlay_row, status_row, volume_row, logger_row] = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(display_height),
+                Constraint::Length(overlay_height),
+                Constraint::Length(status_height),
                 Constraint::Length(1),
-                Constraint::Length(area.height.saturating_sub(display_height + 1))
+                Constraint::Length(area.height.saturating_sub(display_height + overlay_height + status_height + 1))
             ])
             .split(area)[..] else { unreachable!() };
 
-        if self.logging {
+        if logging {
             f.render_widget(
                 logger_widget(Borders::ALL),
                 if logger_column.area() >= logger_row.area() {
@@ -256,13 +490,40 @@ impl Renderer {
             // f.render_widget(b, ba);
         }
 
+        let mut display_title = display_widget.build_title();
+        if let Some(zoom) = zoom_notice {
+            display_title.0.push(Span::styled(
+                format!("[zoom {}x]", zoom),
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            ));
+            display_title.0.push(Span::raw(" "));
+        }
+
         let display_block = Block::default()
-            .title(display_widget.build_title())
+            .title(display_title)
             .borders(Borders::ALL);
         let display_area = display_row.intersection(display_column);
         f.render_widget(display_widget, display_block.inner(display_area));
         f.render_widget(display_block, display_area);
 
+        if let Some(text) = opcode_overlay {
+            f.render_widget(
+                Paragraph::new(text)
+                    .block(Block::default().borders(Borders::LEFT.union(Borders::RIGHT)))
+                    .style(Style::default().fg(Color::Gray)),
+                overlay_row.intersection(display_column),
+            );
+        }
+
+        if let Some(text) = status_bar {
+            f.render_widget(
+                Paragraph::new(text)
+                    .block(Block::default().borders(Borders::LEFT.union(Borders::RIGHT)))
+                    .style(Style::default().fg(Color::Gray)),
+                status_row.intersection(display_column),
+            );
+        }
+
         let volume_area = volume_row.intersection(display_column);
         f.render_widget(
             Gauge::default()
@@ -285,10 +546,9 @@ impl Renderer {
 
         f.render_widget(Block::default().style(bottom_area_style), bottom_area);
         f.render_widget(
-            Paragraph::new(if is_dbg_enabled {
-                " Esc to drop into the debugger, Ctrl+C to exit"
-            } else {
-                " Ctrl+C to exit"
+            Paragraph::new(match dbg_key {
+                Some(key) => format!(" {} to drop into the debugger, Ctrl+C to exit", key),
+                None => String::from(" Ctrl+C to exit"),
             })
             .style(bottom_area_style),
             bottom_area,
@@ -296,6 +556,65 @@ impl Renderer {
     }
 }
 
+// the pointers, timers, why the program is stalled if it is, and the keypad keys held down
+// compact enough to fit under the low resolution display
+fn status_bar_text(vm: &VM) -> String {
+    let interp = vm.interpreter();
+    let stall = match vm.stall_reason() {
+        Some(StallReason::WaitingForKey(vx)) => format!(" key->v{:x}", vx),
+        Some(StallReason::Halted) => String::from(" halted"),
+        Some(StallReason::Paused) => String::from(" paused"),
+        Some(StallReason::WaitingForVerticalBlank) | None => String::new(),
+    };
+
+    let (&down_keys, _, _) = vm.keyboard().state();
+    let keys: String = (0..16)
+        .filter(|key| down_keys & 1 << key != 0)
+        .map(|key| char::from_digit(key, 16).unwrap_or('?').to_ascii_uppercase())
+        .collect();
+
+    format!(
+        "pc {:#05X} i {:#05X} dt {:03} st {:03}{} keys {}",
+        interp.pc,
+        interp.index,
+        vm.delay_timer(),
+        vm.sound_timer(),
+        stall,
+        if keys.is_empty() { "-" } else { &keys }
+    )
+}
+
+// the pc and the instruction there, which is the next to execute
+fn opcode_overlay_text(interp: &Interpreter) -> String {
+    let Some(instruction) = interp.instruction() else {
+        return format!("{:#05X} ???", interp.pc);
+    };
+
+    let mut asm = String::new();
+    write_inst_dasm(&instruction, interp.rom.config, &mut asm, &mut String::new()).ok();
+    format!("{:#05X} {}", interp.pc, asm.trim_end())
+}
+
+// renders a notice instead of the interface if the terminal can't fit its minimum size
+fn render_terminal_too_small<B: Backend>(f: &mut Frame<B>, (min_width, min_height): (u16, u16)) -> bool {
+    let area = f.size();
+
+    if area.width >= min_width && area.height >= min_height {
+        return false;
+    }
+
+    f.render_widget(
+        Paragraph::new(format!(
+            "Terminal too small ({}x{}), resize to at least {}x{}",
+            area.width, area.height, min_width, min_height
+        ))
+        .wrap(Wrap { trim: true }),
+        area,
+    );
+
+    true
+}
+
 pub fn logger_widget(borders: Borders) -> TuiLoggerWidget<'static> {
     TuiLoggerWidget::default()
         .block(
@@ -316,3 +635,77 @@ pub fn logger_widget(borders: Borders) -> TuiLoggerWidget<'static> {
         .style_trace(Style::default().fg(Color::White))
         .style_info(Style::default().fg(Color::Green))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch8::{audio::silent_audio_controller, rom::{Rom, RomKind}};
+
+    use tui::backend::TestBackend;
+
+    fn renderer() -> Renderer {
+        Renderer::new(false, RenderOptions::default())
+    }
+
+    fn vm() -> VM {
+        let kind = RomKind::CHIP8;
+        VM::new(
+            Rom::with_program(kind, &[0x12, 0x00]),
+            kind.default_memory_size(),
+            kind.default_cycles_per_frame(),
+            silent_audio_controller(),
+        )
+    }
+
+    fn test_terminal(width: u16, height: u16) -> tui::Terminal<TestBackend> {
+        tui::Terminal::new(TestBackend::new(width, height)).expect("Failed to create test terminal")
+    }
+
+    fn first_row(terminal: &tui::Terminal<TestBackend>) -> String {
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.width).map(|x| buffer.get(x, 0).symbol.as_str()).collect()
+    }
+
+    #[test]
+    fn one_cell_terminal_renders_the_too_small_notice() {
+        let renderer = renderer();
+        let vm = vm();
+
+        let mut terminal = test_terminal(1, 1);
+        terminal
+            .draw(|f| {
+                let frame = VmFrame {
+                    volume: 1.0,
+                    dbg_key: None,
+                    logging: false,
+                    opcode_overlay: None,
+                    status_bar: None,
+                    zoom_notice: None,
+                };
+                renderer.render_virtual_machine(f, vm.to_display_widget(), frame)
+            })
+            .expect("Failed to draw");
+        assert_eq!(first_row(&terminal), "T");
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn debugger_renders_the_too_small_notice_when_its_panels_do_not_fit() {
+        let renderer = renderer();
+        let vm = vm();
+        let dbg = Debugger::new(&vm, 600);
+
+        let mut terminal = test_terminal(1, 1);
+        terminal.draw(|f| renderer.render_debugger(f, &dbg, &vm, false)).expect("Failed to draw");
+        assert_eq!(first_row(&terminal), "T");
+
+        // big enough for the display alone but not the state panels beside it
+        let mut terminal = test_terminal(80, 24);
+        terminal.draw(|f| renderer.render_debugger(f, &dbg, &vm, false)).expect("Failed to draw");
+        assert!(
+            first_row(&terminal).starts_with("Terminal too small (80x24), resize to at least 81x38"),
+            "{}",
+            first_row(&terminal)
+        );
+    }
+}
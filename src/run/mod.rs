@@ -2,7 +2,7 @@ pub mod color;
 pub mod preset;
 
 use crate::{ch8::{
-    input::Key,
+    input::{Key, KeypadLayout},
     run::{RunResult, Runner},
     vm::VMEvent,
 }, render::RenderController};
@@ -10,7 +10,7 @@ use crate::{ch8::{
 use crossterm::event::{
     poll, read, Event, KeyCode as CrosstermKey, KeyEventKind, KeyModifiers as CrosstermKeyModifiers,
 };
-use device_query::DeviceQuery;
+use device_query::{DeviceQuery, Keycode as DeviceKey};
 
 use std::{
     collections::HashSet,
@@ -19,7 +19,183 @@ use std::{
     time::Duration
 };
 
-pub fn spawn_run_thread(mut runner: Runner, render: RenderController, debugging: bool, logging: bool) -> JoinHandle<RunResult> {
+// the input loop also samples the keyboard each time the poll returns so this bounds key latency too
+// below a millisecond the loop is effectively a busy wait and above a second exiting and resizing feel broken
+pub const DEFAULT_POLL_TIMEOUT_MS: u64 = 15;
+pub const MIN_POLL_TIMEOUT_MS: u64 = 1;
+pub const MAX_POLL_TIMEOUT_MS: u64 = 1000;
+
+pub const DEFAULT_DEBUG_KEY: CrosstermKey = CrosstermKey::Esc;
+pub const OPCODE_OVERLAY_KEY: CrosstermKey = CrosstermKey::Tab;
+pub const DEFAULT_TURBO_KEY: CrosstermKey = CrosstermKey::Char(' ');
+pub const DEFAULT_TURBO_MULTIPLIER: u32 = 10;
+pub const VOLUME_DOWN_KEY: CrosstermKey = CrosstermKey::Char('-');
+pub const VOLUME_UP_KEY: CrosstermKey = CrosstermKey::Char('=');
+pub const ZOOM_IN_KEY: CrosstermKey = CrosstermKey::Char('+');
+pub const ZOOM_OUT_KEY: CrosstermKey = CrosstermKey::Char('_');
+pub const QUIT_KEY: CrosstermKey = CrosstermKey::Char('c'); // with Ctrl
+pub const SUSPEND_KEY: CrosstermKey = CrosstermKey::Char('z'); // with Ctrl
+
+// steps a frame from any debugger panel since it is pressed repeatedly to watch an animation
+pub const FRAME_STEP_KEY: CrosstermKey = CrosstermKey::F(10);
+
+// every fixed key c8 binds besides the keypad and the turbo key, named for warning when a remapped key shadows one
+pub const BOUND_KEYS: [(CrosstermKey, &str); 8] = [
+    (VOLUME_DOWN_KEY, "volume down"),
+    (VOLUME_UP_KEY, "volume up"),
+    (ZOOM_IN_KEY, "zoom in"),
+    (ZOOM_OUT_KEY, "zoom out"),
+    (OPCODE_OVERLAY_KEY, "opcode overlay"),
+    (FRAME_STEP_KEY, "frame step"),
+    (QUIT_KEY, "Ctrl+C quit"),
+    (SUSPEND_KEY, "Ctrl+Z suspend"),
+];
+
+// keys are named the way crossterm reports them so a debug key can be any single character or common named key
+pub fn parse_key_code(value: &str) -> Result<CrosstermKey, String> {
+    let mut chars = value.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(CrosstermKey::Char(c.to_ascii_lowercase()));
+    }
+
+    let name = value.to_ascii_lowercase();
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+        if (1..=12).contains(&n) {
+            return Ok(CrosstermKey::F(n));
+        }
+    }
+
+    match name.as_str() {
+        "space" => Ok(CrosstermKey::Char(' ')),
+        "esc" | "escape" => Ok(CrosstermKey::Esc),
+        "tab" => Ok(CrosstermKey::Tab),
+        "backspace" => Ok(CrosstermKey::Backspace),
+        "enter" => Ok(CrosstermKey::Enter),
+        "insert" => Ok(CrosstermKey::Insert),
+        "delete" => Ok(CrosstermKey::Delete),
+        "home" => Ok(CrosstermKey::Home),
+        "end" => Ok(CrosstermKey::End),
+        "pageup" => Ok(CrosstermKey::PageUp),
+        "pagedown" => Ok(CrosstermKey::PageDown),
+        _ => Err(format!(
+            "unknown key \"{}\" (expected a single character, space, esc, tab, backspace, enter, insert, delete, home, end, pageup, pagedown, or f1 to f12)",
+            value
+        )),
+    }
+}
+
+pub fn key_code_name(key: CrosstermKey) -> String {
+    match key {
+        CrosstermKey::Char(' ') => String::from("Space"),
+        CrosstermKey::Char(c) => c.to_ascii_uppercase().to_string(),
+        CrosstermKey::F(n) => format!("F{}", n),
+        key => format!("{:?}", key),
+    }
+}
+
+// terminals only report presses so a key that is held (turbo) is found by sampling the keyboard instead
+// which needs the physical key, None when there is no equivalent
+pub fn device_key(key: CrosstermKey) -> Option<DeviceKey> {
+    let device_key = match key {
+        CrosstermKey::Char(c) => match c.to_ascii_lowercase() {
+            ' ' => DeviceKey::Space,
+            '`' => DeviceKey::Grave,
+            '-' => DeviceKey::Minus,
+            '=' => DeviceKey::Equal,
+            '[' => DeviceKey::LeftBracket,
+            ']' => DeviceKey::RightBracket,
+            '\\' => DeviceKey::BackSlash,
+            ';' => DeviceKey::Semicolon,
+            '\'' => DeviceKey::Apostrophe,
+            ',' => DeviceKey::Comma,
+            '.' => DeviceKey::Dot,
+            '/' => DeviceKey::Slash,
+            '0' => DeviceKey::Key0,
+            '1' => DeviceKey::Key1,
+            '2' => DeviceKey::Key2,
+            '3' => DeviceKey::Key3,
+            '4' => DeviceKey::Key4,
+            '5' => DeviceKey::Key5,
+            '6' => DeviceKey::Key6,
+            '7' => DeviceKey::Key7,
+            '8' => DeviceKey::Key8,
+            '9' => DeviceKey::Key9,
+            'a' => DeviceKey::A,
+            'b' => DeviceKey::B,
+            'c' => DeviceKey::C,
+            'd' => DeviceKey::D,
+            'e' => DeviceKey::E,
+            'f' => DeviceKey::F,
+            'g' => DeviceKey::G,
+            'h' => DeviceKey::H,
+            'i' => DeviceKey::I,
+            'j' => DeviceKey::J,
+            'k' => DeviceKey::K,
+            'l' => DeviceKey::L,
+            'm' => DeviceKey::M,
+            'n' => DeviceKey::N,
+            'o' => DeviceKey::O,
+            'p' => DeviceKey::P,
+            'q' => DeviceKey::Q,
+            'r' => DeviceKey::R,
+            's' => DeviceKey::S,
+            't' => DeviceKey::T,
+            'u' => DeviceKey::U,
+            'v' => DeviceKey::V,
+            'w' => DeviceKey::W,
+            'x' => DeviceKey::X,
+            'y' => DeviceKey::Y,
+            'z' => DeviceKey::Z,
+            _ => return None,
+        },
+        CrosstermKey::F(n) => match n {
+            1 => DeviceKey::F1,
+            2 => DeviceKey::F2,
+            3 => DeviceKey::F3,
+            4 => DeviceKey::F4,
+            5 => DeviceKey::F5,
+            6 => DeviceKey::F6,
+            7 => DeviceKey::F7,
+            8 => DeviceKey::F8,
+            9 => DeviceKey::F9,
+            10 => DeviceKey::F10,
+            11 => DeviceKey::F11,
+            12 => DeviceKey::F12,
+            _ => return None,
+        },
+        CrosstermKey::Esc => DeviceKey::Escape,
+        CrosstermKey::Tab => DeviceKey::Tab,
+        CrosstermKey::Backspace => DeviceKey::Backspace,
+        CrosstermKey::Enter => DeviceKey::Enter,
+        CrosstermKey::Insert => DeviceKey::Insert,
+        CrosstermKey::Delete => DeviceKey::Delete,
+        CrosstermKey::Home => DeviceKey::Home,
+        CrosstermKey::End => DeviceKey::End,
+        CrosstermKey::PageUp => DeviceKey::PageUp,
+        CrosstermKey::PageDown => DeviceKey::PageDown,
+        _ => return None,
+    };
+
+    Some(device_key)
+}
+
+// characters are compared ignoring case since shift or caps lock changes the reported character
+pub fn key_code_matches(key: CrosstermKey, target: CrosstermKey) -> bool {
+    match (key, target) {
+        (CrosstermKey::Char(a), CrosstermKey::Char(b)) => a.eq_ignore_ascii_case(&b),
+        (key, target) => key == target,
+    }
+}
+
+pub fn spawn_run_thread(
+    mut runner: Runner,
+    render: RenderController,
+    debugging: bool,
+    logging: bool,
+    poll_timeout: Duration,
+    keypad: KeypadLayout,
+    turbo: (DeviceKey, u32),
+) -> JoinHandle<RunResult> {
 
     // main thread
     let c8 = runner.c8();
@@ -27,17 +203,28 @@ pub fn spawn_run_thread(mut runner: Runner, render: RenderController, debugging:
 
     let main_thread = thread::spawn(move || -> RunResult {
         let device_state = device_query::DeviceState::new();
-        let mut last_keys = HashSet::new();
+        let mut last_keys: HashSet<Key> = HashSet::new();
+        let (turbo_key, turbo_multiplier) = turbo;
+        let mut turbo_held = false;
 
         // start runner
         if !debugging {
             runner.resume().expect("Unable to resume runner");
+        } else {
+            // commands given at startup run first, and may resume the program themselves
+            let mut _guard = c8.lock().expect("Unable to lock c8");
+            let (vm, Some(dbg)) = _guard.deref_mut() else {
+                unreachable!("Debug runs should contain a debugger");
+            };
+            dbg.run_queued_commands(&mut runner, vm);
+            drop(_guard);
+            render.trigger();
         }
 
         loop {
             // event loop
             let terminal_event_received =
-                poll(Duration::from_millis(15)).expect("Unable to poll for terminal events");
+                poll(poll_timeout).expect("Unable to poll for terminal events");
 
             if runner.is_finished() {
                 return runner.exit();
@@ -47,6 +234,18 @@ pub fn spawn_run_thread(mut runner: Runner, render: RenderController, debugging:
                 let event = read().expect("Unable to read terminal event");
                 let mut sink_vm_events = false;
 
+                // Ctrl+Z suspends like it would outside of raw mode and is never seen by the program or debugger
+                if let Event::Key(key_event) = &event {
+                    if key_event.modifiers.contains(CrosstermKeyModifiers::CONTROL)
+                        && key_code_matches(key_event.code, SUSPEND_KEY)
+                    {
+                        if let KeyEventKind::Press = key_event.kind {
+                            render.suspend();
+                        }
+                        continue;
+                    }
+                }
+
                 if debugging {
                     let mut _guard = c8.lock().expect("Unable to lock c8");
                     let (vm, Some(dbg)) = _guard.deref_mut() else {
@@ -55,7 +254,7 @@ pub fn spawn_run_thread(mut runner: Runner, render: RenderController, debugging:
 
                     sink_vm_events = sink_vm_events || dbg.is_active();
 
-                    if dbg.handle_input_event(event.clone(), &mut runner, vm) {
+                    if dbg.handle_input_event(event.clone(), &mut runner, vm, &last_keys) {
                         render.trigger();
                     }
 
@@ -84,30 +283,53 @@ pub fn spawn_run_thread(mut runner: Runner, render: RenderController, debugging:
                         // Esc or Crtl+C interrupt handler
                         if (key_event.code == CrosstermKey::Esc && !sink_vm_events) // Esc is an exit if debugger isnt sinking keys
                             || key_event.modifiers.contains(CrosstermKeyModifiers::CONTROL) // Ctrl+C is a hard exit
-                                && (key_event.code == CrosstermKey::Char('c')
-                                    || key_event.code == CrosstermKey::Char('C'))
+                                && key_code_matches(key_event.code, QUIT_KEY)
                         {
                             // exit virtual machine
                             return runner.exit();
                         } else if !sink_vm_events {
                             match key_event.code {
-                                CrosstermKey::Char('-') => {
+                                VOLUME_DOWN_KEY => {
                                     vm_event_sender.send(VMEvent::VolumeChange(false)).ok();
                                 }
-                                CrosstermKey::Char('=') => {
+                                VOLUME_UP_KEY => {
                                     vm_event_sender.send(VMEvent::VolumeChange(true)).ok();
                                 }
+                                ZOOM_IN_KEY => {
+                                    if let KeyEventKind::Press = key_event.kind {
+                                        render.zoom(true);
+                                    }
+                                }
+                                ZOOM_OUT_KEY => {
+                                    if let KeyEventKind::Press = key_event.kind {
+                                        render.zoom(false);
+                                    }
+                                }
+                                OPCODE_OVERLAY_KEY => {
+                                    if let KeyEventKind::Press = key_event.kind {
+                                        render.toggle_opcode_overlay();
+                                    }
+                                }
                                 _ => {
                                     // kinda expecting a crossterm key event to mean renderer is in focus
                                     if let KeyEventKind::Repeat | KeyEventKind::Press =
                                         key_event.kind
                                     {
-                                        if let Ok(key) = Key::try_from(key_event.code) {
+                                        if let Some(key) = keypad.map_crossterm_key(key_event.code) {
                                             vm_event_sender
                                                 .send(VMEvent::FocusingKeyDown(key))
                                                 .expect(
                                                     "Unable to send VM focusing key down event",
                                                 );
+
+                                            // the terminal queues presses but device_query only samples, so a tap
+                                            // shorter than the poll timeout is only seen here. marking it as down
+                                            // lets the next sample release it if it is already up
+                                            if last_keys.insert(key) {
+                                                vm_event_sender
+                                                    .send(VMEvent::KeyDown(key))
+                                                    .expect("Unable to send VM key down event");
+                                            }
                                         }
                                     }
                                 }
@@ -119,11 +341,21 @@ pub fn spawn_run_thread(mut runner: Runner, render: RenderController, debugging:
             }
 
             // execute device query step
+            let device_keys = device_state.get_keys();
+
+            // the speed only changes on press and release so the run thread is not flooded while it is held
+            if device_keys.contains(&turbo_key) != turbo_held {
+                turbo_held = !turbo_held;
+                let multiplier = if turbo_held { turbo_multiplier } else { 1 };
+                if let Err(e) = runner.set_speed_multiplier(multiplier) {
+                    log::warn!("Failed to change speed multiplier: {}", e);
+                }
+            }
+
             let keys = HashSet::from_iter(
-                device_state
-                    .get_keys()
+                device_keys
                     .into_iter()
-                    .filter_map(|keycode| Key::try_from(keycode).ok()),
+                    .filter_map(|keycode| keypad.map_device_key(keycode)),
             );
 
             for &key in keys.difference(&last_keys) {
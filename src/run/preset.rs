@@ -9,7 +9,7 @@ pub const COLOR_PRESETS: [(&'static str, [Color; 16]); 6] = [
     CGA1_COLOR_PRESET,
 ];
 
-const GRAYSCALE_COLOR_PRESET: (&'static str, [Color; 16]) = (
+pub const GRAYSCALE_COLOR_PRESET: (&'static str, [Color; 16]) = (
     "Grayscale",
     [
         Color::Rgb(000, 000, 000),
@@ -31,7 +31,7 @@ const GRAYSCALE_COLOR_PRESET: (&'static str, [Color; 16]) = (
     ],
 );
 
-const OCTO_COLOR_PRESET: (&'static str, [Color; 16]) = (
+pub const OCTO_COLOR_PRESET: (&'static str, [Color; 16]) = (
     "Octo",
     [
         Color::Rgb(153, 102, 000),
@@ -53,7 +53,7 @@ const OCTO_COLOR_PRESET: (&'static str, [Color; 16]) = (
     ],
 );
 
-const LCD_COLOR_PRESET: (&'static str, [Color; 16]) = (
+pub const LCD_COLOR_PRESET: (&'static str, [Color; 16]) = (
     "LCD",
     [
         Color::Rgb(0xF9, 0xFF, 0xB3),
@@ -75,7 +75,7 @@ const LCD_COLOR_PRESET: (&'static str, [Color; 16]) = (
     ],
 );
 
-const HOTDOG_COLOR_PRESET: (&'static str, [Color; 16]) = (
+pub const HOTDOG_COLOR_PRESET: (&'static str, [Color; 16]) = (
     "Hot Dog",
     [
         Color::Rgb(0x00, 0x00, 0x00),
@@ -97,7 +97,7 @@ const HOTDOG_COLOR_PRESET: (&'static str, [Color; 16]) = (
     ],
 );
 
-const CGA0_COLOR_PRESET: (&'static str, [Color; 16]) = (
+pub const CGA0_COLOR_PRESET: (&'static str, [Color; 16]) = (
     "CGA 0",
     [
         Color::Rgb(0x00, 0x00, 0x00),
@@ -119,7 +119,7 @@ const CGA0_COLOR_PRESET: (&'static str, [Color; 16]) = (
     ],
 );
 
-const CGA1_COLOR_PRESET: (&'static str, [Color; 16]) = (
+pub const CGA1_COLOR_PRESET: (&'static str, [Color; 16]) = (
     "CGA 1",
     [
         Color::Rgb(0x00, 0x00, 0x00),
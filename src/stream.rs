@@ -0,0 +1,179 @@
+use crate::ch8::disp::Display;
+
+use std::{
+    io::{self, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{channel, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+// Display frames are published to every connected client as a length-prefixed binary message:
+//
+//   u32  length of the rest of the message in bytes (big endian)
+//   u16  display width in pixels (big endian)
+//   u16  display height in pixels (big endian)
+//   u8   number of planes
+//   [u8] for each plane, one row after another, each row being width / 8 bytes
+//        with the left-most pixel in the most significant bit of the first byte
+//
+// A client is sent the latest frame as soon as it connects and a new frame every time the display changes
+
+// a client that can't keep up is dropped instead of holding back the others
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(50);
+
+// frames are written to clients on the stream's own thread so a slow client never holds up the render thread
+pub struct DisplayStream {
+    frame_sender: Sender<Vec<u8>>,
+}
+
+impl DisplayStream {
+    pub fn publish(&self, display: &Display) {
+        let mut frame = Vec::new();
+        encode_display_frame(display, &mut frame);
+
+        // the writer only stops if it panicked, and then there is nobody left to stream to
+        self.frame_sender.send(frame).ok();
+    }
+}
+
+pub fn spawn_display_stream<A: ToSocketAddrs>(addr: A) -> io::Result<DisplayStream> {
+    let listener = TcpListener::bind(addr)?;
+    let clients = Arc::new(Mutex::new(Vec::<TcpStream>::new()));
+    let frame = Arc::new(Mutex::new(Vec::new()));
+    let (frame_sender, frame_receiver) = channel::<Vec<u8>>();
+
+    let writer_clients = Arc::clone(&clients);
+    let writer_frame = Arc::clone(&frame);
+    thread::spawn(move || {
+        while let Ok(next_frame) = frame_receiver.recv() {
+            // frames published while the last one was being written are skipped in favor of the latest
+            let next_frame = frame_receiver.try_iter().last().unwrap_or(next_frame);
+
+            // lock order must match the listener so a client can't miss a frame between the two
+            let mut clients = writer_clients
+                .lock()
+                .expect("Failed to lock display stream clients");
+            let mut frame = writer_frame
+                .lock()
+                .expect("Failed to lock display stream frame");
+            *frame = next_frame;
+
+            clients.retain_mut(|client| match client.write_all(&frame) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::info!("Dropped display stream client: {}", e);
+                    false
+                }
+            });
+        }
+    });
+
+    let listener_clients = Arc::clone(&clients);
+    let listener_frame = Arc::clone(&frame);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Failed to accept display stream client: {}", e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = stream
+                .set_nodelay(true)
+                .and_then(|_| stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)))
+            {
+                log::warn!("Failed to configure display stream client: {}", e);
+                continue;
+            }
+
+            // lock order must match the writer so the client can't miss a frame between the two
+            let mut clients = listener_clients
+                .lock()
+                .expect("Failed to lock display stream clients");
+            let frame = listener_frame
+                .lock()
+                .expect("Failed to lock display stream frame");
+
+            if !frame.is_empty() {
+                if let Err(e) = stream.write_all(&frame) {
+                    log::info!("Dropped display stream client: {}", e);
+                    continue;
+                }
+            }
+
+            log::info!(
+                "Display stream client connected from {}",
+                stream
+                    .peer_addr()
+                    .map_or("an unknown address".into(), |addr| addr.to_string())
+            );
+            clients.push(stream);
+        }
+    });
+
+    Ok(DisplayStream { frame_sender })
+}
+
+fn encode_display_frame(display: &Display, frame: &mut Vec<u8>) {
+    let (width, height) = display.mode.dimensions();
+    let bytes_per_row = width as usize / 8;
+    let len = 5 + display.planes.len() * height as usize * bytes_per_row;
+
+    frame.clear();
+    frame.reserve(4 + len);
+    frame.extend_from_slice(&(len as u32).to_be_bytes());
+    frame.extend_from_slice(&width.to_be_bytes());
+    frame.extend_from_slice(&height.to_be_bytes());
+    frame.push(display.planes.len() as u8);
+    for plane in display.planes.iter() {
+        for row in plane.iter().take(height as usize) {
+            frame.extend_from_slice(&row.to_be_bytes()[..bytes_per_row]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch8::disp::DisplayMode;
+
+    // the rows of the plane at the index, as sent, each row being width / 8 bytes
+    fn plane_rows(frame: &[u8], plane: usize, width: usize, height: usize) -> Vec<&[u8]> {
+        let plane_start = 9 + plane * height * width / 8;
+        frame[plane_start..plane_start + height * width / 8]
+            .chunks(width / 8)
+            .collect()
+    }
+
+    #[test]
+    fn frames_are_length_prefixed_with_rows_sized_to_the_display_mode() {
+        // the left-most pixel of the first row and the right-most pixel of the last
+        let mut display = Display::default();
+        display.planes[0][0] = 1 << 127;
+        display.planes[1][31] = 1 << 64;
+
+        let mut frame = Vec::new();
+        encode_display_frame(&display, &mut frame);
+        assert_eq!(frame.len(), 4 + 5 + 4 * 32 * 8);
+        assert_eq!(frame[..4], (5 + 4 * 32 * 8 as u32).to_be_bytes());
+        assert_eq!(frame[4..9], [0, 64, 0, 32, 4]);
+        assert_eq!(plane_rows(&frame, 0, 64, 32)[0], [0x80, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(plane_rows(&frame, 1, 64, 32)[31], [0, 0, 0, 0, 0, 0, 0, 0x01]);
+
+        display.mode = DisplayMode::HighResolution;
+        display.planes[3][63] = 1 << 127 | 1;
+
+        encode_display_frame(&display, &mut frame);
+        assert_eq!(frame.len(), 4 + 5 + 4 * 64 * 16);
+        assert_eq!(frame[..4], (5 + 4 * 64 * 16 as u32).to_be_bytes());
+        assert_eq!(frame[4..9], [0, 128, 0, 64, 4]);
+        assert_eq!(plane_rows(&frame, 0, 128, 64)[0], [0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(plane_rows(&frame, 3, 128, 64)[63], [0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+    }
+}
@@ -0,0 +1,55 @@
+// Tests assert on diagnostics that are only ever logged, so a logger is installed that keeps each thread's records
+// apart since tests run in parallel and the logger is global
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use std::{cell::RefCell, sync::Once};
+
+thread_local! {
+    static RECORDS: RefCell<Vec<(Level, String)>> = RefCell::new(Vec::new());
+}
+
+struct CaptureLogger;
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        RECORDS.with(|records| records.borrow_mut().push((record.level(), record.args().to_string())));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger;
+static INSTALL: Once = Once::new();
+
+// starts capturing on this thread, discarding anything captured before
+pub fn capture() {
+    INSTALL.call_once(|| {
+        log::set_logger(&LOGGER).expect("Failed to install the test logger");
+        log::set_max_level(LevelFilter::Trace);
+    });
+    RECORDS.with(|records| records.borrow_mut().clear());
+}
+
+pub fn warnings() -> Vec<String> {
+    messages(Level::Warn)
+}
+
+pub fn traces() -> Vec<String> {
+    messages(Level::Trace)
+}
+
+fn messages(level: Level) -> Vec<String> {
+    RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(record_level, _)| *record_level == level)
+            .map(|(_, message)| message.clone())
+            .collect()
+    })
+}